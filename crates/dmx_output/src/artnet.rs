@@ -0,0 +1,883 @@
+use anyhow::{anyhow, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fmt, io, iter,
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+};
+
+use crate::{
+    rdm::Uid,
+    routing::Route,
+    sink::{DmxSink, SinkConfig, SinkKind},
+    universe::{DmxFrame, UniverseId, UNIVERSE_SIZE},
+};
+
+pub const ARTNET_PORT: u16 = 6454;
+pub const PROTOCOL_VERSION: u16 = 14;
+
+const ARTNET_ID: &[u8; 8] = b"Art-Net\0";
+const ART_DMX_HEADER_LEN: usize = 18;
+/// The length of the fields that ArtTodRequest, ArtTodData, ArtTodControl
+/// and ArtRdm have in common, up to and including the address.
+const ART_RDM_HEADER_LEN: usize = 24;
+const RDM_VERSION: u8 = 0x01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u16)]
+pub enum OpCode {
+    Poll = 0x2000,
+    PollReply = 0x2100,
+    Dmx = 0x5000,
+    Sync = 0x5200,
+    TodRequest = 0x8000,
+    TodData = 0x8100,
+    TodControl = 0x8200,
+    Rdm = 0x8300,
+}
+
+impl OpCode {
+    pub fn from_u16(value: u16) -> Option<Self> {
+        match value {
+            0x2000 => Some(OpCode::Poll),
+            0x2100 => Some(OpCode::PollReply),
+            0x5000 => Some(OpCode::Dmx),
+            0x5200 => Some(OpCode::Sync),
+            0x8000 => Some(OpCode::TodRequest),
+            0x8100 => Some(OpCode::TodData),
+            0x8200 => Some(OpCode::TodControl),
+            0x8300 => Some(OpCode::Rdm),
+            _ => None,
+        }
+    }
+}
+
+/// A 15-bit Art-Net 4 port address, made up of a 7-bit Net,
+/// a 4-bit Sub-Net and a 4-bit Universe.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+pub struct PortAddress {
+    pub net: u8,
+    pub sub_net: u8,
+    pub universe: u8,
+}
+
+impl PortAddress {
+    pub const MAX: u16 = 0x7fff;
+
+    pub fn new(net: u8, sub_net: u8, universe: u8) -> Result<Self> {
+        if net > 0x7f || sub_net > 0xf || universe > 0xf {
+            return Err(anyhow!(
+                "invalid Art-Net port address {net}:{sub_net}:{universe}"
+            ));
+        }
+        Ok(Self {
+            net,
+            sub_net,
+            universe,
+        })
+    }
+
+    pub fn from_u16(value: u16) -> Result<Self> {
+        if value > Self::MAX {
+            return Err(anyhow!("Art-Net port address {value} is out of range"));
+        }
+        Ok(Self {
+            net: (value >> 8) as u8,
+            sub_net: ((value >> 4) & 0xf) as u8,
+            universe: (value & 0xf) as u8,
+        })
+    }
+
+    pub fn to_u16(self) -> u16 {
+        (self.net as u16) << 8 | (self.sub_net as u16) << 4 | self.universe as u16
+    }
+
+    /// The low byte of the port address, as carried in the `SubUni` field.
+    pub fn sub_uni(self) -> u8 {
+        self.sub_net << 4 | self.universe
+    }
+}
+
+impl fmt::Display for PortAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.net, self.sub_net, self.universe)
+    }
+}
+
+/// Writes the common Art-Net header: the ID, the little-endian OpCode and,
+/// for every packet but ArtPollReply, the big-endian protocol version.
+pub(crate) fn write_header(op_code: OpCode, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(ARTNET_ID);
+    buf.extend_from_slice(&(op_code as u16).to_le_bytes());
+    buf.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+}
+
+/// Returns the OpCode of an Art-Net packet, or `None` if the packet is not
+/// Art-Net or has an OpCode we don't handle.
+pub fn parse_op_code(packet: &[u8]) -> Option<OpCode> {
+    if packet.len() < 10 || &packet[..8] != ARTNET_ID {
+        return None;
+    }
+    OpCode::from_u16(u16::from_le_bytes([packet[8], packet[9]]))
+}
+
+pub struct ArtDmx<'a> {
+    pub sequence: u8,
+    pub physical: u8,
+    pub port_address: PortAddress,
+    pub data: &'a [u8],
+}
+
+impl<'a> ArtDmx<'a> {
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        // The length must be even and between 2 and 512.
+        let data = &self.data[..self.data.len().min(512)];
+        let length = (data.len().max(2) + 1) & !1;
+
+        buf.reserve(ART_DMX_HEADER_LEN + length);
+        write_header(OpCode::Dmx, buf);
+        buf.push(self.sequence);
+        buf.push(self.physical);
+        buf.push(self.port_address.sub_uni());
+        buf.push(self.port_address.net);
+        buf.extend_from_slice(&(length as u16).to_be_bytes());
+        buf.extend_from_slice(data);
+        buf.resize(buf.len() + length - data.len(), 0);
+    }
+
+    pub fn parse(packet: &'a [u8]) -> Result<Self> {
+        if parse_op_code(packet) != Some(OpCode::Dmx) {
+            return Err(anyhow!("not an ArtDmx packet"));
+        }
+        if packet.len() < ART_DMX_HEADER_LEN {
+            return Err(anyhow!("ArtDmx packet is too short"));
+        }
+        let length = u16::from_be_bytes([packet[16], packet[17]]) as usize;
+        let end = (ART_DMX_HEADER_LEN + length.min(UNIVERSE_SIZE)).min(packet.len());
+        Ok(Self {
+            sequence: packet[12],
+            physical: packet[13],
+            port_address: PortAddress {
+                net: packet[15] & 0x7f,
+                sub_net: packet[14] >> 4,
+                universe: packet[14] & 0xf,
+            },
+            data: &packet[ART_DMX_HEADER_LEN..end],
+        })
+    }
+}
+
+/// Tells nodes to output the ArtDmx packets they have been holding, so
+/// that every universe changes at once.
+pub struct ArtSync;
+
+impl ArtSync {
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        write_header(OpCode::Sync, buf);
+        // Aux1 and Aux2.
+        buf.extend_from_slice(&[0, 0]);
+    }
+}
+
+/// Asks nodes for the table of devices (TOD) they have discovered on the
+/// ports with the given addresses within `net`.
+pub struct ArtTodRequest {
+    pub net: u8,
+    /// The low bytes of the port addresses, at most 32.
+    pub addresses: Vec<u8>,
+}
+
+impl ArtTodRequest {
+    const TOD_FULL: u8 = 0x00;
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let addresses = &self.addresses[..self.addresses.len().min(32)];
+        write_rdm_header(OpCode::TodRequest, 0, self.net, Self::TOD_FULL, buf);
+        buf.push(addresses.len() as u8);
+        buf.extend_from_slice(addresses);
+        buf.resize(buf.len() + 32 - addresses.len(), 0);
+    }
+}
+
+/// A node's table of devices for one of its ports. Long tables are split
+/// across several packets, each holding part of the UIDs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArtTodData {
+    pub port: u8,
+    pub bind_index: u8,
+    pub port_address: PortAddress,
+    /// Whether the node could not send its table.
+    pub nak: bool,
+    pub uid_total: u16,
+    pub block_count: u8,
+    pub uids: Vec<Uid>,
+}
+
+impl ArtTodData {
+    const TOD_NAK: u8 = 0xff;
+
+    pub fn parse(packet: &[u8]) -> Result<Self> {
+        if parse_op_code(packet) != Some(OpCode::TodData) {
+            return Err(anyhow!("not an ArtTodData packet"));
+        }
+        if packet.len() < ART_RDM_HEADER_LEN + 4 {
+            return Err(anyhow!("ArtTodData packet is too short"));
+        }
+        let uid_count = packet[27] as usize;
+        let uids = packet[28..]
+            .chunks_exact(6)
+            .take(uid_count)
+            .map(|uid| Uid::from_bytes(uid.try_into().unwrap()))
+            .collect();
+        Ok(Self {
+            port: packet[13],
+            bind_index: packet[20],
+            port_address: PortAddress {
+                net: packet[21] & 0x7f,
+                sub_net: packet[23] >> 4,
+                universe: packet[23] & 0xf,
+            },
+            nak: packet[22] == Self::TOD_NAK,
+            uid_total: u16::from_be_bytes([packet[24], packet[25]]),
+            block_count: packet[26],
+            uids,
+        })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let uids = &self.uids[..self.uids.len().min(200)];
+        write_header(OpCode::TodData, buf);
+        buf.push(RDM_VERSION);
+        buf.push(self.port);
+        buf.extend_from_slice(&[0; 6]);
+        buf.push(self.bind_index);
+        buf.push(self.port_address.net);
+        buf.push(if self.nak { Self::TOD_NAK } else { 0 });
+        buf.push(self.port_address.sub_uni());
+        buf.extend_from_slice(&self.uid_total.to_be_bytes());
+        buf.push(self.block_count);
+        buf.push(uids.len() as u8);
+        for uid in uids {
+            buf.extend_from_slice(&uid.to_bytes());
+        }
+    }
+}
+
+/// Tells a node to forget its table of devices and discover them again.
+pub struct ArtTodControl {
+    pub port_address: PortAddress,
+}
+
+impl ArtTodControl {
+    const FLUSH: u8 = 0x01;
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        write_rdm_header(
+            OpCode::TodControl,
+            0,
+            self.port_address.net,
+            Self::FLUSH,
+            buf,
+        );
+        buf.push(self.port_address.sub_uni());
+    }
+}
+
+/// An RDM message to or from the device behind a node's port.
+pub struct ArtRdm<'a> {
+    pub port_address: PortAddress,
+    /// The RDM message without its start code.
+    pub message: &'a [u8],
+}
+
+impl<'a> ArtRdm<'a> {
+    const PROCESS: u8 = 0x00;
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        write_rdm_header(
+            OpCode::Rdm,
+            RDM_VERSION,
+            self.port_address.net,
+            Self::PROCESS,
+            buf,
+        );
+        buf.push(self.port_address.sub_uni());
+        buf.extend_from_slice(self.message);
+    }
+
+    pub fn parse(packet: &'a [u8]) -> Result<Self> {
+        if parse_op_code(packet) != Some(OpCode::Rdm) {
+            return Err(anyhow!("not an ArtRdm packet"));
+        }
+        if packet.len() < ART_RDM_HEADER_LEN {
+            return Err(anyhow!("ArtRdm packet is too short"));
+        }
+        Ok(Self {
+            port_address: PortAddress {
+                net: packet[21] & 0x7f,
+                sub_net: packet[23] >> 4,
+                universe: packet[23] & 0xf,
+            },
+            message: &packet[ART_RDM_HEADER_LEN..],
+        })
+    }
+}
+
+/// Writes the header shared by the RDM packets up to the command, leaving
+/// the address byte to the caller.
+fn write_rdm_header(op_code: OpCode, version: u8, net: u8, command: u8, buf: &mut Vec<u8>) {
+    write_header(op_code, buf);
+    buf.push(version);
+    buf.extend_from_slice(&[0; 8]);
+    buf.push(net);
+    buf.push(command);
+}
+
+/// Asks every node on the network to reply with an ArtPollReply.
+pub struct ArtPoll {
+    pub flags: u8,
+    pub diag_priority: u8,
+}
+
+impl ArtPoll {
+    /// Ask nodes to send an ArtPollReply whenever their state changes,
+    /// not only when polled.
+    pub const REPLY_ON_CHANGE: u8 = 0x02;
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        write_header(OpCode::Poll, buf);
+        buf.push(self.flags);
+        buf.push(self.diag_priority);
+    }
+}
+
+/// A single port on a node, with the port addresses it inputs from and
+/// outputs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodePort {
+    pub port_type: u8,
+    pub input: Option<PortAddress>,
+    pub output: Option<PortAddress>,
+}
+
+impl NodePort {
+    const CAN_OUTPUT: u8 = 0x80;
+    const CAN_INPUT: u8 = 0x40;
+}
+
+/// A node's reply to an ArtPoll, describing up to four of its ports. Nodes
+/// with more ports send one reply per `bind_index`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArtPollReply {
+    pub ip: Ipv4Addr,
+    pub firmware: u16,
+    pub oem: u16,
+    pub esta_manufacturer: u16,
+    pub status1: u8,
+    pub short_name: String,
+    pub long_name: String,
+    pub node_report: String,
+    pub ports: Vec<NodePort>,
+    pub style: u8,
+    pub mac: [u8; 6],
+    pub bind_index: u8,
+    pub status2: u8,
+}
+
+impl ArtPollReply {
+    /// Older nodes send replies without the fields from `Style` onwards.
+    const MIN_LEN: usize = 197;
+    const LEN: usize = 239;
+
+    pub fn parse(packet: &[u8]) -> Result<Self> {
+        if parse_op_code(packet) != Some(OpCode::PollReply) {
+            return Err(anyhow!("not an ArtPollReply"));
+        }
+        if packet.len() < Self::MIN_LEN {
+            return Err(anyhow!(
+                "ArtPollReply is {} bytes, expected at least {}",
+                packet.len(),
+                Self::MIN_LEN
+            ));
+        }
+        let byte = |offset: usize| packet.get(offset).copied().unwrap_or(0);
+        let net = packet[18] & 0x7f;
+        let sub_net = packet[19] & 0xf;
+        let num_ports = (packet[173] as usize).min(4);
+        let ports = (0..num_ports)
+            .map(|i| {
+                let port_type = packet[174 + i];
+                let address = |universe: u8| PortAddress {
+                    net,
+                    sub_net,
+                    universe: universe & 0xf,
+                };
+                NodePort {
+                    port_type,
+                    input: (port_type & NodePort::CAN_INPUT != 0).then(|| address(packet[186 + i])),
+                    output: (port_type & NodePort::CAN_OUTPUT != 0)
+                        .then(|| address(packet[190 + i])),
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            ip: Ipv4Addr::new(packet[10], packet[11], packet[12], packet[13]),
+            firmware: u16::from_be_bytes([packet[16], packet[17]]),
+            oem: u16::from_be_bytes([packet[20], packet[21]]),
+            esta_manufacturer: u16::from_le_bytes([packet[24], packet[25]]),
+            status1: packet[23],
+            short_name: read_string(&packet[26..44]),
+            long_name: read_string(&packet[44..108]),
+            node_report: read_string(&packet[108..172]),
+            ports,
+            style: byte(200),
+            mac: std::array::from_fn(|i| byte(201 + i)),
+            bind_index: byte(211),
+            status2: byte(212),
+        })
+    }
+
+    /// Encodes the reply. Every port must share the Net and Sub-Net of the
+    /// first, as ArtPollReply only carries one of each.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let start = buf.len();
+        buf.extend_from_slice(ARTNET_ID);
+        buf.extend_from_slice(&(OpCode::PollReply as u16).to_le_bytes());
+        buf.extend_from_slice(&self.ip.octets());
+        buf.extend_from_slice(&ARTNET_PORT.to_le_bytes());
+        buf.extend_from_slice(&self.firmware.to_be_bytes());
+        let first = self
+            .ports
+            .iter()
+            .find_map(|port| port.output.or(port.input))
+            .unwrap_or_default();
+        buf.push(first.net);
+        buf.push(first.sub_net);
+        buf.extend_from_slice(&self.oem.to_be_bytes());
+        buf.push(0);
+        buf.push(self.status1);
+        buf.extend_from_slice(&self.esta_manufacturer.to_le_bytes());
+        write_string(&self.short_name, 18, buf);
+        write_string(&self.long_name, 64, buf);
+        write_string(&self.node_report, 64, buf);
+        let ports = &self.ports[..self.ports.len().min(4)];
+        buf.extend_from_slice(&(ports.len() as u16).to_be_bytes());
+        let mut fields = [[0u8; 4]; 5];
+        for (i, port) in ports.iter().enumerate() {
+            fields[0][i] = port.port_type;
+            fields[3][i] = port.input.map_or(0, |address| address.universe);
+            fields[4][i] = port.output.map_or(0, |address| address.universe);
+        }
+        for field in fields {
+            buf.extend_from_slice(&field);
+        }
+        buf.resize(start + 200, 0);
+        buf.push(self.style);
+        buf.extend_from_slice(&self.mac);
+        buf.extend_from_slice(&self.ip.octets());
+        buf.push(self.bind_index);
+        buf.push(self.status2);
+        buf.resize(start + Self::LEN, 0);
+    }
+}
+
+/// Reads a null-terminated ASCII string out of a fixed-size field.
+fn read_string(field: &[u8]) -> String {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..len]).into_owned()
+}
+
+/// Writes a string into a fixed-size field, always leaving room for the
+/// null terminator.
+fn write_string(value: &str, len: usize, buf: &mut Vec<u8>) {
+    let bytes = &value.as_bytes()[..value.len().min(len - 1)];
+    buf.extend_from_slice(bytes);
+    buf.resize(buf.len() + len - bytes.len(), 0);
+}
+
+/// Advances an ArtDmx sequence number, skipping zero which tells
+/// receivers that sequencing is disabled.
+pub(crate) fn next_sequence(sequence: u8) -> u8 {
+    sequence.checked_add(1).unwrap_or(1)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArtNetConfig {
+    /// The local interface address to send from.
+    pub bind_address: Ipv4Addr,
+    /// Where to send ArtDmx packets, either a directed broadcast
+    /// address such as `2.255.255.255` or a single node.
+    pub destination: Ipv4Addr,
+    /// Whether to send an ArtSync after each frame's ArtDmx packets, to
+    /// every destination they went to, so that nodes which support it
+    /// output every universe at the same moment.
+    #[serde(default)]
+    pub sync: bool,
+}
+
+impl ArtNetConfig {
+    /// The addresses ArtDmx for `routes` is sent from. Without a bind
+    /// address, these are the interfaces the destinations are reached
+    /// through.
+    pub(crate) fn source_addresses(&self, routes: &[Route]) -> BTreeSet<IpAddr> {
+        if !self.bind_address.is_unspecified() {
+            return BTreeSet::from([IpAddr::V4(self.bind_address)]);
+        }
+        iter::once(IpAddr::V4(self.destination))
+            .chain(
+                routes
+                    .iter()
+                    .flat_map(|route| route.destinations.iter().copied()),
+            )
+            .filter_map(|destination| interface_address(destination).ok())
+            .collect()
+    }
+}
+
+/// The address of the interface that packets to `destination` leave from.
+fn interface_address(destination: IpAddr) -> io::Result<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_broadcast(true)?;
+    socket.connect((destination, ARTNET_PORT))?;
+    Ok(socket.local_addr()?.ip())
+}
+
+struct ArtNetRoute {
+    universe: UniverseId,
+    port_address: PortAddress,
+    destinations: Vec<SocketAddr>,
+    sequence: u8,
+}
+
+/// Transmits an ArtDmx packet for each routed universe every frame, or as
+/// often as the routes' send policies allow, optionally followed by an
+/// ArtSync.
+/// Routes without an address send internal universe `n` on port
+/// address `n - 1`.
+pub struct ArtNetOutput {
+    socket: UdpSocket,
+    routes: Vec<ArtNetRoute>,
+    sync: bool,
+    /// The destinations sent to in the current frame, for the ArtSync.
+    sync_destinations: BTreeSet<SocketAddr>,
+    buf: Vec<u8>,
+}
+
+impl ArtNetOutput {
+    pub const KIND: &'static str = "artnet";
+
+    pub fn sink_kind() -> SinkKind {
+        SinkKind {
+            addresses: Some(0..=PortAddress::MAX),
+            default_address: |universe| universe.checked_sub(1),
+            build: Self::build,
+        }
+    }
+
+    pub fn new(routes: &[Route], config: ArtNetConfig) -> Result<Self> {
+        let default_destination = SocketAddr::from((config.destination, ARTNET_PORT));
+        let routes = routes
+            .iter()
+            .map(|route| {
+                let port_address = route
+                    .address
+                    .or_else(|| route.universe.checked_sub(1))
+                    .ok_or_else(|| anyhow!("there is no universe 0"))
+                    .and_then(PortAddress::from_u16)?;
+                let destinations = if route.destinations.is_empty() {
+                    vec![default_destination]
+                } else {
+                    route
+                        .destinations
+                        .iter()
+                        .map(|ip| SocketAddr::new(*ip, ARTNET_PORT))
+                        .collect()
+                };
+                Ok(ArtNetRoute {
+                    universe: route.universe,
+                    port_address,
+                    destinations,
+                    sequence: 0,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let socket = UdpSocket::bind((config.bind_address, 0))
+            .with_context(|| format!("binding Art-Net socket to {}", config.bind_address))?;
+        socket.set_broadcast(true)?;
+
+        Ok(Self {
+            socket,
+            routes,
+            sync: config.sync,
+            sync_destinations: BTreeSet::new(),
+            buf: Vec::with_capacity(ART_DMX_HEADER_LEN + UNIVERSE_SIZE),
+        })
+    }
+
+    /// Sends the routes for `universes`, or every route.
+    fn send(&mut self, frame: &DmxFrame, universes: Option<&[UniverseId]>) -> Result<()> {
+        let mut result = Ok(());
+        self.sync_destinations.clear();
+        for route in &mut self.routes {
+            if universes.is_some_and(|universes| !universes.contains(&route.universe)) {
+                continue;
+            }
+            route.sequence = next_sequence(route.sequence);
+            self.buf.clear();
+            ArtDmx {
+                sequence: route.sequence,
+                physical: 0,
+                port_address: route.port_address,
+                data: frame
+                    .universe(route.universe)
+                    .map_or(&[0; UNIVERSE_SIZE], |universe| &**universe),
+            }
+            .encode(&mut self.buf);
+
+            for destination in &route.destinations {
+                if let Err(err) = self.socket.send_to(&self.buf, destination) {
+                    result = Err(err).with_context(|| {
+                        format!("sending ArtDmx for {} to {destination}", route.port_address)
+                    });
+                }
+            }
+            if self.sync {
+                self.sync_destinations.extend(&route.destinations);
+            }
+        }
+
+        if !self.sync_destinations.is_empty() {
+            self.buf.clear();
+            ArtSync.encode(&mut self.buf);
+            for destination in &self.sync_destinations {
+                if let Err(err) = self.socket.send_to(&self.buf, destination) {
+                    result = Err(err).with_context(|| format!("sending ArtSync to {destination}"));
+                }
+            }
+        }
+        result
+    }
+
+    pub fn build(config: &SinkConfig) -> Result<Box<dyn DmxSink>> {
+        Ok(Box::new(Self::new(&config.routes, config.options()?)?))
+    }
+}
+
+impl DmxSink for ArtNetOutput {
+    fn send_frame(&mut self, frame: &DmxFrame) -> Result<()> {
+        self.send(frame, None)
+    }
+
+    fn send_universes(&mut self, frame: &DmxFrame, universes: &[UniverseId]) -> Result<()> {
+        self.send(frame, Some(universes))
+    }
+}
+
+/// Serializes the tests that listen on the Art-Net port.
+#[cfg(test)]
+pub(crate) static TEST_PORT_LOCK: parking_lot::Mutex<()> = parking_lot::Mutex::new(());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::universe::{FrameSources, UniverseBuffer};
+    use socket2::{Domain, Protocol, Socket, Type};
+    use std::time::{Duration, Instant};
+
+    fn listener(ip: Ipv4Addr) -> UdpSocket {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).unwrap();
+        socket.set_reuse_address(true).unwrap();
+        socket
+            .bind(&SocketAddr::from((ip, ARTNET_PORT)).into())
+            .unwrap();
+        let socket = UdpSocket::from(socket);
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        socket
+    }
+
+    fn frame(universe: UniverseId, levels: &[u8]) -> DmxFrame {
+        let mut universes = UniverseBuffer::default();
+        universes.get_mut(universe).copy_from(levels);
+        DmxFrame {
+            sequence: 0,
+            timestamp: Instant::now(),
+            universes,
+            sources: FrameSources::default(),
+        }
+    }
+
+    /// Receives packets until the ArtDmx for `port_address`, skipping any
+    /// other Art-Net traffic on the port.
+    fn receive_art_dmx(socket: &UdpSocket, port_address: PortAddress) -> Vec<u8> {
+        let mut buf = [0; 1024];
+        loop {
+            let (len, _) = socket.recv_from(&mut buf).unwrap();
+            let packet = &buf[..len];
+            if parse_op_code(packet) == Some(OpCode::Dmx)
+                && packet[14] == port_address.sub_uni()
+                && packet[15] == port_address.net
+            {
+                return packet.to_vec();
+            }
+        }
+    }
+
+    fn assert_art_dmx(packet: &[u8], sequence: u8, port_address: PortAddress, levels: &[u8]) {
+        assert_eq!(&packet[..8], b"Art-Net\0");
+        assert_eq!(&packet[8..10], &[0x00, 0x50], "OpCode is little-endian");
+        assert_eq!(&packet[10..12], &[0, 14], "protocol version is big-endian");
+        assert_eq!(packet[12], sequence);
+        assert_eq!(packet[13], 0);
+        assert_eq!(
+            packet[14],
+            port_address.sub_net << 4 | port_address.universe
+        );
+        assert_eq!(packet[15], port_address.net);
+        assert_eq!(&packet[16..18], &[0x02, 0x00], "length is big-endian");
+        assert_eq!(packet.len(), ART_DMX_HEADER_LEN + UNIVERSE_SIZE);
+        assert_eq!(&packet[18..18 + levels.len()], levels);
+        assert!(packet[18 + levels.len()..].iter().all(|level| *level == 0));
+    }
+
+    fn route(universe: UniverseId, address: u16, destinations: Vec<IpAddr>) -> Route {
+        Route {
+            universe,
+            address: Some(address),
+            destinations,
+            priority: None,
+            policy: None,
+        }
+    }
+
+    #[test]
+    fn test_art_dmx_length_is_even() {
+        let mut buf = Vec::new();
+        ArtDmx {
+            sequence: 1,
+            physical: 0,
+            port_address: PortAddress::default(),
+            data: &[1, 2, 3],
+        }
+        .encode(&mut buf);
+        assert_eq!(&buf[16..18], &[0, 4]);
+        assert_eq!(&buf[18..], &[1, 2, 3, 0]);
+
+        buf.clear();
+        ArtDmx {
+            sequence: 1,
+            physical: 0,
+            port_address: PortAddress::default(),
+            data: &[],
+        }
+        .encode(&mut buf);
+        assert_eq!(&buf[16..18], &[0, 2]);
+        assert_eq!(buf.len(), ART_DMX_HEADER_LEN + 2);
+    }
+
+    #[test]
+    fn test_sends_art_dmx_to_broadcast_destination() {
+        let _lock = TEST_PORT_LOCK.lock();
+        let socket = listener(Ipv4Addr::UNSPECIFIED);
+        let port_address = PortAddress::new(0x12, 0x3, 0x4).unwrap();
+        let mut output = ArtNetOutput::new(
+            &[route(7, port_address.to_u16(), Vec::new())],
+            ArtNetConfig {
+                bind_address: Ipv4Addr::UNSPECIFIED,
+                destination: Ipv4Addr::new(127, 255, 255, 255),
+                sync: false,
+            },
+        )
+        .unwrap();
+
+        let levels = [255, 128, 1];
+        output.send_frame(&frame(7, &levels)).unwrap();
+        let packet = receive_art_dmx(&socket, port_address);
+        assert_art_dmx(&packet, 1, port_address, &levels);
+    }
+
+    #[test]
+    fn test_sends_art_dmx_to_unicast_destination() {
+        let _lock = TEST_PORT_LOCK.lock();
+        let socket = listener(Ipv4Addr::LOCALHOST);
+        let port_address = PortAddress::new(0x7f, 0xf, 0xe).unwrap();
+        let mut output = ArtNetOutput::new(
+            &[route(
+                2,
+                port_address.to_u16(),
+                vec![IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            )],
+            ArtNetConfig {
+                bind_address: Ipv4Addr::UNSPECIFIED,
+                destination: Ipv4Addr::new(2, 255, 255, 255),
+                sync: false,
+            },
+        )
+        .unwrap();
+
+        // Sequence numbers run from 1 to 255 and then wrap to 1, as zero
+        // turns sequencing off.
+        let levels = [10; UNIVERSE_SIZE];
+        for sequence in (1..=255).chain(1..=2) {
+            output.send_frame(&frame(2, &levels)).unwrap();
+            let packet = receive_art_dmx(&socket, port_address);
+            assert_art_dmx(&packet, sequence, port_address, &levels);
+        }
+    }
+
+    #[test]
+    fn test_sends_art_sync_to_each_destination() {
+        let _lock = TEST_PORT_LOCK.lock();
+        let first = Ipv4Addr::LOCALHOST;
+        let second = Ipv4Addr::new(127, 0, 0, 2);
+        let sockets = [listener(first), listener(second)];
+        let mut output = ArtNetOutput::new(
+            &[
+                route(1, 0, vec![IpAddr::V4(first)]),
+                route(2, 1, vec![IpAddr::V4(first), IpAddr::V4(second)]),
+            ],
+            ArtNetConfig {
+                bind_address: Ipv4Addr::UNSPECIFIED,
+                destination: Ipv4Addr::new(2, 255, 255, 255),
+                sync: true,
+            },
+        )
+        .unwrap();
+        output.send_frame(&frame(1, &[255])).unwrap();
+
+        // One ArtSync per destination, after the last ArtDmx sent to it.
+        let mut buf = [0; 1024];
+        for (socket, dmx_count) in sockets.iter().zip([2, 1]) {
+            let mut op_codes = Vec::new();
+            while op_codes.last() != Some(&OpCode::Sync) {
+                let (len, _) = socket.recv_from(&mut buf).unwrap();
+                op_codes.extend(parse_op_code(&buf[..len]));
+            }
+            let mut expected = vec![OpCode::Dmx; dmx_count];
+            expected.push(OpCode::Sync);
+            assert_eq!(op_codes, expected);
+
+            socket
+                .set_read_timeout(Some(Duration::from_millis(100)))
+                .unwrap();
+            assert!(socket.recv_from(&mut buf).is_err());
+        }
+    }
+}
@@ -0,0 +1,457 @@
+use anyhow::{Context, Result};
+use gpui::{AppContext, Global};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    artnet::{
+        parse_op_code, ArtDmx, ArtPoll, ArtPollReply, ArtRdm, ArtTodData, OpCode, PortAddress,
+        ARTNET_PORT,
+    },
+    artnet_rdm::{ArtNetRdm, RdmConfig},
+    input::DmxInput,
+};
+
+/// How often the listener wakes up to poll and forget stale nodes, and at
+/// most how often it lets RDM send requests.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArtNetDiscoveryConfig {
+    /// Whether to poll for nodes. The listener still runs without polling
+    /// when Art-Net input is configured, and polls anyway when RDM is
+    /// enabled.
+    pub enabled: bool,
+    /// The local interface address to listen on and poll from.
+    pub bind_address: Ipv4Addr,
+    /// Where to send ArtPoll packets, usually a directed broadcast address.
+    pub destination: Ipv4Addr,
+    /// How often to poll. The spec asks for between 2.5 and 3 seconds.
+    pub poll_interval_ms: u64,
+    /// How long a node is kept after its last reply.
+    pub node_timeout_ms: u64,
+}
+
+impl Default for ArtNetDiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            bind_address: Ipv4Addr::UNSPECIFIED,
+            destination: Ipv4Addr::new(2, 255, 255, 255),
+            poll_interval_ms: 2500,
+            node_timeout_ms: 10000,
+        }
+    }
+}
+
+/// A node that has replied to an ArtPoll. Nodes with more than four ports
+/// are listed once per bind index.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArtNetNode {
+    pub address: SocketAddr,
+    pub reply: ArtPollReply,
+    pub last_seen: Instant,
+}
+
+type NodeKey = (Ipv4Addr, u8);
+
+/// The addresses our Art-Net sinks send from and the port addresses they
+/// send, so that our own ArtDmx is not merged back in as input.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LocalArtNetPorts {
+    pub addresses: BTreeSet<IpAddr>,
+    pub port_addresses: BTreeSet<u16>,
+}
+
+impl LocalArtNetPorts {
+    fn contains(&self, ip: IpAddr, port_address: PortAddress) -> bool {
+        self.addresses.contains(&ip) && self.port_addresses.contains(&port_address.to_u16())
+    }
+}
+
+struct GlobalArtNetDiscovery(Arc<ArtNetDiscovery>);
+
+impl Global for GlobalArtNetDiscovery {}
+
+/// Polls the network for Art-Net nodes and keeps a table of the ones
+/// that reply. As it owns the Art-Net port, it also hands received ArtDmx
+/// packets, other than our own, to the input merge and RDM replies to the
+/// RDM controller.
+pub struct ArtNetDiscovery {
+    nodes: Arc<Mutex<BTreeMap<NodeKey, ArtNetNode>>>,
+    rdm: Option<Arc<ArtNetRdm>>,
+    running: Arc<AtomicBool>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ArtNetDiscovery {
+    pub fn global(cx: &AppContext) -> Arc<Self> {
+        cx.global::<GlobalArtNetDiscovery>().0.clone()
+    }
+
+    pub fn try_global(cx: &AppContext) -> Option<Arc<Self>> {
+        cx.try_global::<GlobalArtNetDiscovery>()
+            .map(|discovery| discovery.0.clone())
+    }
+
+    pub fn set_global(discovery: Arc<Self>, cx: &mut AppContext) {
+        cx.set_global(GlobalArtNetDiscovery(discovery));
+    }
+
+    pub fn start(
+        config: &ArtNetDiscoveryConfig,
+        rdm_config: &RdmConfig,
+        mut local: LocalArtNetPorts,
+        input: Option<Arc<DmxInput>>,
+    ) -> Result<Self> {
+        let socket = listener_socket(config.bind_address)?;
+        if !config.bind_address.is_unspecified() {
+            local.addresses.insert(IpAddr::V4(config.bind_address));
+        }
+        let destination = SocketAddr::from((config.destination, ARTNET_PORT));
+        let poll = config.enabled || rdm_config.enabled;
+        let rdm = if rdm_config.enabled {
+            let socket = socket
+                .try_clone()
+                .context("cloning Art-Net socket for RDM")?;
+            Some(Arc::new(ArtNetRdm::new(rdm_config, socket, destination)))
+        } else {
+            None
+        };
+        let poll_interval = Duration::from_millis(config.poll_interval_ms);
+        let node_timeout = Duration::from_millis(config.node_timeout_ms);
+
+        let nodes = Arc::new(Mutex::new(BTreeMap::<NodeKey, ArtNetNode>::new()));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let thread = thread::Builder::new()
+            .name("artnet-discovery".into())
+            .spawn({
+                let nodes = nodes.clone();
+                let running = running.clone();
+                let rdm = rdm.clone();
+                move || {
+                    let mut poll_packet = Vec::new();
+                    ArtPoll {
+                        flags: ArtPoll::REPLY_ON_CHANGE,
+                        diag_priority: 0,
+                    }
+                    .encode(&mut poll_packet);
+
+                    // Big enough for an ArtTodData with 200 UIDs.
+                    let mut buf = [0; 2048];
+                    let mut last_poll = None::<Instant>;
+                    let mut last_rdm_tick = None::<Instant>;
+                    while running.load(Ordering::Relaxed) {
+                        let now = Instant::now();
+                        if poll
+                            && last_poll.is_none_or(|last_poll| now - last_poll >= poll_interval)
+                        {
+                            if let Err(err) = socket.send_to(&poll_packet, destination) {
+                                log::warn!("failed to send ArtPoll to {destination}: {err}");
+                            }
+                            last_poll = Some(now);
+                            nodes
+                                .lock()
+                                .retain(|_, node| now - node.last_seen < node_timeout);
+                        }
+                        // With busy Art-Net traffic the listener wakes for
+                        // every packet, far more often than RDM needs.
+                        if let Some(rdm) = &rdm {
+                            if last_rdm_tick.is_none_or(|last_tick| now - last_tick >= READ_TIMEOUT)
+                            {
+                                rdm.tick(nodes.lock().values(), now);
+                                last_rdm_tick = Some(now);
+                            }
+                        }
+
+                        let (len, address) = match socket.recv_from(&mut buf) {
+                            Ok(received) => received,
+                            Err(err)
+                                if matches!(
+                                    err.kind(),
+                                    ErrorKind::WouldBlock | ErrorKind::TimedOut
+                                ) =>
+                            {
+                                continue
+                            }
+                            Err(err) => {
+                                log::error!("Art-Net discovery failed to receive: {err}");
+                                thread::sleep(READ_TIMEOUT);
+                                continue;
+                            }
+                        };
+
+                        let packet = &buf[..len];
+                        match parse_op_code(packet) {
+                            Some(OpCode::PollReply) => match ArtPollReply::parse(packet) {
+                                Ok(reply) => {
+                                    nodes.lock().insert(
+                                        (reply.ip, reply.bind_index),
+                                        ArtNetNode {
+                                            address,
+                                            reply,
+                                            last_seen: Instant::now(),
+                                        },
+                                    );
+                                }
+                                Err(err) => log::debug!("ignoring reply from {address}: {err}"),
+                            },
+                            Some(OpCode::Dmx) => {
+                                if let Some(input) = &input {
+                                    match ArtDmx::parse(packet) {
+                                        Ok(dmx)
+                                            if local.contains(address.ip(), dmx.port_address) => {}
+                                        Ok(dmx) => input.receive_artnet(
+                                            address.ip(),
+                                            dmx.port_address,
+                                            dmx.data,
+                                        ),
+                                        Err(err) => {
+                                            log::debug!("ignoring ArtDmx from {address}: {err}")
+                                        }
+                                    }
+                                }
+                            }
+                            Some(OpCode::TodData) => {
+                                if let Some(rdm) = &rdm {
+                                    match ArtTodData::parse(packet) {
+                                        Ok(tod) => rdm.receive_tod(address, tod),
+                                        Err(err) => {
+                                            log::debug!("ignoring ArtTodData from {address}: {err}")
+                                        }
+                                    }
+                                }
+                            }
+                            Some(OpCode::Rdm) => {
+                                if let Some(rdm) = &rdm {
+                                    match ArtRdm::parse(packet) {
+                                        Ok(packet) => rdm.receive_rdm(address, packet),
+                                        Err(err) => {
+                                            log::debug!("ignoring ArtRdm from {address}: {err}")
+                                        }
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            nodes,
+            rdm,
+            running,
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    /// Returns every node seen recently, ordered by IP address.
+    pub fn nodes(&self) -> Vec<ArtNetNode> {
+        self.nodes.lock().values().cloned().collect()
+    }
+
+    /// The RDM controller, when RDM is enabled.
+    pub fn rdm(&self) -> Option<Arc<ArtNetRdm>> {
+        self.rdm.clone()
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.lock().take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl Drop for ArtNetDiscovery {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Binds to the Art-Net port, sharing it with any other Art-Net software
+/// on this machine.
+fn listener_socket(bind_address: Ipv4Addr) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_broadcast(true)?;
+    socket.set_read_timeout(Some(READ_TIMEOUT))?;
+    let address = SocketAddrV4::new(bind_address, ARTNET_PORT);
+    socket
+        .bind(&address.into())
+        .with_context(|| format!("binding Art-Net discovery socket to {address}"))?;
+    Ok(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        artnet::{NodePort, TEST_PORT_LOCK},
+        input::{InputConfig, InputProtocol, InputSourceSettings, InputUniverseSettings, SourceId},
+    };
+
+    /// A node listening on every interface, so it hears the poll broadcast
+    /// while the discovery socket, bound to loopback only, gets the reply.
+    fn fake_node() -> UdpSocket {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).unwrap();
+        socket.set_reuse_address(true).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let address = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, ARTNET_PORT);
+        socket.bind(&address.into()).unwrap();
+        socket.into()
+    }
+
+    fn poll_reply() -> ArtPollReply {
+        ArtPollReply {
+            ip: Ipv4Addr::new(10, 0, 0, 5),
+            firmware: 0x0102,
+            oem: 0x1234,
+            esta_manufacturer: 0x7ff0,
+            status1: 0,
+            short_name: "Fake node".into(),
+            long_name: "Fake Art-Net node".into(),
+            node_report: "#0001 [0001] Power On Tests successful".into(),
+            ports: vec![NodePort {
+                port_type: 0x80,
+                input: None,
+                output: Some(PortAddress::new(1, 2, 3).unwrap()),
+            }],
+            style: 0,
+            mac: [0x02, 0, 0, 0, 0, 5],
+            bind_index: 1,
+            status2: 0,
+        }
+    }
+
+    #[test]
+    fn test_lists_node_that_replies_to_poll() {
+        let _lock = TEST_PORT_LOCK.lock();
+        let node = fake_node();
+        let discovery = ArtNetDiscovery::start(
+            &ArtNetDiscoveryConfig {
+                enabled: true,
+                bind_address: Ipv4Addr::LOCALHOST,
+                destination: Ipv4Addr::new(127, 255, 255, 255),
+                poll_interval_ms: 100,
+                node_timeout_ms: 10000,
+            },
+            &RdmConfig::default(),
+            LocalArtNetPorts::default(),
+            None,
+        )
+        .unwrap();
+
+        let mut buf = [0; 1024];
+        let poller = loop {
+            let (len, address) = node.recv_from(&mut buf).expect("no ArtPoll received");
+            if parse_op_code(&buf[..len]) == Some(OpCode::Poll) {
+                break address;
+            }
+        };
+        assert_eq!(poller, SocketAddr::from((Ipv4Addr::LOCALHOST, ARTNET_PORT)));
+
+        let reply = poll_reply();
+        let mut packet = Vec::new();
+        reply.encode(&mut packet);
+        node.send_to(&packet, poller).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let nodes = loop {
+            let nodes = discovery.nodes();
+            if !nodes.is_empty() {
+                break nodes;
+            }
+            assert!(Instant::now() < deadline, "node was not listed");
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].address.ip(), Ipv4Addr::LOCALHOST);
+        assert_eq!(nodes[0].reply, reply);
+        discovery.stop();
+    }
+
+    #[test]
+    fn test_ignores_own_art_dmx() {
+        let _lock = TEST_PORT_LOCK.lock();
+        let input = Arc::new(
+            DmxInput::new(&InputConfig {
+                universes: (1..=2)
+                    .map(|universe| InputUniverseSettings {
+                        universe,
+                        merge: Default::default(),
+                        sources: vec![InputSourceSettings {
+                            protocol: InputProtocol::Artnet,
+                            address: None,
+                        }],
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let discovery = ArtNetDiscovery::start(
+            &ArtNetDiscoveryConfig {
+                enabled: false,
+                bind_address: Ipv4Addr::LOCALHOST,
+                ..Default::default()
+            },
+            &RdmConfig::default(),
+            LocalArtNetPorts {
+                addresses: BTreeSet::new(),
+                port_addresses: BTreeSet::from([0]),
+            },
+            Some(input.clone()),
+        )
+        .unwrap();
+
+        // Both come from the address the listener is bound to, but only
+        // port address 0 is one we send.
+        let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        for port_address in 0..=1 {
+            let mut packet = Vec::new();
+            ArtDmx {
+                sequence: 1,
+                physical: 0,
+                port_address: PortAddress::from_u16(port_address).unwrap(),
+                data: &[255; 512],
+            }
+            .encode(&mut packet);
+            sender
+                .send_to(&packet, (Ipv4Addr::LOCALHOST, ARTNET_PORT))
+                .unwrap();
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let sources = loop {
+            let sources = input.sources();
+            if !sources[&2].is_empty() {
+                break sources;
+            }
+            assert!(Instant::now() < deadline, "ArtDmx was not received");
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(sources[&1], []);
+        assert_eq!(
+            sources[&2],
+            [SourceId::ArtNet(IpAddr::V4(Ipv4Addr::LOCALHOST))]
+        );
+        discovery.stop();
+    }
+}
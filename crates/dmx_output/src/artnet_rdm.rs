@@ -0,0 +1,646 @@
+use anyhow::{anyhow, Context, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    artnet::{ArtRdm, ArtTodControl, ArtTodData, ArtTodRequest, PortAddress, ARTNET_PORT},
+    artnet_discovery::ArtNetNode,
+    rdm::{
+        controller_uid, nack_reason, CommandClass, DeviceInfo, Pid, RdmMessage, ResponseType, Uid,
+    },
+};
+
+/// Devices are forgotten after missing this many table of devices
+/// requests.
+const MISSED_TOD_LIMIT: u32 = 3;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RdmConfig {
+    /// Whether to discover RDM devices through the Art-Net nodes found by
+    /// polling.
+    pub enabled: bool,
+    /// How often to ask nodes for their tables of devices.
+    pub tod_interval_ms: u64,
+    /// How long to wait for a device to answer a request.
+    pub response_timeout_ms: u64,
+}
+
+impl Default for RdmConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tod_interval_ms: 30000,
+            response_timeout_ms: 2000,
+        }
+    }
+}
+
+/// A device found in a node's table of devices, with what it has told us
+/// about itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RdmDevice {
+    pub uid: Uid,
+    /// The node the device is connected to, and the port it is behind.
+    pub node: Ipv4Addr,
+    pub port_address: PortAddress,
+    /// The reply to DEVICE_INFO, once the device has sent one.
+    pub info: Option<DeviceInfo>,
+    pub identify: bool,
+    /// Why the most recent request to the device failed.
+    pub last_error: Option<String>,
+    pub last_seen: Instant,
+}
+
+struct PendingRequest {
+    uid: Uid,
+    command_class: CommandClass,
+    pid: Pid,
+    data: Vec<u8>,
+    sent: Instant,
+}
+
+#[derive(Default)]
+struct RdmState {
+    devices: BTreeMap<Uid, RdmDevice>,
+    /// The output ports of the nodes found by polling.
+    ports: BTreeSet<(Ipv4Addr, PortAddress)>,
+    pending: HashMap<u8, PendingRequest>,
+    transaction: u8,
+    last_tod_request: Option<Instant>,
+}
+
+/// Finds RDM devices behind Art-Net nodes with ArtTodRequest, and gets and
+/// sets their parameters with ArtRdm. Runs on the Art-Net discovery
+/// listener, which hands it the replies.
+pub struct ArtNetRdm {
+    socket: UdpSocket,
+    destination: SocketAddr,
+    tod_interval: Duration,
+    response_timeout: Duration,
+    state: Mutex<RdmState>,
+}
+
+impl ArtNetRdm {
+    /// Sends from `socket`, which should be bound to the Art-Net port so
+    /// that every node's replies reach the listener. Table of devices
+    /// requests go to `destination`.
+    pub fn new(config: &RdmConfig, socket: UdpSocket, destination: SocketAddr) -> Self {
+        Self {
+            socket,
+            destination,
+            tod_interval: Duration::from_millis(config.tod_interval_ms),
+            response_timeout: Duration::from_millis(config.response_timeout_ms),
+            state: Mutex::default(),
+        }
+    }
+
+    /// Returns every device found, ordered by UID.
+    pub fn devices(&self) -> Vec<RdmDevice> {
+        self.state.lock().devices.values().cloned().collect()
+    }
+
+    /// Returns the UIDs found behind each node's port.
+    pub fn tables(&self) -> BTreeMap<(Ipv4Addr, PortAddress), Vec<Uid>> {
+        let mut tables = BTreeMap::<_, Vec<_>>::new();
+        for device in self.state.lock().devices.values() {
+            tables
+                .entry((device.node, device.port_address))
+                .or_default()
+                .push(device.uid);
+        }
+        tables
+    }
+
+    pub fn device(&self, uid: Uid) -> Option<RdmDevice> {
+        self.state.lock().devices.get(&uid).cloned()
+    }
+
+    /// Asks every node to run full discovery again, then for its table of
+    /// devices.
+    pub fn discover(&self) -> Result<()> {
+        let state = &mut *self.state.lock();
+        let mut buf = Vec::new();
+        for (node, port_address) in &state.ports {
+            buf.clear();
+            ArtTodControl {
+                port_address: *port_address,
+            }
+            .encode(&mut buf);
+            let destination = SocketAddr::from((*node, ARTNET_PORT));
+            self.socket
+                .send_to(&buf, destination)
+                .with_context(|| format!("sending ArtTodControl to {destination}"))?;
+        }
+        state.last_tod_request = None;
+        Ok(())
+    }
+
+    pub fn get(&self, uid: Uid, pid: Pid) -> Result<()> {
+        self.send_request(uid, CommandClass::Get, pid, Vec::new())
+    }
+
+    pub fn set_start_address(&self, uid: Uid, start_address: u16) -> Result<()> {
+        if !(1..=512).contains(&start_address) {
+            return Err(anyhow!("DMX start address {start_address} is out of range"));
+        }
+        self.send_request(
+            uid,
+            CommandClass::Set,
+            Pid::DmxStartAddress,
+            start_address.to_be_bytes().to_vec(),
+        )
+    }
+
+    pub fn set_personality(&self, uid: Uid, personality: u8) -> Result<()> {
+        if personality == 0 {
+            return Err(anyhow!("DMX personalities start at 1"));
+        }
+        self.send_request(
+            uid,
+            CommandClass::Set,
+            Pid::DmxPersonality,
+            vec![personality],
+        )
+    }
+
+    pub fn identify(&self, uid: Uid, identify: bool) -> Result<()> {
+        self.send_request(
+            uid,
+            CommandClass::Set,
+            Pid::IdentifyDevice,
+            vec![identify as u8],
+        )
+    }
+
+    fn send_request(
+        &self,
+        uid: Uid,
+        command_class: CommandClass,
+        pid: Pid,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let state = &mut *self.state.lock();
+        self.send_request_locked(state, uid, command_class, pid, data)
+    }
+
+    fn send_request_locked(
+        &self,
+        state: &mut RdmState,
+        uid: Uid,
+        command_class: CommandClass,
+        pid: Pid,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let device = state
+            .devices
+            .get(&uid)
+            .ok_or_else(|| anyhow!("no RDM device {uid}"))?;
+        let destination = SocketAddr::from((device.node, ARTNET_PORT));
+        let port_address = device.port_address;
+
+        state.transaction = state.transaction.wrapping_add(1);
+        let transaction = state.transaction;
+        let mut message = Vec::new();
+        RdmMessage::request(uid, transaction, command_class, pid, data.clone())
+            .encode(&mut message);
+        let mut buf = Vec::new();
+        ArtRdm {
+            port_address,
+            message: &message,
+        }
+        .encode(&mut buf);
+        self.socket
+            .send_to(&buf, destination)
+            .with_context(|| format!("sending ArtRdm for {uid} to {destination}"))?;
+
+        state.pending.insert(
+            transaction,
+            PendingRequest {
+                uid,
+                command_class,
+                pid,
+                data,
+                sent: Instant::now(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Asks for tables of devices when it is time, and gives up on requests
+    /// that have not been answered. Called by the listener regularly.
+    pub(crate) fn tick<'a>(&self, nodes: impl IntoIterator<Item = &'a ArtNetNode>, now: Instant) {
+        let state = &mut *self.state.lock();
+        state.ports = nodes
+            .into_iter()
+            .flat_map(|node| {
+                node.reply
+                    .ports
+                    .iter()
+                    .filter_map(move |port| Some((node.reply.ip, port.output?)))
+            })
+            .collect();
+
+        let timeout = self.response_timeout;
+        state.pending.retain(|_, request| {
+            if now - request.sent < timeout {
+                return true;
+            }
+            if let Some(device) = state.devices.get_mut(&request.uid) {
+                device.last_error = Some(format!("no response to {:?}", request.pid));
+            }
+            false
+        });
+
+        let tod_due = state
+            .last_tod_request
+            .is_none_or(|last_tod_request| now - last_tod_request >= self.tod_interval);
+        if !tod_due || state.ports.is_empty() {
+            return;
+        }
+        state.last_tod_request = Some(now);
+        let forget_after = self.tod_interval * MISSED_TOD_LIMIT;
+        state
+            .devices
+            .retain(|_, device| now - device.last_seen < forget_after);
+
+        let mut nets = BTreeMap::<u8, BTreeSet<u8>>::new();
+        for (_, port_address) in &state.ports {
+            nets.entry(port_address.net)
+                .or_default()
+                .insert(port_address.sub_uni());
+        }
+        let mut buf = Vec::new();
+        for (net, addresses) in nets {
+            let addresses = addresses.into_iter().collect::<Vec<_>>();
+            for addresses in addresses.chunks(32) {
+                buf.clear();
+                ArtTodRequest {
+                    net,
+                    addresses: addresses.to_vec(),
+                }
+                .encode(&mut buf);
+                if let Err(err) = self.socket.send_to(&buf, self.destination) {
+                    log::warn!(
+                        "failed to send ArtTodRequest to {}: {err}",
+                        self.destination
+                    );
+                }
+            }
+        }
+    }
+
+    /// Adds the devices in a node's table, and asks the new ones for their
+    /// DEVICE_INFO.
+    pub(crate) fn receive_tod(&self, address: SocketAddr, tod: ArtTodData) {
+        let IpAddr::V4(node) = address.ip() else {
+            return;
+        };
+        if tod.nak {
+            log::debug!(
+                "{node} could not send its table of devices for {}",
+                tod.port_address
+            );
+            return;
+        }
+        let now = Instant::now();
+        let state = &mut *self.state.lock();
+        for uid in tod.uids {
+            let device = state.devices.entry(uid).or_insert_with(|| RdmDevice {
+                uid,
+                node,
+                port_address: tod.port_address,
+                info: None,
+                identify: false,
+                last_error: None,
+                last_seen: now,
+            });
+            device.node = node;
+            device.port_address = tod.port_address;
+            device.last_seen = now;
+            if device.info.is_none() {
+                if let Err(err) = self.send_request_locked(
+                    state,
+                    uid,
+                    CommandClass::Get,
+                    Pid::DeviceInfo,
+                    Vec::new(),
+                ) {
+                    log::warn!("failed to ask {uid} for its device info: {err:?}");
+                }
+            }
+        }
+    }
+
+    /// Applies a device's response to one of our requests.
+    pub(crate) fn receive_rdm(&self, address: SocketAddr, rdm: ArtRdm) {
+        let message = match RdmMessage::parse(rdm.message) {
+            Ok(message) => message,
+            Err(err) => {
+                log::debug!("ignoring ArtRdm from {address}: {err}");
+                return;
+            }
+        };
+        if message.destination != controller_uid() {
+            return;
+        }
+        let state = &mut *self.state.lock();
+        let Some(request) = state.pending.remove(&message.transaction) else {
+            return;
+        };
+        if request.uid != message.source {
+            return;
+        }
+        let Some(device) = state.devices.get_mut(&request.uid) else {
+            return;
+        };
+
+        match message.response_type() {
+            Some(ResponseType::Ack) => {
+                device.last_error = None;
+                let refresh = apply_response(device, &request, &message.data);
+                if refresh {
+                    // A new personality can change the footprint and start
+                    // address, so read them all back.
+                    if let Err(err) = self.send_request_locked(
+                        state,
+                        request.uid,
+                        CommandClass::Get,
+                        Pid::DeviceInfo,
+                        Vec::new(),
+                    ) {
+                        log::warn!("failed to ask {} for its device info: {err:?}", request.uid);
+                    }
+                }
+            }
+            Some(ResponseType::AckTimer) => {
+                device.last_error = Some("device is busy, try again".into());
+            }
+            Some(ResponseType::NackReason) => {
+                device.last_error = Some(format!(
+                    "{:?} refused: {}",
+                    request.pid,
+                    nack_reason(&message.data)
+                ));
+            }
+            Some(ResponseType::AckOverflow) | None => {
+                device.last_error = Some(format!("unexpected response to {:?}", request.pid));
+            }
+        }
+    }
+}
+
+/// Updates `device` from an acknowledged request, returning whether its
+/// DEVICE_INFO should be read again.
+fn apply_response(device: &mut RdmDevice, request: &PendingRequest, data: &[u8]) -> bool {
+    let data = match request.command_class {
+        CommandClass::Get | CommandClass::GetResponse => data,
+        CommandClass::Set | CommandClass::SetResponse => &request.data,
+    };
+    match request.pid {
+        Pid::DeviceInfo => match DeviceInfo::parse(data) {
+            Ok(info) => device.info = Some(info),
+            Err(err) => device.last_error = Some(format!("{err:#}")),
+        },
+        Pid::DmxStartAddress => {
+            if let (Some(info), [hi, lo, ..]) = (&mut device.info, data) {
+                info.start_address = Some(u16::from_be_bytes([*hi, *lo]));
+            }
+        }
+        Pid::DmxPersonality => {
+            if let (Some(info), [personality, ..]) = (&mut device.info, data) {
+                info.personality = *personality;
+            }
+            return request.command_class == CommandClass::Set;
+        }
+        Pid::IdentifyDevice => {
+            if let [identify, ..] = data {
+                device.identify = *identify != 0;
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artnet::{parse_op_code, OpCode, TEST_PORT_LOCK};
+
+    const NODE: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 3);
+    const DEVICE: Uid = Uid {
+        manufacturer: 0x4a50,
+        device: 0x0000_0007,
+    };
+
+    /// Stands in for the node the device is behind, on the Art-Net port.
+    fn node() -> UdpSocket {
+        let socket = UdpSocket::bind((NODE, ARTNET_PORT)).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        socket
+    }
+
+    fn rdm() -> ArtNetRdm {
+        ArtNetRdm::new(
+            &RdmConfig {
+                enabled: true,
+                ..Default::default()
+            },
+            UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap(),
+            SocketAddr::from((NODE, ARTNET_PORT)),
+        )
+    }
+
+    fn port_address() -> PortAddress {
+        PortAddress::new(0, 1, 2).unwrap()
+    }
+
+    /// Receives the next ArtRdm sent to the node and returns its request.
+    fn receive_request(node: &UdpSocket) -> RdmMessage {
+        let mut buf = [0; 1024];
+        let (len, _) = node.recv_from(&mut buf).unwrap();
+        assert_eq!(parse_op_code(&buf[..len]), Some(OpCode::Rdm));
+        let rdm = ArtRdm::parse(&buf[..len]).unwrap();
+        assert_eq!(rdm.port_address, port_address());
+        let request = RdmMessage::parse(rdm.message).unwrap();
+        assert_eq!(request.destination, DEVICE);
+        assert_eq!(request.source, controller_uid());
+        request
+    }
+
+    /// Answers `request` the way the device would, through the node.
+    fn respond(rdm: &ArtNetRdm, request: &RdmMessage, response_type: ResponseType, data: Vec<u8>) {
+        let command_class = match CommandClass::from_u8(request.command_class) {
+            Some(CommandClass::Get) => CommandClass::GetResponse,
+            _ => CommandClass::SetResponse,
+        };
+        let mut message = Vec::new();
+        RdmMessage {
+            destination: request.source,
+            source: request.destination,
+            transaction: request.transaction,
+            port_or_response: response_type as u8,
+            message_count: 0,
+            sub_device: 0,
+            command_class: command_class as u8,
+            pid: request.pid,
+            data,
+        }
+        .encode(&mut message);
+        let mut buf = Vec::new();
+        ArtRdm {
+            port_address: port_address(),
+            message: &message,
+        }
+        .encode(&mut buf);
+        rdm.receive_rdm(
+            SocketAddr::from((NODE, ARTNET_PORT)),
+            ArtRdm::parse(&buf).unwrap(),
+        );
+    }
+
+    fn receive_tod(rdm: &ArtNetRdm, tod: ArtTodData) {
+        let mut buf = Vec::new();
+        tod.encode(&mut buf);
+        rdm.receive_tod(
+            SocketAddr::from((NODE, ARTNET_PORT)),
+            ArtTodData::parse(&buf).unwrap(),
+        );
+    }
+
+    fn tod(uids: Vec<Uid>) -> ArtTodData {
+        ArtTodData {
+            port: 1,
+            bind_index: 1,
+            port_address: port_address(),
+            nak: false,
+            uid_total: uids.len() as u16,
+            block_count: 0,
+            uids,
+        }
+    }
+
+    fn device_info() -> DeviceInfo {
+        DeviceInfo {
+            protocol_version: 0x0100,
+            model: 0x0042,
+            footprint: 8,
+            personality: 1,
+            personality_count: 2,
+            start_address: Some(1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_gets_and_sets_parameters_of_discovered_device() {
+        let _lock = TEST_PORT_LOCK.lock();
+        let node = node();
+        let rdm = rdm();
+
+        receive_tod(&rdm, tod(vec![DEVICE]));
+        let device = rdm.device(DEVICE).unwrap();
+        assert_eq!(device.node, NODE);
+        assert_eq!(device.port_address, port_address());
+        assert_eq!(device.info, None);
+
+        let request = receive_request(&node);
+        assert_eq!(request.command_class, CommandClass::Get as u8);
+        assert_eq!(request.pid, Pid::DeviceInfo as u16);
+        let mut data = Vec::new();
+        device_info().encode(&mut data);
+        respond(&rdm, &request, ResponseType::Ack, data);
+        assert_eq!(rdm.device(DEVICE).unwrap().info, Some(device_info()));
+
+        rdm.set_start_address(DEVICE, 300).unwrap();
+        let request = receive_request(&node);
+        assert_eq!(request.command_class, CommandClass::Set as u8);
+        assert_eq!(request.pid, Pid::DmxStartAddress as u16);
+        assert_eq!(request.data, vec![0x01, 0x2c]);
+        respond(&rdm, &request, ResponseType::Ack, Vec::new());
+        let info = rdm.device(DEVICE).unwrap().info.unwrap();
+        assert_eq!(info.start_address, Some(300));
+
+        rdm.identify(DEVICE, true).unwrap();
+        let request = receive_request(&node);
+        assert_eq!(request.pid, Pid::IdentifyDevice as u16);
+        assert_eq!(request.data, vec![1]);
+        respond(&rdm, &request, ResponseType::Ack, Vec::new());
+        assert!(rdm.device(DEVICE).unwrap().identify);
+
+        rdm.set_personality(DEVICE, 2).unwrap();
+        let request = receive_request(&node);
+        assert_eq!(request.pid, Pid::DmxPersonality as u16);
+        assert_eq!(request.data, vec![2]);
+        respond(&rdm, &request, ResponseType::Ack, Vec::new());
+        assert_eq!(rdm.device(DEVICE).unwrap().info.unwrap().personality, 2);
+        // A new personality is followed by a fresh DEVICE_INFO.
+        let request = receive_request(&node);
+        assert_eq!(request.command_class, CommandClass::Get as u8);
+        assert_eq!(request.pid, Pid::DeviceInfo as u16);
+    }
+
+    #[test]
+    fn test_records_refused_requests() {
+        let _lock = TEST_PORT_LOCK.lock();
+        let node = node();
+        let rdm = rdm();
+        receive_tod(&rdm, tod(vec![DEVICE]));
+        receive_request(&node);
+
+        rdm.get(DEVICE, Pid::DmxStartAddress).unwrap();
+        let request = receive_request(&node);
+        // Data out of range.
+        respond(&rdm, &request, ResponseType::NackReason, vec![0x00, 0x06]);
+        assert_eq!(
+            rdm.device(DEVICE).unwrap().last_error.as_deref(),
+            Some("DmxStartAddress refused: data out of range")
+        );
+
+        // A response to a request we did not send is ignored.
+        respond(&rdm, &request, ResponseType::Ack, vec![0, 42]);
+        assert_eq!(rdm.device(DEVICE).unwrap().info, None);
+
+        assert!(rdm.set_start_address(DEVICE, 513).is_err());
+        assert!(rdm.set_personality(DEVICE, 0).is_err());
+        let unknown = Uid {
+            manufacturer: 0x4a50,
+            device: 8,
+        };
+        assert!(rdm.identify(unknown, true).is_err());
+    }
+
+    #[test]
+    fn test_table_of_devices_round_trips() {
+        let uids = vec![
+            DEVICE,
+            Uid {
+                manufacturer: 0x7a70,
+                device: 0xdead_beef,
+            },
+        ];
+        let mut buf = Vec::new();
+        tod(uids.clone()).encode(&mut buf);
+        assert_eq!(ArtTodData::parse(&buf).unwrap(), tod(uids));
+
+        let nak = ArtTodData {
+            nak: true,
+            ..tod(Vec::new())
+        };
+        buf.clear();
+        nak.encode(&mut buf);
+        assert_eq!(ArtTodData::parse(&buf).unwrap(), nak);
+        let rdm = rdm();
+        receive_tod(&rdm, nak);
+        assert!(rdm.devices().is_empty());
+
+        assert!(ArtTodData::parse(&buf[..20]).is_err());
+        assert!(ArtRdm::parse(&buf).is_err());
+    }
+}
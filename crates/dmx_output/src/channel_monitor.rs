@@ -0,0 +1,330 @@
+use gpui::{
+    actions, div, AnyElement, AppContext, EventEmitter, FocusHandle, FocusableView, IntoElement,
+    Render, StatefulInteractiveElement, Task, View, ViewContext, VisualContext,
+};
+use patch::{FixtureId, PatchStore};
+use std::{collections::BTreeSet, sync::Arc, time::Duration};
+use ui::{
+    h_flex, rems, v_flex, ActiveTheme, Button, Clickable, Color, Disableable, FluentBuilder,
+    Indicator, InteractiveElement, Label, LabelCommon, LabelSize, ParentElement, Selectable,
+    Styled, WindowContext,
+};
+use workspace::{
+    item::{Item, ItemEvent, TabContentParams},
+    Workspace,
+};
+
+use crate::{
+    engine::DmxEngine,
+    universe::{SlotSource, Universe, UniverseId, UNIVERSE_SIZE},
+};
+
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The number of addresses in each row of the grid.
+const COLUMNS: usize = 16;
+
+actions!(tungsten, [ChannelMonitor]);
+
+pub fn init(cx: &mut AppContext) {
+    cx.observe_new_views(move |workspace: &mut Workspace, _| {
+        workspace.register_action(move |workspace, _: &ChannelMonitor, cx| {
+            ChannelMonitorView::open(workspace, cx);
+        });
+    })
+    .detach();
+}
+
+/// How levels are written in the grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LevelFormat {
+    Decimal,
+    Percent,
+    Hex,
+}
+
+impl LevelFormat {
+    fn next(self) -> Self {
+        match self {
+            LevelFormat::Decimal => LevelFormat::Percent,
+            LevelFormat::Percent => LevelFormat::Hex,
+            LevelFormat::Hex => LevelFormat::Decimal,
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            LevelFormat::Decimal => "Decimal",
+            LevelFormat::Percent => "Percent",
+            LevelFormat::Hex => "Hex",
+        }
+    }
+
+    fn format(self, level: u8) -> String {
+        match self {
+            LevelFormat::Decimal => level.to_string(),
+            LevelFormat::Percent => ((u32::from(level) * 100 + 127) / 255).to_string(),
+            LevelFormat::Hex => format!("{level:02X}"),
+        }
+    }
+}
+
+fn source_color(source: SlotSource) -> Color {
+    match source {
+        SlotSource::None => Color::Muted,
+        SlotSource::Playback => Color::Default,
+        SlotSource::Programmer => Color::Accent,
+        SlotSource::Input => Color::Warning,
+    }
+}
+
+/// The patched fixture that takes up an address.
+#[derive(Clone, Copy)]
+struct Owner {
+    id: FixtureId,
+    /// Whether the fixture, or its split part, starts at the address.
+    first: bool,
+}
+
+/// Shows the levels of a universe as they were last sent, colored by
+/// where each came from and labelled with the fixture patched there.
+pub struct ChannelMonitorView {
+    focus_handle: FocusHandle,
+    engine: Option<Arc<DmxEngine>>,
+    store: Option<Arc<PatchStore>>,
+    universe: UniverseId,
+    format: LevelFormat,
+    /// The universes that are sent or patched, to pick from.
+    universes: BTreeSet<UniverseId>,
+    levels: Universe,
+    sources: Vec<SlotSource>,
+    owners: Vec<Option<Owner>>,
+    _refresh_task: Task<()>,
+}
+
+impl ChannelMonitorView {
+    pub fn open(workspace: &mut Workspace, cx: &mut ViewContext<Workspace>) {
+        let existing = workspace
+            .active_pane()
+            .read(cx)
+            .items()
+            .find_map(|item| item.downcast::<ChannelMonitorView>());
+
+        if let Some(existing) = existing {
+            workspace.activate_item(&existing, cx);
+        } else {
+            let view = ChannelMonitorView::new(cx);
+            workspace.add_item_to_active_pane(Box::new(view), None, cx)
+        }
+    }
+
+    pub fn new(cx: &mut ViewContext<Workspace>) -> View<Self> {
+        cx.new_view(|cx: &mut ViewContext<Self>| {
+            let focus_handle = cx.focus_handle();
+            let refresh_task = cx.spawn(|this, mut cx| async move {
+                loop {
+                    cx.background_executor().timer(REFRESH_INTERVAL).await;
+                    if this.update(&mut cx, |this, cx| this.refresh(cx)).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            let mut this = Self {
+                focus_handle,
+                engine: DmxEngine::try_global(cx),
+                store: PatchStore::try_global(cx),
+                universe: 1,
+                format: LevelFormat::Decimal,
+                universes: BTreeSet::new(),
+                levels: Universe::default(),
+                sources: vec![SlotSource::None; UNIVERSE_SIZE],
+                owners: vec![None; UNIVERSE_SIZE],
+                _refresh_task: refresh_task,
+            };
+            this.refresh(cx);
+            this
+        })
+    }
+
+    fn refresh(&mut self, cx: &mut ViewContext<Self>) {
+        let universe = self.universe;
+        let frame = self
+            .engine
+            .as_ref()
+            .and_then(|engine| engine.latest_frame());
+        self.universes.clear();
+        match &frame {
+            Some(frame) => {
+                self.universes.extend(frame.universes.ids());
+                self.levels = frame.universe(universe).cloned().unwrap_or_default();
+                for (address, source) in (1..).zip(self.sources.iter_mut()) {
+                    *source = frame.sources.get(universe, address);
+                }
+            }
+            None => {
+                self.levels = Universe::default();
+                self.sources.fill(SlotSource::None);
+            }
+        }
+
+        self.owners.fill(None);
+        if let Some(store) = &self.store {
+            store.read(|patch| {
+                for fixture in patch.fixtures() {
+                    for (start, len) in fixture.parts() {
+                        self.universes.insert(start.universe);
+                        if start.universe != universe {
+                            continue;
+                        }
+                        let first = usize::from(start.address) - 1;
+                        for (offset, owner) in self
+                            .owners
+                            .iter_mut()
+                            .skip(first)
+                            .take(len.into())
+                            .enumerate()
+                        {
+                            *owner = Some(Owner {
+                                id: fixture.id,
+                                first: offset == 0,
+                            });
+                        }
+                    }
+                }
+            });
+        }
+        cx.notify();
+    }
+
+    fn select_universe(&mut self, universe: UniverseId, cx: &mut ViewContext<Self>) {
+        self.universe = universe.max(1);
+        self.refresh(cx);
+    }
+
+    fn next_format(&mut self, cx: &mut ViewContext<Self>) {
+        self.format = self.format.next();
+        cx.notify();
+    }
+
+    fn render_toolbar(&self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let universe = self.universe;
+        h_flex()
+            .gap_2()
+            .child(
+                Button::new("channel-monitor-previous", "‹")
+                    .disabled(universe <= 1)
+                    .on_click(cx.listener(move |this, _, cx| {
+                        this.select_universe(universe.saturating_sub(1), cx)
+                    })),
+            )
+            .child(Label::new(format!("Universe {universe}")))
+            .child(Button::new("channel-monitor-next", "›").on_click(
+                cx.listener(move |this, _, cx| {
+                    this.select_universe(universe.saturating_add(1), cx)
+                }),
+            ))
+            .children(self.universes.iter().map(|&id| {
+                Button::new(
+                    ("channel-monitor-universe", usize::from(id)),
+                    id.to_string(),
+                )
+                .selected(id == universe)
+                .on_click(cx.listener(move |this, _, cx| this.select_universe(id, cx)))
+            }))
+            .child(
+                Button::new("channel-monitor-format", self.format.title())
+                    .on_click(cx.listener(|this, _, cx| this.next_format(cx))),
+            )
+            .children(
+                [
+                    (SlotSource::Programmer, "Programmer"),
+                    (SlotSource::Playback, "Playback"),
+                    (SlotSource::Input, "Input merge"),
+                ]
+                .into_iter()
+                .map(|(source, title)| {
+                    h_flex()
+                        .gap_1()
+                        .child(Indicator::dot().color(source_color(source)))
+                        .child(Label::new(title).size(LabelSize::Small))
+                }),
+            )
+    }
+
+    fn render_slot(&self, index: usize, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let owner = self.owners[index];
+        v_flex()
+            .w(rems(3.))
+            .px_1()
+            .border_1()
+            .border_color(cx.theme().colors().border)
+            .child(
+                Label::new((index + 1).to_string())
+                    .size(LabelSize::XSmall)
+                    .color(Color::Muted),
+            )
+            .child(
+                Label::new(self.format.format(self.levels[index]))
+                    .color(source_color(self.sources[index])),
+            )
+            .child(
+                Label::new(owner.map_or(String::new(), |owner| owner.id.to_string()))
+                    .size(LabelSize::XSmall)
+                    .color(if owner.is_some_and(|owner| owner.first) {
+                        Color::Default
+                    } else {
+                        Color::Muted
+                    }),
+            )
+    }
+}
+
+impl Render for ChannelMonitorView {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        v_flex()
+            .size_full()
+            .p_4()
+            .gap_2()
+            .bg(cx.theme().colors().tab_active_background)
+            .track_focus(&self.focus_handle)
+            .when(self.engine.is_none(), |this| {
+                this.child(Label::new("The DMX engine is not running").color(Color::Error))
+            })
+            .child(self.render_toolbar(cx))
+            .child(
+                div()
+                    .id("channel-monitor-grid")
+                    .overflow_y_scroll()
+                    .child(
+                        v_flex().children((0..UNIVERSE_SIZE).step_by(COLUMNS).map(|row| {
+                            h_flex().children(
+                                (row..row + COLUMNS).map(|index| self.render_slot(index, cx)),
+                            )
+                        })),
+                    ),
+            )
+    }
+}
+
+impl EventEmitter<ItemEvent> for ChannelMonitorView {}
+
+impl FocusableView for ChannelMonitorView {
+    fn focus_handle(&self, _: &AppContext) -> gpui::FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Item for ChannelMonitorView {
+    type Event = ItemEvent;
+
+    fn tab_content(&self, params: TabContentParams, _: &WindowContext) -> AnyElement {
+        Label::new("Channel Monitor")
+            .color(if params.selected {
+                Color::Default
+            } else {
+                Color::Muted
+            })
+            .into_any_element()
+    }
+}
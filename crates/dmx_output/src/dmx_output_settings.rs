@@ -1,21 +1,265 @@
-use anyhow;
+use anyhow::anyhow;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use settings::SettingsSources;
+use std::collections::BTreeMap;
+
+use crate::{
+    artnet::{ArtNetConfig, ArtNetOutput, PortAddress},
+    artnet_discovery::{ArtNetDiscoveryConfig, LocalArtNetPorts},
+    artnet_rdm::RdmConfig,
+    engine::FRAME_RATES,
+    input::{InputConfig, InputUniverseSettings},
+    playback::PlaybackConfig,
+    routing::{routes_for_sink, validate_routes, RouteSettings},
+    sacn::{default_cid, SacnConfig, SacnOutput},
+    sacn_discovery::{LocalSources, SacnDiscoveryConfig},
+    sink::{SinkConfig, SinkRegistry},
+    universe::UniverseId,
+};
 
 #[derive(Serialize, Deserialize, PartialEq, Default)]
 pub(crate) struct DmxOuputSettings {
-    pub(crate) enable_artnet: bool,
-    pub(crate) enable_sacn: bool,
+    pub(crate) frame_rate: f32,
+    pub(crate) sinks: BTreeMap<String, SinkSettings>,
+    #[serde(default)]
+    pub(crate) routes: Vec<RouteSettings>,
+    pub(crate) artnet_discovery: ArtNetDiscoveryConfig,
+    pub(crate) rdm: RdmConfig,
+    pub(crate) sacn_discovery: SacnDiscoveryConfig,
+    pub(crate) input: InputConfig,
+    pub(crate) playback: PlaybackConfig,
+    /// Replaced by `sinks.artnet.enabled`, which it is applied to on load.
+    #[serde(default)]
+    pub(crate) enable_artnet: Option<bool>,
+    /// Replaced by `sinks.sacn.enabled`, which it is applied to on load.
+    #[serde(default)]
+    pub(crate) enable_sacn: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub(crate) struct SinkSettings {
+    pub(crate) kind: Option<String>,
+    pub(crate) enabled: bool,
+    #[serde(flatten)]
+    pub(crate) options: serde_json::Map<String, serde_json::Value>,
+}
+
+impl DmxOuputSettings {
+    pub(crate) fn sink_kind<'a>(&'a self, name: &'a str) -> &'a str {
+        self.sinks
+            .get(name)
+            .and_then(|sink| sink.kind.as_deref())
+            .unwrap_or(name)
+    }
+
+    pub(crate) fn sink_config(&self, name: &str, registry: &SinkRegistry) -> Option<SinkConfig> {
+        let sink = self.sinks.get(name)?;
+        Some(SinkConfig {
+            name: name.to_string(),
+            routes: routes_for_sink(&self.routes, name, registry.get(self.sink_kind(name))),
+            options: sink.options.clone(),
+        })
+    }
+
+    /// Applies the `enable_artnet` and `enable_sacn` keys of older settings
+    /// files to the sinks that replaced them.
+    fn migrate_enable_keys(&mut self) -> anyhow::Result<()> {
+        for (key, enabled, sink) in [
+            (
+                "enable_artnet",
+                self.enable_artnet.take(),
+                ArtNetOutput::KIND,
+            ),
+            ("enable_sacn", self.enable_sacn.take(), SacnOutput::KIND),
+        ] {
+            let Some(enabled) = enabled else {
+                continue;
+            };
+            let settings = self.sinks.get_mut(sink).ok_or_else(|| {
+                anyhow!(
+                    "{key} has been replaced by sinks.{sink}.enabled, but there is no {sink} sink"
+                )
+            })?;
+            log::warn!("dmx_output.{key} is deprecated, use dmx_output.sinks.{sink}.enabled");
+            settings.enabled = enabled;
+        }
+        Ok(())
+    }
+
+    /// The internal universe routed to an Art-Net port address, or the one
+    /// an Art-Net sink maps to it by default.
+    pub(crate) fn artnet_universe(&self, port_address: PortAddress) -> UniverseId {
+        let address = port_address.to_u16();
+        self.routes
+            .iter()
+            .find(|route| {
+                route.outputs.iter().any(|output| {
+                    self.sink_kind(&output.sink) == ArtNetOutput::KIND
+                        && output
+                            .address
+                            .and_then(|address| address.to_u16().ok())
+                            .or_else(|| route.universe.checked_sub(1))
+                            == Some(address)
+                })
+            })
+            .map_or(address + 1, |route| route.universe)
+    }
+
+    /// The source addresses and port addresses of every enabled Art-Net
+    /// sink.
+    pub(crate) fn local_artnet_ports(&self, registry: &SinkRegistry) -> LocalArtNetPorts {
+        let mut local = LocalArtNetPorts::default();
+        for (name, sink) in &self.sinks {
+            if !sink.enabled || self.sink_kind(name) != ArtNetOutput::KIND {
+                continue;
+            }
+            let Some(config) = self.sink_config(name, registry) else {
+                continue;
+            };
+            let Ok(options) = config.options::<ArtNetConfig>() else {
+                continue;
+            };
+            local
+                .addresses
+                .extend(options.source_addresses(&config.routes));
+            local.port_addresses.extend(
+                config
+                    .routes
+                    .iter()
+                    .filter_map(|route| route.address.or_else(|| route.universe.checked_sub(1))),
+            );
+        }
+        local
+    }
+
+    /// The CIDs and universes of every enabled sACN sink.
+    pub(crate) fn local_sacn_sources(&self, registry: &SinkRegistry) -> LocalSources {
+        let mut local = LocalSources::default();
+        for (name, sink) in &self.sinks {
+            if !sink.enabled || self.sink_kind(name) != SacnOutput::KIND {
+                continue;
+            }
+            let Some(config) = self.sink_config(name, registry) else {
+                continue;
+            };
+            let cid = config
+                .options::<SacnConfig>()
+                .ok()
+                .and_then(|options| options.cid);
+            local.cids.insert(cid.unwrap_or_else(default_cid));
+            local.universes.extend(
+                config
+                    .routes
+                    .iter()
+                    .map(|route| route.address.unwrap_or(route.universe)),
+            );
+        }
+        local
+    }
 }
 
 /// Task-related settings.
 #[derive(Serialize, Deserialize, PartialEq, Default, Clone, JsonSchema)]
 pub(crate) struct DmxOuputSettingsContent {
+    /// How many frames per second are sent to the sinks, from 0.1 to 1000.
+    frame_rate: Option<f32>,
+    /// The outputs frames are sent to, by name.
+    sinks: Option<BTreeMap<String, SinkSettingsContent>>,
+    /// Which sinks and addresses each internal universe is sent to.
+    routes: Option<Vec<RouteSettings>>,
+    /// Polling for Art-Net nodes on the network.
+    artnet_discovery: Option<ArtNetDiscoverySettingsContent>,
+    /// Finding and configuring RDM devices behind Art-Net nodes.
+    rdm: Option<RdmSettingsContent>,
+    /// Listening for other sACN sources and the universes they send.
+    sacn_discovery: Option<SacnDiscoverySettingsContent>,
+    /// Art-Net and sACN universes received and merged into our own.
+    input: Option<InputSettingsContent>,
+    /// Playing a recording back into the engine at startup.
+    playback: Option<PlaybackSettingsContent>,
+    /// Deprecated: use `sinks.artnet.enabled`.
     enable_artnet: Option<bool>,
+    /// Deprecated: use `sinks.sacn.enabled`.
     enable_sacn: Option<bool>,
 }
 
+#[derive(Serialize, Deserialize, PartialEq, Default, Clone, JsonSchema)]
+pub(crate) struct ArtNetDiscoverySettingsContent {
+    /// Whether to poll for Art-Net nodes.
+    enabled: Option<bool>,
+    /// The local interface address to listen on and poll from.
+    bind_address: Option<String>,
+    /// Where to send ArtPoll packets.
+    destination: Option<String>,
+    /// How often to poll, in milliseconds.
+    poll_interval_ms: Option<u64>,
+    /// How long a node is listed after its last reply, in milliseconds.
+    node_timeout_ms: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Default, Clone, JsonSchema)]
+pub(crate) struct RdmSettingsContent {
+    /// Whether to ask Art-Net nodes for the RDM devices they have found.
+    /// Polls for nodes even when Art-Net discovery is disabled.
+    enabled: Option<bool>,
+    /// How often to ask nodes for their tables of devices, in
+    /// milliseconds.
+    tod_interval_ms: Option<u64>,
+    /// How long to wait for a device to answer, in milliseconds.
+    response_timeout_ms: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Default, Clone, JsonSchema)]
+pub(crate) struct SacnDiscoverySettingsContent {
+    /// Whether to listen for sACN Universe Discovery packets.
+    enabled: Option<bool>,
+    /// The local interface address to listen on.
+    bind_address: Option<String>,
+    /// How long a source is listed after its last discovery packet, in
+    /// milliseconds.
+    source_timeout_ms: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Default, Clone, JsonSchema)]
+pub(crate) struct InputSettingsContent {
+    /// How long a source's levels are kept after its last packet, in
+    /// milliseconds.
+    source_timeout_ms: Option<u64>,
+    /// The priority of our own levels and of Art-Net sources, from 0 to
+    /// 200, for universes merged by priority.
+    priority: Option<u8>,
+    /// The internal universes that receive, how each is merged and the
+    /// protocol addresses it receives from.
+    universes: Option<Vec<InputUniverseSettings>>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Default, Clone, JsonSchema)]
+pub(crate) struct PlaybackSettingsContent {
+    /// Whether to play the recording when Tungsten starts.
+    enabled: Option<bool>,
+    /// The recording to play, as written by a `recorder` sink.
+    path: Option<String>,
+    /// Whether to start again from the beginning at the end.
+    #[serde(rename = "loop")]
+    looping: Option<bool>,
+    /// How fast to play, where 1 is the speed it was recorded at.
+    speed: Option<f32>,
+}
+
+/// Settings for a single output.
+#[derive(Serialize, Deserialize, PartialEq, Default, Clone, JsonSchema)]
+pub(crate) struct SinkSettingsContent {
+    /// What kind of sink this is, such as `artnet` or `sacn`.
+    /// Defaults to the sink's name.
+    kind: Option<String>,
+    /// Whether frames are sent to this sink.
+    enabled: Option<bool>,
+    /// Options specific to the kind of sink.
+    #[serde(flatten)]
+    options: serde_json::Map<String, serde_json::Value>,
+}
+
 impl settings::Settings for DmxOuputSettings {
     const KEY: Option<&'static str> = Some("dmx_output");
 
@@ -23,8 +267,29 @@ impl settings::Settings for DmxOuputSettings {
 
     fn load(
         sources: SettingsSources<Self::FileContent>,
-        _: &mut gpui::AppContext,
+        cx: &mut gpui::AppContext,
     ) -> anyhow::Result<Self> {
-        sources.json_merge()
+        let mut settings: Self = sources.json_merge()?;
+        settings.migrate_enable_keys()?;
+        if !FRAME_RATES.contains(&settings.frame_rate) {
+            return Err(anyhow!(
+                "frame_rate must be between {} and {}, not {}",
+                FRAME_RATES.start(),
+                FRAME_RATES.end(),
+                settings.frame_rate
+            ));
+        }
+        let sink_kinds = settings
+            .sinks
+            .keys()
+            .map(|name| (name.clone(), settings.sink_kind(name).to_string()))
+            .collect();
+        validate_routes(
+            &settings.routes,
+            &sink_kinds,
+            cx.try_global::<SinkRegistry>(),
+        )?;
+        settings.input.validate()?;
+        Ok(settings)
     }
 }
@@ -0,0 +1,258 @@
+use anyhow::{anyhow, Result};
+use gpui::{AppContext, Global};
+use parking_lot::{Mutex, RwLock};
+use patch::PatchStore;
+use std::{
+    ops::RangeInclusive,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    input::DmxInput,
+    sink::SinkHandle,
+    status::SinkStatus,
+    universe::{DmxFrame, FrameSources, SlotSource, UniverseBuffer},
+};
+
+/// The frame rates the engine and the routes' send policies can run at.
+pub const FRAME_RATES: RangeInclusive<f32> = 0.1..=1000.;
+
+struct GlobalDmxEngine(Arc<DmxEngine>);
+
+impl Global for GlobalDmxEngine {}
+
+/// Owns the universe buffer that the UI and playback write into, and a
+/// scheduler thread that snapshots it at a fixed rate, writes the levels
+/// of the patched fixtures over it and hands each frame to every enabled
+/// sink.
+pub struct DmxEngine {
+    universes: Arc<RwLock<UniverseBuffer>>,
+    sinks: Arc<Mutex<Vec<SinkHandle>>>,
+    /// A copy of each sink's status, published after every frame so that
+    /// reading it never waits on a sink that is slow to send.
+    sink_status: Arc<Mutex<Vec<SinkStatus>>>,
+    fixtures: Arc<Mutex<Option<Arc<PatchStore>>>>,
+    input: Arc<Mutex<Option<Arc<DmxInput>>>>,
+    failed_sinks: Mutex<Vec<SinkStatus>>,
+    latest_frame: Arc<Mutex<Option<DmxFrame>>>,
+    frame_rate: f32,
+    running: Arc<AtomicBool>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl DmxEngine {
+    pub fn global(cx: &AppContext) -> Arc<Self> {
+        cx.global::<GlobalDmxEngine>().0.clone()
+    }
+
+    pub fn try_global(cx: &AppContext) -> Option<Arc<Self>> {
+        cx.try_global::<GlobalDmxEngine>()
+            .map(|engine| engine.0.clone())
+    }
+
+    pub fn set_global(engine: Arc<Self>, cx: &mut AppContext) {
+        cx.set_global(GlobalDmxEngine(engine));
+    }
+
+    pub fn start(frame_rate: f32, sinks: Vec<SinkHandle>) -> Result<Self> {
+        if !FRAME_RATES.contains(&frame_rate) {
+            return Err(anyhow!(
+                "DMX frame rate {frame_rate} is not between {} and {}",
+                FRAME_RATES.start(),
+                FRAME_RATES.end()
+            ));
+        }
+
+        let universes = Arc::new(RwLock::new(UniverseBuffer::default()));
+        let sink_status = Arc::new(Mutex::new(collect_status(&sinks)));
+        let sinks = Arc::new(Mutex::new(sinks));
+        let fixtures = Arc::new(Mutex::new(None::<Arc<PatchStore>>));
+        let input = Arc::new(Mutex::new(None::<Arc<DmxInput>>));
+        let latest_frame = Arc::new(Mutex::new(None));
+        let running = Arc::new(AtomicBool::new(true));
+        let interval = Duration::from_secs_f32(1. / frame_rate);
+
+        let thread = thread::Builder::new()
+            .name("dmx-frame-scheduler".into())
+            .spawn({
+                let universes = universes.clone();
+                let sinks = sinks.clone();
+                let sink_status = sink_status.clone();
+                let fixtures = fixtures.clone();
+                let input = input.clone();
+                let latest_frame = latest_frame.clone();
+                let running = running.clone();
+                move || {
+                    let mut sequence = 0;
+                    let mut next_frame = Instant::now();
+                    while running.load(Ordering::Relaxed) {
+                        let timestamp = Instant::now();
+                        let mut levels = universes.read().clone();
+                        let mut sources = FrameSources::playback(&levels);
+                        if let Some(fixtures) = &*fixtures.lock() {
+                            fixtures.render(|address, level| {
+                                levels.get_mut(address.universe).set(address.address, level);
+                                sources.set(
+                                    address.universe,
+                                    address.address,
+                                    SlotSource::Programmer,
+                                );
+                            });
+                        }
+                        if let Some(input) = &*input.lock() {
+                            let local = levels.clone();
+                            input.merge(&mut levels, timestamp);
+                            sources.mark_changes(&local, &levels, SlotSource::Input);
+                        }
+                        let frame = DmxFrame {
+                            sequence,
+                            timestamp,
+                            universes: levels,
+                            sources,
+                        };
+                        let mut sinks = sinks.lock();
+                        for handle in sinks.iter_mut() {
+                            handle.status.expected_rate = handle.schedule.expected_rate(frame_rate);
+                            let result = if handle.schedule.is_scheduled() {
+                                let due = handle.schedule.due(&frame);
+                                if due.is_empty() {
+                                    match handle.sink.tick(frame.timestamp) {
+                                        Ok(()) => {
+                                            handle.status.record_skipped(Instant::now());
+                                            continue;
+                                        }
+                                        Err(err) => Err(err),
+                                    }
+                                } else {
+                                    handle.sink.send_universes(&frame, due)
+                                }
+                            } else {
+                                handle.sink.send_frame(&frame)
+                            };
+                            if let Err(err) = &result {
+                                // Only log the first of a run of failures.
+                                if !handle.status.failing {
+                                    log::error!("sink \"{}\" failed: {err:?}", handle.name);
+                                }
+                            }
+                            handle.status.record(&result, Instant::now());
+                        }
+                        *sink_status.lock() = collect_status(&sinks);
+                        drop(sinks);
+                        *latest_frame.lock() = Some(frame);
+                        sequence += 1;
+
+                        next_frame += interval;
+                        let now = Instant::now();
+                        if next_frame > now {
+                            thread::sleep(next_frame - now);
+                        } else {
+                            next_frame = now;
+                        }
+                    }
+
+                    // Dropping the sinks lets them say goodbye to receivers.
+                    sinks.lock().clear();
+                    sink_status.lock().clear();
+                }
+            })?;
+
+        Ok(Self {
+            universes,
+            sinks,
+            sink_status,
+            fixtures,
+            input,
+            failed_sinks: Mutex::default(),
+            latest_frame,
+            frame_rate,
+            running,
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    pub fn frame_rate(&self) -> f32 {
+        self.frame_rate
+    }
+
+    /// Writes into the universe buffer. Changes go out with the next frame.
+    pub fn update<R>(&self, f: impl FnOnce(&mut UniverseBuffer) -> R) -> R {
+        f(&mut self.universes.write())
+    }
+
+    /// Renders the levels of the patched fixtures into every frame.
+    pub fn set_fixtures(&self, fixtures: Arc<PatchStore>) {
+        *self.fixtures.lock() = Some(fixtures);
+    }
+
+    /// Merges levels received from the network into every frame.
+    pub fn set_input(&self, input: Arc<DmxInput>) {
+        *self.input.lock() = Some(input);
+    }
+
+    /// Returns a copy of the current universe buffer, without any
+    /// received levels merged in.
+    pub fn snapshot(&self) -> UniverseBuffer {
+        self.universes.read().clone()
+    }
+
+    /// Returns the most recent frame handed to the sinks.
+    pub fn latest_frame(&self) -> Option<DmxFrame> {
+        self.latest_frame.lock().clone()
+    }
+
+    pub fn update_sinks<R>(&self, f: impl FnOnce(&mut Vec<SinkHandle>) -> R) -> R {
+        let mut sinks = self.sinks.lock();
+        let result = f(&mut sinks);
+        *self.sink_status.lock() = collect_status(&sinks);
+        result
+    }
+
+    /// Records a sink that could not be started, so that it is shown
+    /// alongside the running ones.
+    pub fn report_failed_sink(&self, status: SinkStatus) {
+        self.failed_sinks.lock().push(status);
+    }
+
+    /// Returns the status of every sink, running or not.
+    pub fn sink_status(&self) -> Vec<SinkStatus> {
+        let running = self.running.load(Ordering::Relaxed);
+        self.sink_status
+            .lock()
+            .iter()
+            .map(|status| {
+                let mut status = status.clone();
+                status.running = running;
+                status
+            })
+            .chain(self.failed_sinks.lock().iter().cloned())
+            .collect()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    /// Stops the frame scheduler and shuts down every sink.
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.lock().take() {
+            thread.join().ok();
+        }
+    }
+}
+
+fn collect_status(sinks: &[SinkHandle]) -> Vec<SinkStatus> {
+    sinks.iter().map(|handle| handle.status.clone()).collect()
+}
+
+impl Drop for DmxEngine {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,479 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serialport::SerialPort;
+use std::{
+    io::{Read, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    sink::{DmxSink, SinkConfig, SinkKind},
+    universe::{DmxFrame, UniverseId, UNIVERSE_SIZE},
+};
+
+const START_OF_MESSAGE: u8 = 0x7e;
+const END_OF_MESSAGE: u8 = 0xe7;
+
+/// The widget's break and mark-after-break times are set in units of
+/// 10.67 microseconds.
+const TIME_UNIT_NS: u32 = 10_670;
+const MIN_BREAK_TIME: u8 = 9;
+const MAX_TIME: u8 = 127;
+
+/// The widget sends at least 24 slots, whatever is asked for.
+const MIN_SLOTS: usize = 24;
+
+/// How long to wait for the widget to answer a request.
+const REPLY_TIMEOUT: Duration = Duration::from_millis(500);
+/// How often to try to reopen a widget that has gone away.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Label {
+    GetWidgetParameters = 3,
+    SetWidgetParameters = 4,
+    SendDmx = 6,
+    GetSerialNumber = 10,
+}
+
+/// Writes a widget message: the start byte, label, little-endian length,
+/// data and end byte.
+pub fn write_message(label: Label, data: &[u8], buf: &mut Vec<u8>) {
+    buf.push(START_OF_MESSAGE);
+    buf.push(label as u8);
+    buf.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    buf.extend_from_slice(data);
+    buf.push(END_OF_MESSAGE);
+}
+
+/// Reads the next message from the widget, skipping anything before its
+/// start byte.
+pub fn read_message<R: Read + ?Sized>(reader: &mut R) -> Result<(u8, Vec<u8>)> {
+    let mut byte = [0];
+    loop {
+        reader.read_exact(&mut byte)?;
+        if byte[0] == START_OF_MESSAGE {
+            break;
+        }
+    }
+    let mut header = [0; 3];
+    reader.read_exact(&mut header)?;
+    let mut data = vec![0; u16::from_le_bytes([header[1], header[2]]) as usize];
+    reader.read_exact(&mut data)?;
+    reader.read_exact(&mut byte)?;
+    if byte[0] != END_OF_MESSAGE {
+        return Err(anyhow!(
+            "widget message with label {} is not terminated",
+            header[0]
+        ));
+    }
+    Ok((header[0], data))
+}
+
+/// The widget's reply to Get Widget Parameters and Get Serial Number.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WidgetInfo {
+    pub firmware: u16,
+    pub serial_number: u32,
+    pub break_time: u8,
+    pub mab_time: u8,
+    pub output_rate: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EnttecProConfig {
+    /// The serial port of the widget, such as `/dev/ttyUSB0` or `COM3`.
+    pub port: String,
+    /// The length of the break, in microseconds. The widget's own setting
+    /// is kept when this is not set.
+    #[serde(default)]
+    pub break_us: Option<u32>,
+    /// The length of the mark after break, in microseconds.
+    #[serde(default)]
+    pub mab_us: Option<u32>,
+    /// Packets per second the widget sends, from 1 to 40, or 0 for as
+    /// fast as it can.
+    #[serde(default)]
+    pub output_rate: Option<u8>,
+}
+
+/// Sends one universe to an ENTTEC DMX USB Pro, or any widget that speaks
+/// its serial protocol. Sends universe 1 when nothing is routed to it, and
+/// reopens the widget if it is unplugged.
+pub struct EnttecProOutput {
+    config: EnttecProConfig,
+    universe: UniverseId,
+    port: Option<Box<dyn SerialPort>>,
+    info: Option<WidgetInfo>,
+    last_attempt: Option<Instant>,
+    data: Vec<u8>,
+    buf: Vec<u8>,
+}
+
+impl EnttecProOutput {
+    pub const KIND: &'static str = "enttec_pro";
+
+    pub fn sink_kind() -> SinkKind {
+        SinkKind::unaddressed(Self::build)
+    }
+
+    pub fn new(universe: UniverseId, config: EnttecProConfig) -> Result<Self> {
+        let mut this = Self {
+            config,
+            universe,
+            port: None,
+            info: None,
+            last_attempt: None,
+            data: Vec::with_capacity(UNIVERSE_SIZE + 1),
+            buf: Vec::with_capacity(UNIVERSE_SIZE + 6),
+        };
+        // A widget that is not plugged in yet is reported by `send_frame`,
+        // which keeps trying to open it.
+        if let Err(err) = this.connect() {
+            log::warn!("{err:#}");
+        }
+        Ok(this)
+    }
+
+    pub fn info(&self) -> Option<WidgetInfo> {
+        self.info
+    }
+
+    /// Opens the widget, reads its parameters and serial number, and
+    /// applies any timings from the config.
+    fn connect(&mut self) -> Result<()> {
+        self.last_attempt = Some(Instant::now());
+        let mut port = serialport::new(&self.config.port, 115_200)
+            .timeout(REPLY_TIMEOUT)
+            .open()
+            .with_context(|| format!("opening ENTTEC DMX USB Pro at {}", self.config.port))?;
+
+        let mut info = self.request_parameters(&mut *port)?;
+        info.serial_number = self.request_serial_number(&mut *port)?;
+
+        if self.config.break_us.is_some()
+            || self.config.mab_us.is_some()
+            || self.config.output_rate.is_some()
+        {
+            if let Some(break_us) = self.config.break_us {
+                info.break_time = to_time_units(break_us).clamp(MIN_BREAK_TIME, MAX_TIME);
+            }
+            if let Some(mab_us) = self.config.mab_us {
+                info.mab_time = to_time_units(mab_us).clamp(1, MAX_TIME);
+            }
+            if let Some(output_rate) = self.config.output_rate {
+                info.output_rate = output_rate.min(40);
+            }
+            self.buf.clear();
+            write_message(
+                Label::SetWidgetParameters,
+                &[0, 0, info.break_time, info.mab_time, info.output_rate],
+                &mut self.buf,
+            );
+            port.write_all(&self.buf)?;
+        }
+
+        log::info!(
+            "ENTTEC DMX USB Pro {:08x} at {}, firmware {}.{}",
+            info.serial_number,
+            self.config.port,
+            info.firmware >> 8,
+            info.firmware & 0xff
+        );
+        self.info = Some(info);
+        self.port = Some(port);
+        Ok(())
+    }
+
+    fn request(&mut self, port: &mut dyn SerialPort, label: Label, data: &[u8]) -> Result<Vec<u8>> {
+        self.buf.clear();
+        write_message(label, data, &mut self.buf);
+        port.write_all(&self.buf)?;
+        // The widget may still be sending received DMX or replies to
+        // earlier requests, so skip anything with another label.
+        for _ in 0..8 {
+            let (reply_label, reply) = read_message(port)?;
+            if reply_label == label as u8 {
+                return Ok(reply);
+            }
+        }
+        Err(anyhow!("no reply from widget to label {}", label as u8))
+    }
+
+    fn request_parameters(&mut self, port: &mut dyn SerialPort) -> Result<WidgetInfo> {
+        let reply = self
+            .request(port, Label::GetWidgetParameters, &[0, 0])
+            .context("reading widget parameters")?;
+        if reply.len() < 5 {
+            return Err(anyhow!("widget parameters reply is too short"));
+        }
+        Ok(WidgetInfo {
+            firmware: u16::from_le_bytes([reply[0], reply[1]]),
+            serial_number: 0,
+            break_time: reply[2],
+            mab_time: reply[3],
+            output_rate: reply[4],
+        })
+    }
+
+    fn request_serial_number(&mut self, port: &mut dyn SerialPort) -> Result<u32> {
+        let reply = self
+            .request(port, Label::GetSerialNumber, &[])
+            .context("reading widget serial number")?;
+        let serial: [u8; 4] = reply
+            .get(..4)
+            .and_then(|serial| serial.try_into().ok())
+            .ok_or_else(|| anyhow!("widget serial number reply is too short"))?;
+        // The serial number is BCD, so reads as hex.
+        Ok(u32::from_le_bytes(serial))
+    }
+
+    pub fn build(config: &SinkConfig) -> Result<Box<dyn DmxSink>> {
+        let universe = match config.routes.as_slice() {
+            [route] => route.universe,
+            [] => 1,
+            routes => {
+                return Err(anyhow!(
+                    "an ENTTEC DMX USB Pro sends one universe, but {} are routed to it",
+                    routes.len()
+                ))
+            }
+        };
+        Ok(Box::new(Self::new(universe, config.options()?)?))
+    }
+}
+
+impl DmxSink for EnttecProOutput {
+    fn send_frame(&mut self, frame: &DmxFrame) -> Result<()> {
+        if self.port.is_none() {
+            let reconnect_due = self
+                .last_attempt
+                .is_none_or(|last_attempt| frame.timestamp - last_attempt >= RECONNECT_INTERVAL);
+            if !reconnect_due {
+                return Err(anyhow!(
+                    "ENTTEC DMX USB Pro at {} is not connected",
+                    self.config.port
+                ));
+            }
+            self.connect()?;
+        }
+
+        let levels = frame
+            .universe(self.universe)
+            .map_or(&[0; UNIVERSE_SIZE], |universe| &**universe);
+        self.data.clear();
+        self.data.push(0);
+        self.data.extend_from_slice(levels);
+        self.data.resize(self.data.len().max(MIN_SLOTS + 1), 0);
+
+        self.buf.clear();
+        write_message(Label::SendDmx, &self.data, &mut self.buf);
+        let Some(port) = &mut self.port else {
+            return Ok(());
+        };
+        if let Err(err) = port.write_all(&self.buf) {
+            self.port = None;
+            return Err(err)
+                .with_context(|| format!("sending to ENTTEC DMX USB Pro at {}", self.config.port));
+        }
+        Ok(())
+    }
+}
+
+fn to_time_units(us: u32) -> u8 {
+    let units = (us as u64 * 1000 + TIME_UNIT_NS as u64 / 2) / TIME_UNIT_NS as u64;
+    units.min(u8::MAX as u64) as u8
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{
+        status::SinkStatus,
+        universe::{FrameSources, UniverseBuffer},
+    };
+    use serialport::TTYPort;
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc, Arc,
+        },
+        thread::{self, JoinHandle},
+    };
+
+    const FIRMWARE: u16 = 0x0144;
+    const SERIAL_NUMBER: u32 = 0x1234_5678;
+
+    /// Plays the widget on the master side of a pseudo-terminal, answering
+    /// requests and passing on every message it receives.
+    struct FakeWidget {
+        messages: mpsc::Receiver<(u8, Vec<u8>)>,
+        running: Arc<AtomicBool>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl FakeWidget {
+        fn start(mut master: TTYPort) -> Self {
+            let (sender, messages) = mpsc::channel();
+            let running = Arc::new(AtomicBool::new(true));
+            let thread = thread::spawn({
+                let running = running.clone();
+                move || {
+                    let mut buf = Vec::new();
+                    while running.load(Ordering::Relaxed) {
+                        // Times out every 100ms to check whether to stop.
+                        let Ok((label, data)) = read_message(&mut master) else {
+                            continue;
+                        };
+                        buf.clear();
+                        if label == Label::GetWidgetParameters as u8 {
+                            let [firmware_lo, firmware_hi] = FIRMWARE.to_le_bytes();
+                            write_message(
+                                Label::GetWidgetParameters,
+                                &[firmware_lo, firmware_hi, 9, 1, 40],
+                                &mut buf,
+                            );
+                        } else if label == Label::GetSerialNumber as u8 {
+                            write_message(
+                                Label::GetSerialNumber,
+                                &SERIAL_NUMBER.to_le_bytes(),
+                                &mut buf,
+                            );
+                        }
+                        master.write_all(&buf).unwrap();
+                        sender.send((label, data)).ok();
+                    }
+                }
+            });
+            Self {
+                messages,
+                running,
+                thread: Some(thread),
+            }
+        }
+
+        fn next_message(&self) -> (u8, Vec<u8>) {
+            self.messages
+                .recv_timeout(Duration::from_secs(2))
+                .expect("no message from the output")
+        }
+
+        /// Closes the master side, as if the widget were unplugged.
+        fn unplug(&mut self) {
+            self.running.store(false, Ordering::Relaxed);
+            if let Some(thread) = self.thread.take() {
+                thread.join().unwrap();
+            }
+        }
+    }
+
+    fn frame(universe: UniverseId, levels: &[u8]) -> DmxFrame {
+        let mut universes = UniverseBuffer::default();
+        universes.get_mut(universe).copy_from(levels);
+        DmxFrame {
+            sequence: 0,
+            timestamp: Instant::now(),
+            universes,
+            sources: FrameSources::default(),
+        }
+    }
+
+    #[test]
+    fn test_writes_message() {
+        let mut buf = Vec::new();
+        write_message(Label::SendDmx, &[0, 1, 2], &mut buf);
+        assert_eq!(buf, [START_OF_MESSAGE, 6, 3, 0, 0, 1, 2, END_OF_MESSAGE]);
+        assert_eq!(read_message(&mut &buf[..]).unwrap(), (6, vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn test_talks_to_widget_until_it_is_unplugged() {
+        let (master, slave) = TTYPort::pair().unwrap();
+        let mut widget = FakeWidget::start(master);
+        let mut output = EnttecProOutput::new(
+            2,
+            EnttecProConfig {
+                port: slave.name().unwrap(),
+                break_us: Some(200),
+                mab_us: None,
+                output_rate: Some(30),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(widget.next_message(), (3, vec![0, 0]));
+        assert_eq!(widget.next_message(), (10, vec![]));
+        assert_eq!(widget.next_message(), (4, vec![0, 0, 19, 1, 30]));
+        assert_eq!(
+            output.info(),
+            Some(WidgetInfo {
+                firmware: FIRMWARE,
+                serial_number: SERIAL_NUMBER,
+                break_time: 19,
+                mab_time: 1,
+                output_rate: 30,
+            })
+        );
+
+        let mut levels = [0; UNIVERSE_SIZE];
+        levels[0] = 255;
+        levels[UNIVERSE_SIZE - 1] = 7;
+        output.send_frame(&frame(2, &levels)).unwrap();
+        let (label, data) = widget.next_message();
+        assert_eq!(label, 6);
+        assert_eq!(data.len(), UNIVERSE_SIZE + 1);
+        assert_eq!(data[0], 0);
+        assert_eq!(data[1..], levels);
+
+        // The engine records each frame's result in the sink's status.
+        widget.unplug();
+        let mut status = SinkStatus::new("enttec", EnttecProOutput::KIND);
+        let now = Instant::now();
+        status.record(&output.send_frame(&frame(2, &levels)), now);
+        assert!(status.has_error());
+        assert!(status
+            .last_error
+            .as_deref()
+            .is_some_and(|error| error.starts_with("sending to ENTTEC DMX USB Pro")));
+        status.record(&output.send_frame(&frame(2, &levels)), now);
+        assert!(status.has_error());
+        assert_eq!(status.errors, 2);
+    }
+
+    #[test]
+    fn test_starts_without_widget() {
+        let port = std::env::temp_dir().join(format!("tungsten-no-widget-{}", std::process::id()));
+        let mut output = EnttecProOutput::new(
+            1,
+            EnttecProConfig {
+                port: port.display().to_string(),
+                break_us: None,
+                mab_us: None,
+                output_rate: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(output.info(), None);
+
+        let mut status = SinkStatus::new("enttec", EnttecProOutput::KIND);
+        let frame = frame(1, &[255]);
+        status.record(&output.send_frame(&frame), frame.timestamp);
+        assert!(status
+            .last_error
+            .as_deref()
+            .is_some_and(|error| error.ends_with("is not connected")));
+
+        // Tries to open the widget again once the interval has passed.
+        let later = DmxFrame {
+            timestamp: frame.timestamp + RECONNECT_INTERVAL,
+            ..frame
+        };
+        status.record(&output.send_frame(&later), later.timestamp);
+        assert!(status
+            .last_error
+            .as_deref()
+            .is_some_and(|error| error.starts_with("opening ENTTEC DMX USB Pro")));
+        assert_eq!(status.errors, 2);
+    }
+}
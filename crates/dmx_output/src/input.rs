@@ -0,0 +1,550 @@
+use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+use crate::{
+    artnet::{ArtNetOutput, PortAddress},
+    routing::OutputAddress,
+    sacn::{
+        DataPacket, SacnOutput, DEFAULT_PRIORITY, MAX_PRIORITY, MAX_UNIVERSE, MIN_UNIVERSE,
+        OPTION_STREAM_TERMINATED, START_CODE_ADDRESS_PRIORITY,
+    },
+    universe::{Universe, UniverseBuffer, UniverseId, UNIVERSE_SIZE},
+};
+
+/// How a universe's own levels are combined with the ones received for it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeMode {
+    /// The highest level of any source wins.
+    #[default]
+    Htp,
+    /// The most recently changed level wins.
+    Ltp,
+    /// The source with the highest sACN priority wins, using per-address
+    /// priorities where a source sends them. Sources with the same
+    /// priority are merged HTP.
+    Priority,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InputProtocol {
+    Artnet,
+    Sacn,
+}
+
+impl InputProtocol {
+    fn default_address(self, universe: UniverseId) -> Option<u16> {
+        match self {
+            InputProtocol::Artnet => (ArtNetOutput::sink_kind().default_address)(universe),
+            InputProtocol::Sacn => (SacnOutput::sink_kind().default_address)(universe),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct InputSourceSettings {
+    pub protocol: InputProtocol,
+    /// The Art-Net port address or sACN universe to receive. Defaults to
+    /// the same mapping the outputs use.
+    #[serde(default)]
+    pub address: Option<OutputAddress>,
+}
+
+/// The sources merged into one internal universe.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct InputUniverseSettings {
+    pub universe: UniverseId,
+    #[serde(default)]
+    pub merge: MergeMode,
+    pub sources: Vec<InputSourceSettings>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InputConfig {
+    /// How long a source's levels are kept after its last packet.
+    pub source_timeout_ms: u64,
+    /// The priority of our own levels and of Art-Net sources when merging
+    /// by priority.
+    pub priority: u8,
+    #[serde(default)]
+    pub universes: Vec<InputUniverseSettings>,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            source_timeout_ms: 2500,
+            priority: DEFAULT_PRIORITY,
+            universes: Vec::new(),
+        }
+    }
+}
+
+impl InputConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.priority > MAX_PRIORITY {
+            return Err(anyhow!(
+                "input: priority {} is above {MAX_PRIORITY}",
+                self.priority
+            ));
+        }
+        let mut universes = BTreeSet::new();
+        for input in &self.universes {
+            if input.universe == 0 {
+                return Err(anyhow!(
+                    "input: there is no universe 0, universes start at 1"
+                ));
+            }
+            if !universes.insert(input.universe) {
+                return Err(anyhow!(
+                    "input: universe {} is listed more than once",
+                    input.universe
+                ));
+            }
+            for source in &input.sources {
+                let address = resolve_address(input.universe, source)
+                    .map_err(|err| anyhow!("input: universe {}: {err}", input.universe))?;
+                let valid = match source.protocol {
+                    InputProtocol::Artnet => address <= PortAddress::MAX,
+                    InputProtocol::Sacn => (MIN_UNIVERSE..=MAX_UNIVERSE).contains(&address),
+                };
+                if !valid {
+                    return Err(anyhow!(
+                        "input: universe {} receives from invalid {:?} address {address}",
+                        input.universe,
+                        source.protocol
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn resolve_address(universe: UniverseId, source: &InputSourceSettings) -> Result<u16> {
+    match source.address {
+        Some(address) => address.to_u16(),
+        None => source
+            .protocol
+            .default_address(universe)
+            .ok_or_else(|| anyhow!("there is no default address for universe {universe}")),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SourceId {
+    ArtNet(IpAddr),
+    Sacn(Uuid),
+}
+
+struct RemoteSource {
+    data: Universe,
+    /// The levels at the previous merge, to find what changed for LTP.
+    previous: Universe,
+    priority: u8,
+    address_priority: Option<Universe>,
+    address_priority_seen: Option<Instant>,
+    sequence: Option<u8>,
+    last_seen: Instant,
+}
+
+impl RemoteSource {
+    fn new(priority: u8, now: Instant) -> Self {
+        Self {
+            data: Universe::default(),
+            previous: Universe::default(),
+            priority,
+            address_priority: None,
+            address_priority_seen: None,
+            sequence: None,
+            last_seen: now,
+        }
+    }
+
+    /// The priority of a single slot, where zero means the source is not
+    /// sending it.
+    fn slot_priority(&self, slot: usize) -> u8 {
+        match &self.address_priority {
+            Some(address_priority) => address_priority[slot],
+            None => self.priority,
+        }
+    }
+}
+
+struct MergeUniverse {
+    mode: MergeMode,
+    sources: BTreeMap<SourceId, RemoteSource>,
+    /// Our own levels at the previous merge, to find what changed for LTP.
+    previous_local: Universe,
+    ltp: Universe,
+}
+
+#[derive(Default)]
+struct InputState {
+    artnet: HashMap<u16, Vec<UniverseId>>,
+    sacn: HashMap<u16, Vec<UniverseId>>,
+    universes: BTreeMap<UniverseId, MergeUniverse>,
+}
+
+/// Levels received over Art-Net and sACN, merged into the engine's own
+/// levels every frame.
+pub struct DmxInput {
+    state: Mutex<InputState>,
+    source_timeout: Duration,
+    priority: u8,
+}
+
+impl DmxInput {
+    pub fn new(config: &InputConfig) -> Result<Self> {
+        config.validate()?;
+        let mut state = InputState::default();
+        for input in &config.universes {
+            for source in &input.sources {
+                let address = resolve_address(input.universe, source)?;
+                let addresses = match source.protocol {
+                    InputProtocol::Artnet => &mut state.artnet,
+                    InputProtocol::Sacn => &mut state.sacn,
+                };
+                addresses.entry(address).or_default().push(input.universe);
+            }
+            state.universes.insert(
+                input.universe,
+                MergeUniverse {
+                    mode: input.merge,
+                    sources: BTreeMap::new(),
+                    previous_local: Universe::default(),
+                    ltp: Universe::default(),
+                },
+            );
+        }
+        Ok(Self {
+            state: Mutex::new(state),
+            source_timeout: Duration::from_millis(config.source_timeout_ms),
+            priority: config.priority,
+        })
+    }
+
+    pub fn has_artnet(&self) -> bool {
+        !self.state.lock().artnet.is_empty()
+    }
+
+    /// The sACN universes to listen to.
+    pub fn sacn_universes(&self) -> Vec<u16> {
+        self.state.lock().sacn.keys().copied().collect()
+    }
+
+    pub fn receive_artnet(&self, ip: IpAddr, port_address: PortAddress, data: &[u8]) {
+        let now = Instant::now();
+        let state = &mut *self.state.lock();
+        let Some(universes) = state.artnet.get(&port_address.to_u16()) else {
+            return;
+        };
+        for universe in universes {
+            if let Some(merge) = state.universes.get_mut(universe) {
+                let source = merge
+                    .sources
+                    .entry(SourceId::ArtNet(ip))
+                    .or_insert_with(|| RemoteSource::new(self.priority, now));
+                source.data.copy_from(data);
+                source.last_seen = now;
+            }
+        }
+    }
+
+    /// Takes the levels or per-address priorities of an E1.31 data packet.
+    /// Out of order packets are dropped, and a terminated stream is
+    /// forgotten at once.
+    pub fn receive_sacn(&self, packet: &DataPacket) {
+        let now = Instant::now();
+        let id = SourceId::Sacn(*packet.cid);
+        let state = &mut *self.state.lock();
+        let Some(universes) = state.sacn.get(&packet.universe) else {
+            return;
+        };
+        for universe in universes {
+            let Some(merge) = state.universes.get_mut(universe) else {
+                continue;
+            };
+            if packet.options & OPTION_STREAM_TERMINATED != 0 {
+                merge.sources.remove(&id);
+                continue;
+            }
+            let source = merge
+                .sources
+                .entry(id)
+                .or_insert_with(|| RemoteSource::new(packet.priority, now));
+            if let Some(last) = source.sequence {
+                // E1.31 6.7.2: drop packets up to 20 behind the last one.
+                let behind = packet.sequence.wrapping_sub(last) as i8;
+                if behind <= 0 && behind > -20 {
+                    continue;
+                }
+            }
+            source.sequence = Some(packet.sequence);
+            source.last_seen = now;
+            match packet.start_code {
+                0 => {
+                    source.priority = packet.priority;
+                    source.data.copy_from(packet.data);
+                }
+                START_CODE_ADDRESS_PRIORITY => {
+                    let mut address_priority = Universe::default();
+                    address_priority.copy_from(packet.data);
+                    source.address_priority = Some(address_priority);
+                    source.address_priority_seen = Some(now);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Merges the received levels into `universes`, which holds our own.
+    pub fn merge(&self, universes: &mut UniverseBuffer, now: Instant) {
+        let state = &mut *self.state.lock();
+        for (id, merge) in &mut state.universes {
+            merge.sources.retain(|_, source| {
+                if source
+                    .address_priority_seen
+                    .is_some_and(|seen| now - seen >= self.source_timeout)
+                {
+                    source.address_priority = None;
+                    source.address_priority_seen = None;
+                }
+                now - source.last_seen < self.source_timeout
+            });
+            if merge.sources.is_empty() {
+                // Nothing to merge, but LTP keeps following our own levels.
+                if let Some(local) = universes.get(*id) {
+                    merge.ltp = local.clone();
+                    merge.previous_local = local.clone();
+                }
+                continue;
+            }
+
+            let local = universes.get_mut(*id);
+            match merge.mode {
+                MergeMode::Htp => {
+                    for source in merge.sources.values() {
+                        for (level, remote) in local.iter_mut().zip(source.data.iter()) {
+                            *level = (*level).max(*remote);
+                        }
+                    }
+                }
+                MergeMode::Ltp => {
+                    for slot in 0..UNIVERSE_SIZE {
+                        if local[slot] != merge.previous_local[slot] {
+                            merge.ltp[slot] = local[slot];
+                        }
+                    }
+                    merge.previous_local = local.clone();
+                    for source in merge.sources.values_mut() {
+                        for slot in 0..UNIVERSE_SIZE {
+                            if source.data[slot] != source.previous[slot] {
+                                merge.ltp[slot] = source.data[slot];
+                            }
+                        }
+                        source.previous = source.data.clone();
+                    }
+                    *local = merge.ltp.clone();
+                }
+                MergeMode::Priority => {
+                    for slot in 0..UNIVERSE_SIZE {
+                        let mut priority = self.priority;
+                        let mut level = local[slot];
+                        for source in merge.sources.values() {
+                            let source_priority = source.slot_priority(slot);
+                            if source_priority == 0 {
+                                continue;
+                            }
+                            if source_priority > priority {
+                                priority = source_priority;
+                                level = source.data[slot];
+                            } else if source_priority == priority {
+                                level = level.max(source.data[slot]);
+                            }
+                        }
+                        local[slot] = level;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The sources currently feeding each universe.
+    pub fn sources(&self) -> BTreeMap<UniverseId, Vec<SourceId>> {
+        self.state
+            .lock()
+            .universes
+            .iter()
+            .map(|(universe, merge)| (*universe, merge.sources.keys().copied().collect()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const UNIVERSE: UniverseId = 1;
+    const SACN_UNIVERSE: u16 = 1;
+
+    fn input(merge: MergeMode) -> DmxInput {
+        DmxInput::new(&InputConfig {
+            universes: vec![InputUniverseSettings {
+                universe: UNIVERSE,
+                merge,
+                sources: vec![
+                    InputSourceSettings {
+                        protocol: InputProtocol::Artnet,
+                        address: None,
+                    },
+                    InputSourceSettings {
+                        protocol: InputProtocol::Sacn,
+                        address: None,
+                    },
+                ],
+            }],
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn packet<'a>(cid: &'a Uuid, sequence: u8, priority: u8, data: &'a [u8]) -> DataPacket<'a> {
+        DataPacket {
+            cid,
+            source_name: "test",
+            priority,
+            sync_address: 0,
+            sequence,
+            options: 0,
+            universe: SACN_UNIVERSE,
+            start_code: 0,
+            data,
+        }
+    }
+
+    /// Merges into our own `local` levels and returns the first slots of
+    /// the result.
+    fn merge(input: &DmxInput, local: &[u8], now: Instant) -> Vec<u8> {
+        let mut universes = UniverseBuffer::default();
+        universes.get_mut(UNIVERSE).copy_from(local);
+        input.merge(&mut universes, now);
+        universes.get(UNIVERSE).unwrap()[..local.len()].to_vec()
+    }
+
+    fn artnet_source() -> IpAddr {
+        Ipv4Addr::new(10, 0, 0, 5).into()
+    }
+
+    #[test]
+    fn test_merges_htp() {
+        let input = input(MergeMode::Htp);
+        let cid = Uuid::new_v4();
+        input.receive_artnet(artnet_source(), PortAddress::default(), &[100, 50, 0]);
+        input.receive_sacn(&packet(&cid, 1, 100, &[150, 0, 0]));
+        assert_eq!(merge(&input, &[10, 200, 0], Instant::now()), [150, 200, 0]);
+
+        // Port addresses that are not received are ignored.
+        let other = PortAddress::new(0, 0, 5).unwrap();
+        input.receive_artnet(artnet_source(), other, &[255, 255, 255]);
+        assert_eq!(merge(&input, &[10, 200, 0], Instant::now()), [150, 200, 0]);
+    }
+
+    #[test]
+    fn test_merges_ltp() {
+        let input = input(MergeMode::Ltp);
+        let cid = Uuid::new_v4();
+        let now = Instant::now();
+        input.receive_sacn(&packet(&cid, 1, 100, &[100, 0]));
+        assert_eq!(merge(&input, &[10, 0], now), [100, 0]);
+
+        // Our own level changes, so it takes over.
+        assert_eq!(merge(&input, &[20, 0], now), [20, 0]);
+        // Nothing changes, so the last change holds.
+        input.receive_sacn(&packet(&cid, 2, 100, &[100, 0]));
+        assert_eq!(merge(&input, &[20, 0], now), [20, 0]);
+        // The source changes, so it takes over.
+        input.receive_sacn(&packet(&cid, 3, 100, &[30, 40]));
+        assert_eq!(merge(&input, &[20, 0], now), [30, 40]);
+    }
+
+    #[test]
+    fn test_merges_by_priority() {
+        let input = input(MergeMode::Priority);
+        let high = Uuid::new_v4();
+        let also_high = Uuid::new_v4();
+        let low = Uuid::new_v4();
+        let now = Instant::now();
+        input.receive_sacn(&packet(&high, 1, 150, &[10, 10, 0]));
+        input.receive_sacn(&packet(&also_high, 1, 150, &[20, 5, 0]));
+        input.receive_sacn(&packet(&low, 1, 50, &[255, 255, 255]));
+        // Our own levels have the configured priority of 100, and equal
+        // priorities are merged HTP.
+        assert_eq!(merge(&input, &[0, 0, 255], now), [20, 10, 0]);
+
+        // Per-address priorities: `also_high` stops sending the first slot
+        // and takes the second at a higher priority.
+        let mut priorities = [0; 3];
+        priorities[1] = 200;
+        input.receive_sacn(&DataPacket {
+            start_code: START_CODE_ADDRESS_PRIORITY,
+            ..packet(&also_high, 2, 150, &priorities)
+        });
+        assert_eq!(merge(&input, &[0, 0, 255], now), [10, 5, 0]);
+    }
+
+    #[test]
+    fn test_drops_out_of_order_sacn_packets() {
+        let input = input(MergeMode::Htp);
+        let cid = Uuid::new_v4();
+        let now = Instant::now();
+        input.receive_sacn(&packet(&cid, 10, 100, &[10]));
+        input.receive_sacn(&packet(&cid, 9, 100, &[9]));
+        input.receive_sacn(&packet(&cid, 10, 100, &[11]));
+        // 250 is 16 behind 10 once the sequence wraps.
+        input.receive_sacn(&packet(&cid, 250, 100, &[250]));
+        assert_eq!(merge(&input, &[0], now), [10]);
+
+        input.receive_sacn(&packet(&cid, 11, 100, &[11]));
+        assert_eq!(merge(&input, &[0], now), [11]);
+        // 20 or more behind is taken as a new stream.
+        input.receive_sacn(&packet(&cid, 11u8.wrapping_sub(20), 100, &[247]));
+        assert_eq!(merge(&input, &[0], now), [247]);
+    }
+
+    #[test]
+    fn test_forgets_terminated_and_silent_sources() {
+        let input = input(MergeMode::Htp);
+        let cid = Uuid::new_v4();
+        input.receive_sacn(&packet(&cid, 1, 100, &[100]));
+        input.receive_artnet(artnet_source(), PortAddress::default(), &[50]);
+        assert_eq!(
+            input.sources()[&UNIVERSE],
+            [SourceId::ArtNet(artnet_source()), SourceId::Sacn(cid)]
+        );
+
+        input.receive_sacn(&DataPacket {
+            options: OPTION_STREAM_TERMINATED,
+            ..packet(&cid, 2, 100, &[100])
+        });
+        assert_eq!(
+            input.sources()[&UNIVERSE],
+            [SourceId::ArtNet(artnet_source())]
+        );
+        assert_eq!(merge(&input, &[0], Instant::now()), [50]);
+
+        let later = Instant::now() + Duration::from_millis(2500);
+        assert_eq!(merge(&input, &[0], later), [0]);
+        assert!(input.sources()[&UNIVERSE].is_empty());
+    }
+}
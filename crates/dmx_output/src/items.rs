@@ -1,45 +1,130 @@
-use gpui::{IntoElement, Render, ViewContext, WeakView};
+use gpui::{IntoElement, Render, Task, ViewContext, WeakView};
+use patch::PatchStore;
 use ui::{
-    h_flex, rems, ButtonLike, Color, Icon, IconName, IconSize, Label, LabelCommon, LabelSize,
-    ParentElement, Styled,
+    h_flex, rems, ButtonLike, Clickable, Color, FluentBuilder, Icon, IconName, IconSize, Indicator,
+    Label, LabelCommon, LabelSize, ParentElement, Styled,
 };
 use workspace::{item::ItemHandle, StatusItemView, Workspace};
 
+use crate::{
+    engine::DmxEngine,
+    output_status::{sink_color, OutputStatusView, REFRESH_INTERVAL},
+    status::SinkStatus,
+};
+
 pub struct DmxIndicator {
     workspace: WeakView<Workspace>,
+    sinks: Vec<SinkStatus>,
+    engine_running: bool,
+    /// Pairs of patched fixtures that share addresses, counted as warnings.
+    patch_conflicts: usize,
+    _refresh_task: Task<()>,
 }
 
 impl DmxIndicator {
-    pub fn new(workspace: &Workspace, _cx: &mut ViewContext<Self>) -> Self {
-        Self {
+    pub fn new(workspace: &Workspace, cx: &mut ViewContext<Self>) -> Self {
+        let refresh_task = cx.spawn(|this, mut cx| async move {
+            loop {
+                cx.background_executor().timer(REFRESH_INTERVAL).await;
+                if this.update(&mut cx, |this, cx| this.refresh(cx)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut this = Self {
             workspace: workspace.weak_handle(),
+            sinks: Vec::new(),
+            engine_running: false,
+            patch_conflicts: 0,
+            _refresh_task: refresh_task,
+        };
+        this.refresh(cx);
+        this
+    }
+
+    fn refresh(&mut self, cx: &mut ViewContext<Self>) {
+        match DmxEngine::try_global(cx) {
+            Some(engine) => {
+                self.sinks = engine.sink_status();
+                self.engine_running = engine.is_running();
+            }
+            None => {
+                self.sinks.clear();
+                self.engine_running = false;
+            }
+        }
+        self.patch_conflicts = PatchStore::try_global(cx)
+            .map_or(0, |store| store.read(|patch| patch.conflicts().len()));
+        cx.notify();
+    }
+
+    fn open_output_status(&mut self, cx: &mut ViewContext<Self>) {
+        if let Some(workspace) = self.workspace.upgrade() {
+            workspace.update(cx, |workspace, cx| OutputStatusView::open(workspace, cx));
         }
     }
 }
 
 impl Render for DmxIndicator {
-    fn render(&mut self, _cx: &mut ViewContext<Self>) -> impl IntoElement {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let errors = self.sinks.iter().filter(|sink| sink.has_error()).count()
+            + usize::from(!self.engine_running);
+        let warnings =
+            self.sinks.iter().filter(|sink| sink.has_warning()).count() + self.patch_conflicts;
+
         h_flex().h(rems(1.375)).gap_2().child(
-            ButtonLike::new("diagnostic-indicator").child(
-                h_flex()
-                    .gap_1()
-                    .child(
-                        Icon::new(IconName::XCircle)
-                            .size(IconSize::Small)
-                            .color(Color::Error),
-                    )
-                    .child(Label::new("0".to_string()).size(LabelSize::Small))
-                    .child(
-                        Icon::new(IconName::ExclamationTriangle)
-                            .size(IconSize::Small)
-                            .color(Color::Warning),
-                    )
-                    .child(Label::new("0".to_string()).size(LabelSize::Small)),
-            ),
+            ButtonLike::new("dmx-output-indicator")
+                .child(
+                    h_flex()
+                        .gap_1()
+                        .children(self.sinks.iter().map(|sink| {
+                            h_flex()
+                                .gap_1()
+                                .child(Indicator::dot().color(sink_color(sink)))
+                                .child(Label::new(sink.name.clone()).size(LabelSize::Small))
+                                .child(
+                                    Label::new(rate_label(sink))
+                                        .size(LabelSize::Small)
+                                        .color(Color::Muted),
+                                )
+                                .when(sink.policy.is_some(), |this| {
+                                    this.child(
+                                        Icon::new(IconName::Filter)
+                                            .size(IconSize::XSmall)
+                                            .color(Color::Muted),
+                                    )
+                                })
+                        }))
+                        .child(
+                            Icon::new(IconName::XCircle)
+                                .size(IconSize::Small)
+                                .color(Color::Error),
+                        )
+                        .child(Label::new(errors.to_string()).size(LabelSize::Small))
+                        .child(
+                            Icon::new(IconName::ExclamationTriangle)
+                                .size(IconSize::Small)
+                                .color(Color::Warning),
+                        )
+                        .child(Label::new(warnings.to_string()).size(LabelSize::Small)),
+                )
+                .on_click(cx.listener(|this, _, cx| this.open_output_status(cx))),
         )
     }
 }
 
+/// The frame rate of a sink, with the rate its send policies allow when
+/// they slow it down.
+fn rate_label(sink: &SinkStatus) -> String {
+    match (&sink.policy, sink.expected_rate) {
+        (Some(_), Some(expected_rate)) => {
+            format!("{:.0}/{expected_rate:.0}", sink.frames_per_second)
+        }
+        _ => format!("{:.0}", sink.frames_per_second),
+    }
+}
+
 impl StatusItemView for DmxIndicator {
     fn set_active_pane_item(
         &mut self,
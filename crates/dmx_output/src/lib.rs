@@ -1,10 +1,191 @@
+pub mod artnet;
+pub mod artnet_discovery;
+pub mod artnet_rdm;
+pub mod channel_monitor;
 mod dmx_output_settings;
+pub mod engine;
+pub mod enttec;
+pub mod input;
 pub mod items;
+pub mod node_browser;
+pub mod open_dmx;
+pub mod output_status;
+pub mod playback;
+pub mod rdm;
+pub mod rdm_devices;
+pub mod recording;
+pub mod routing;
+pub mod sacn;
+pub mod sacn_discovery;
+pub mod sacn_sources;
+pub mod schedule;
+pub mod sink;
+pub mod status;
+pub mod universe;
 
+use artnet::ArtNetOutput;
+use artnet_discovery::ArtNetDiscovery;
 use dmx_output_settings::DmxOuputSettings;
+pub use engine::DmxEngine;
+use enttec::EnttecProOutput;
 use gpui::AppContext;
+use input::DmxInput;
+use open_dmx::OpenDmxOutput;
+use patch::PatchStore;
+use playback::Playback;
+use recording::RecorderSink;
+use sacn::SacnOutput;
+use sacn_discovery::SacnDiscovery;
 use settings::Settings;
+pub use sink::{DmxSink, NullSink, SinkConfig, SinkHandle, SinkKind, SinkRegistry};
+pub use status::SinkStatus;
+use std::sync::Arc;
+pub use universe::{DmxFrame, FrameSources, SlotSource, Universe, UniverseBuffer, UniverseId};
+
+/// The file the sACN CID is saved in, next to the patch.
+const SACN_CID_FILE: &str = "sacn_cid.txt";
 
 pub fn init(cx: &mut AppContext) {
+    // Registered first so that routes can be checked against the sink kinds.
+    let mut registry = SinkRegistry::default();
+    registry.register(ArtNetOutput::KIND, ArtNetOutput::sink_kind());
+    registry.register(SacnOutput::KIND, SacnOutput::sink_kind());
+    registry.register(EnttecProOutput::KIND, EnttecProOutput::sink_kind());
+    registry.register(OpenDmxOutput::KIND, OpenDmxOutput::sink_kind());
+    registry.register(RecorderSink::KIND, RecorderSink::sink_kind());
+    registry.register("null", SinkKind::unaddressed(|_| Ok(Box::new(NullSink))));
+    cx.set_global(registry);
+
     DmxOuputSettings::register(cx);
+
+    output_status::init(cx);
+    node_browser::init(cx);
+    sacn_sources::init(cx);
+    rdm_devices::init(cx);
+    channel_monitor::init(cx);
+
+    let settings = DmxOuputSettings::get_global(cx);
+    let input = if settings.input.universes.is_empty() {
+        None
+    } else {
+        match DmxInput::new(&settings.input) {
+            Ok(input) => Some(Arc::new(input)),
+            Err(err) => {
+                log::error!("failed to set up DMX input: {err:?}");
+                None
+            }
+        }
+    };
+
+    // Kept next to the patch, as the settings files are not written to.
+    if let Some(path) = PatchStore::try_global(cx)
+        .and_then(|store| store.path().map(|path| path.with_file_name(SACN_CID_FILE)))
+    {
+        if let Err(err) = sacn::load_default_cid(&path) {
+            log::error!("failed to load the sACN cid: {err:?}");
+        }
+    }
+
+    let (sinks, failed_sinks) = build_sinks(settings, SinkRegistry::global(cx));
+    match DmxEngine::start(settings.frame_rate, sinks) {
+        Ok(engine) => {
+            for status in failed_sinks {
+                engine.report_failed_sink(status);
+            }
+            if let Some(input) = &input {
+                engine.set_input(input.clone());
+            }
+            if let Some(fixtures) = PatchStore::try_global(cx) {
+                engine.set_fixtures(fixtures);
+            }
+            let engine = Arc::new(engine);
+            DmxEngine::set_global(engine.clone(), cx);
+
+            let playback = if settings.playback.enabled {
+                Playback::start(engine.clone(), &settings.playback)
+                    .map_err(|err| log::error!("failed to start DMX playback: {err:?}"))
+                    .ok()
+            } else {
+                None
+            };
+
+            cx.on_app_quit(move |_| {
+                if let Some(playback) = &playback {
+                    playback.stop();
+                }
+                engine.stop();
+                async {}
+            })
+            .detach();
+        }
+        Err(err) => log::error!("failed to start DMX engine: {err:?}"),
+    }
+
+    // The discovery listeners own the Art-Net and sACN ports, so they also
+    // run when only input needs them.
+    let settings = DmxOuputSettings::get_global(cx);
+    let artnet_input = input.clone().filter(|input| input.has_artnet());
+    if settings.artnet_discovery.enabled || settings.rdm.enabled || artnet_input.is_some() {
+        let local = settings.local_artnet_ports(SinkRegistry::global(cx));
+        match ArtNetDiscovery::start(
+            &settings.artnet_discovery,
+            &settings.rdm,
+            local,
+            artnet_input,
+        ) {
+            Ok(discovery) => {
+                let discovery = Arc::new(discovery);
+                ArtNetDiscovery::set_global(discovery.clone(), cx);
+                cx.on_app_quit(move |_| {
+                    discovery.stop();
+                    async {}
+                })
+                .detach();
+            }
+            Err(err) => log::error!("failed to start Art-Net discovery: {err:?}"),
+        }
+    }
+
+    let settings = DmxOuputSettings::get_global(cx);
+    let sacn_input = input.filter(|input| !input.sacn_universes().is_empty());
+    if settings.sacn_discovery.enabled || sacn_input.is_some() {
+        let local = settings.local_sacn_sources(SinkRegistry::global(cx));
+        match SacnDiscovery::start(&settings.sacn_discovery, local, sacn_input) {
+            Ok(discovery) => {
+                let discovery = Arc::new(discovery);
+                SacnDiscovery::set_global(discovery.clone(), cx);
+                cx.on_app_quit(move |_| {
+                    discovery.stop();
+                    async {}
+                })
+                .detach();
+            }
+            Err(err) => log::error!("failed to start sACN discovery: {err:?}"),
+        }
+    }
+}
+
+fn build_sinks(
+    settings: &DmxOuputSettings,
+    registry: &SinkRegistry,
+) -> (Vec<SinkHandle>, Vec<SinkStatus>) {
+    let mut sinks = Vec::new();
+    let mut failed_sinks = Vec::new();
+    for (name, sink) in &settings.sinks {
+        if !sink.enabled {
+            continue;
+        }
+        let Some(config) = settings.sink_config(name, registry) else {
+            continue;
+        };
+        let kind = settings.sink_kind(name);
+        match registry.build(kind, &config) {
+            Ok(handle) => sinks.push(handle),
+            Err(err) => {
+                log::error!("failed to start sink \"{name}\": {err:?}");
+                failed_sinks.push(SinkStatus::failed(name, kind, format!("{err:#}")));
+            }
+        }
+    }
+    (sinks, failed_sinks)
 }
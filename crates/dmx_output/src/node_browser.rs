@@ -0,0 +1,172 @@
+use gpui::{
+    actions, AnyElement, AppContext, EventEmitter, FocusHandle, FocusableView, IntoElement, Render,
+    Task, View, ViewContext, VisualContext,
+};
+use std::time::Duration;
+use ui::{
+    h_flex, v_flex, ActiveTheme, Color, FluentBuilder, InteractiveElement, Label, LabelCommon,
+    LabelSize, ParentElement, Styled, WindowContext,
+};
+use workspace::{
+    item::{Item, ItemEvent, TabContentParams},
+    Workspace,
+};
+
+use crate::{
+    artnet_discovery::{ArtNetDiscovery, ArtNetNode},
+    output_status::cell,
+};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+actions!(tungsten, [ArtNetNodes]);
+
+pub fn init(cx: &mut AppContext) {
+    cx.observe_new_views(move |workspace: &mut Workspace, _| {
+        workspace.register_action(move |workspace, _: &ArtNetNodes, cx| {
+            NodeBrowserView::open(workspace, cx);
+        });
+    })
+    .detach();
+}
+
+/// Lists the Art-Net nodes that have replied to our polls.
+pub struct NodeBrowserView {
+    focus_handle: FocusHandle,
+    nodes: Vec<ArtNetNode>,
+    discovery_running: bool,
+    _refresh_task: Task<()>,
+}
+
+impl NodeBrowserView {
+    pub fn open(workspace: &mut Workspace, cx: &mut ViewContext<Workspace>) {
+        let existing = workspace
+            .active_pane()
+            .read(cx)
+            .items()
+            .find_map(|item| item.downcast::<NodeBrowserView>());
+
+        if let Some(existing) = existing {
+            workspace.activate_item(&existing, cx);
+        } else {
+            let view = NodeBrowserView::new(cx);
+            workspace.add_item_to_active_pane(Box::new(view), None, cx)
+        }
+    }
+
+    pub fn new(cx: &mut ViewContext<Workspace>) -> View<Self> {
+        cx.new_view(|cx: &mut ViewContext<Self>| {
+            let focus_handle = cx.focus_handle();
+            let refresh_task = cx.spawn(|this, mut cx| async move {
+                loop {
+                    cx.background_executor().timer(REFRESH_INTERVAL).await;
+                    if this.update(&mut cx, |this, cx| this.refresh(cx)).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            let mut this = Self {
+                focus_handle,
+                nodes: Vec::new(),
+                discovery_running: false,
+                _refresh_task: refresh_task,
+            };
+            this.refresh(cx);
+            this
+        })
+    }
+
+    fn refresh(&mut self, cx: &mut ViewContext<Self>) {
+        match ArtNetDiscovery::try_global(cx) {
+            Some(discovery) => {
+                self.nodes = discovery.nodes();
+                self.discovery_running = true;
+            }
+            None => {
+                self.nodes.clear();
+                self.discovery_running = false;
+            }
+        }
+        cx.notify();
+    }
+
+    fn render_row(&self, node: &ArtNetNode) -> impl IntoElement {
+        let reply = &node.reply;
+        let ports = reply
+            .ports
+            .iter()
+            .filter_map(|port| match (port.input, port.output) {
+                (_, Some(output)) => Some(format!("out {output}")),
+                (Some(input), None) => Some(format!("in {input}")),
+                (None, None) => None,
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        h_flex()
+            .gap_4()
+            .child(cell(8., Label::new(reply.ip.to_string())))
+            .child(cell(10., Label::new(reply.short_name.clone())))
+            .child(cell(
+                16.,
+                Label::new(reply.long_name.clone()).color(Color::Muted),
+            ))
+            .child(cell(12., Label::new(ports)))
+            .child(cell(
+                5.,
+                Label::new(format!("{}.{}", reply.firmware >> 8, reply.firmware & 0xff)),
+            ))
+            .child(Label::new(reply.node_report.clone()).size(LabelSize::Small))
+    }
+}
+
+impl Render for NodeBrowserView {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let header = h_flex()
+            .gap_4()
+            .child(cell(8., Label::new("IP").color(Color::Muted)))
+            .child(cell(10., Label::new("Short name").color(Color::Muted)))
+            .child(cell(16., Label::new("Long name").color(Color::Muted)))
+            .child(cell(12., Label::new("Ports").color(Color::Muted)))
+            .child(cell(5., Label::new("Firmware").color(Color::Muted)))
+            .child(Label::new("Status").color(Color::Muted));
+
+        v_flex()
+            .size_full()
+            .p_4()
+            .gap_2()
+            .bg(cx.theme().colors().tab_active_background)
+            .track_focus(&self.focus_handle)
+            .when(!self.discovery_running, |this| {
+                this.child(Label::new("Art-Net discovery is not running").color(Color::Error))
+            })
+            .when(self.discovery_running && self.nodes.is_empty(), |this| {
+                this.child(Label::new("No Art-Net nodes found").color(Color::Muted))
+            })
+            .child(header)
+            .children(self.nodes.iter().map(|node| self.render_row(node)))
+    }
+}
+
+impl EventEmitter<ItemEvent> for NodeBrowserView {}
+
+impl FocusableView for NodeBrowserView {
+    fn focus_handle(&self, _: &AppContext) -> gpui::FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Item for NodeBrowserView {
+    type Event = ItemEvent;
+
+    fn tab_content(&self, params: TabContentParams, _: &WindowContext) -> AnyElement {
+        Label::new("Art-Net Nodes")
+            .color(if params.selected {
+                Color::Default
+            } else {
+                Color::Muted
+            })
+            .into_any_element()
+    }
+}
@@ -0,0 +1,391 @@
+use anyhow::{anyhow, Context, Result};
+use parking_lot::{Condvar, Mutex};
+use serde::{Deserialize, Serialize};
+use serialport::{DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::{
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    engine::FRAME_RATES,
+    sink::{DmxSink, SinkConfig, SinkKind},
+    universe::{DmxFrame, UniverseId, UNIVERSE_SIZE},
+};
+
+/// DMX512 is sent at 250 kbaud, 8N2.
+const BAUD_RATE: u32 = 250_000;
+
+/// The shortest break and mark after break a transmitter may send.
+const MIN_BREAK_US: u32 = 92;
+const MIN_MAB_US: u32 = 12;
+
+/// A full universe takes about 22.7ms to send, plus the break and mark
+/// after break, so this is as fast as the line goes.
+const MAX_FRAME_RATE: f32 = 44.;
+
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+/// How often to try to reopen an interface that has gone away.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OpenDmxConfig {
+    /// The serial port of the interface, such as `/dev/ttyUSB0` or `COM3`.
+    pub port: String,
+    /// The length of the break, in microseconds. It may come out a little
+    /// longer, depending on how precisely the OS sleeps.
+    pub break_us: u32,
+    /// The length of the mark after break, in microseconds.
+    pub mab_us: u32,
+    /// The most frames to send per second. Frames from the engine beyond
+    /// this are skipped.
+    pub max_frame_rate: f32,
+}
+
+impl OpenDmxConfig {
+    fn validate(&self) -> Result<()> {
+        if self.break_us < MIN_BREAK_US {
+            return Err(anyhow!(
+                "a break of {}us is shorter than the minimum of {MIN_BREAK_US}us",
+                self.break_us
+            ));
+        }
+        if self.mab_us < MIN_MAB_US {
+            return Err(anyhow!(
+                "a mark after break of {}us is shorter than the minimum of {MIN_MAB_US}us",
+                self.mab_us
+            ));
+        }
+        let min_frame_rate = *FRAME_RATES.start();
+        if !(min_frame_rate..=MAX_FRAME_RATE).contains(&self.max_frame_rate) {
+            return Err(anyhow!(
+                "max_frame_rate must be from {min_frame_rate} to {MAX_FRAME_RATE}, not {}",
+                self.max_frame_rate
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Sends one universe through a "dumb" Open DMX style interface, such as
+/// an FTDI USB serial adapter wired to a line driver. The host makes the
+/// break and mark after break itself, then streams the slots at 250 kbaud.
+/// That takes most of a frame, so it happens on a thread of its own that
+/// always sends the latest frame. Sends universe 1 when nothing is routed
+/// to it, and reopens the interface if it is unplugged.
+pub struct OpenDmxOutput {
+    universe: UniverseId,
+    min_interval: Duration,
+    next_due: Option<Instant>,
+    writer: Arc<Writer>,
+    thread: Option<JoinHandle<()>>,
+    buf: Vec<u8>,
+}
+
+/// What the engine hands the writer thread.
+struct Writer {
+    state: Mutex<WriterState>,
+    wake: Condvar,
+}
+
+#[derive(Default)]
+struct WriterState {
+    /// The start code and slots of the latest frame, until the writer
+    /// takes them.
+    pending: Option<Vec<u8>>,
+    /// Why the interface could not be opened or written to, until a frame
+    /// gets through again.
+    error: Option<String>,
+    stopped: bool,
+}
+
+impl OpenDmxOutput {
+    pub const KIND: &'static str = "open_dmx";
+
+    pub fn sink_kind() -> SinkKind {
+        SinkKind::unaddressed(Self::build)
+    }
+
+    pub fn new(universe: UniverseId, config: OpenDmxConfig) -> Result<Self> {
+        config.validate()?;
+        let min_interval = Duration::from_secs_f32(1. / config.max_frame_rate);
+        let writer = Arc::new(Writer {
+            state: Mutex::default(),
+            wake: Condvar::new(),
+        });
+        // An interface that is not plugged in yet is reported by
+        // `send_frame`, and the writer keeps trying to open it.
+        let port = match connect(&config) {
+            Ok(port) => Some(port),
+            Err(err) => {
+                log::warn!("{err:#}");
+                writer.state.lock().error = Some(format!("{err:#}"));
+                None
+            }
+        };
+        let thread = thread::Builder::new()
+            .name(format!("open-dmx-{}", config.port))
+            .spawn({
+                let writer = writer.clone();
+                move || write_frames(&writer, config, port)
+            })?;
+        Ok(Self {
+            universe,
+            min_interval,
+            next_due: None,
+            writer,
+            thread: Some(thread),
+            buf: Vec::with_capacity(UNIVERSE_SIZE + 1),
+        })
+    }
+
+    pub fn build(config: &SinkConfig) -> Result<Box<dyn DmxSink>> {
+        let universe = match config.routes.as_slice() {
+            [route] => route.universe,
+            [] => 1,
+            routes => {
+                return Err(anyhow!(
+                    "an Open DMX interface sends one universe, but {} are routed to it",
+                    routes.len()
+                ))
+            }
+        };
+        Ok(Box::new(Self::new(universe, config.options()?)?))
+    }
+}
+
+impl DmxSink for OpenDmxOutput {
+    /// Hands the frame to the writer thread, replacing any it has not sent
+    /// yet, and reports why the previous ones did not go out.
+    fn send_frame(&mut self, frame: &DmxFrame) -> Result<()> {
+        if self.next_due.is_some_and(|due| frame.timestamp < due) {
+            return Ok(());
+        }
+        // Keep to the cap on average, even when the engine's frames do not
+        // line up with it, but do not try to catch up after falling behind.
+        self.next_due = Some(match self.next_due {
+            Some(due) if frame.timestamp - due < self.min_interval => due + self.min_interval,
+            _ => frame.timestamp + self.min_interval,
+        });
+
+        let levels = frame
+            .universe(self.universe)
+            .map_or(&[0; UNIVERSE_SIZE], |universe| &**universe);
+        self.buf.clear();
+        self.buf.push(0);
+        self.buf.extend_from_slice(levels);
+
+        let mut state = self.writer.state.lock();
+        state
+            .pending
+            .get_or_insert_with(Vec::new)
+            .clone_from(&self.buf);
+        self.writer.wake.notify_one();
+        match &state.error {
+            Some(error) => Err(anyhow!("{error}")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for OpenDmxOutput {
+    fn drop(&mut self) {
+        self.writer.state.lock().stopped = true;
+        self.writer.wake.notify_one();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+fn connect(config: &OpenDmxConfig) -> Result<Box<dyn SerialPort>> {
+    let port = serialport::new(&config.port, BAUD_RATE)
+        .data_bits(DataBits::Eight)
+        .parity(Parity::None)
+        .stop_bits(StopBits::Two)
+        .flow_control(FlowControl::None)
+        .timeout(WRITE_TIMEOUT)
+        .open()
+        .with_context(|| format!("opening Open DMX interface at {}", config.port))?;
+    log::info!("Open DMX interface at {}", config.port);
+    Ok(port)
+}
+
+/// Runs on the writer thread, sending each frame the engine hands over
+/// until the output is dropped.
+fn write_frames(writer: &Writer, config: OpenDmxConfig, mut port: Option<Box<dyn SerialPort>>) {
+    let mut last_attempt = Instant::now();
+    loop {
+        let data = {
+            let mut state = writer.state.lock();
+            loop {
+                if state.stopped {
+                    return;
+                }
+                if let Some(data) = state.pending.take() {
+                    break data;
+                }
+                writer.wake.wait(&mut state);
+            }
+        };
+
+        let result = match &mut port {
+            Some(port) => send(port.as_mut(), &config, &data)
+                .with_context(|| format!("sending to Open DMX interface at {}", config.port)),
+            None if last_attempt.elapsed() >= RECONNECT_INTERVAL => {
+                last_attempt = Instant::now();
+                connect(&config).and_then(|opened| {
+                    send(port.insert(opened).as_mut(), &config, &data).with_context(|| {
+                        format!("sending to Open DMX interface at {}", config.port)
+                    })
+                })
+            }
+            None => continue,
+        };
+        if result.is_err() {
+            port = None;
+        }
+        writer.state.lock().error = result.err().map(|err| format!("{err:#}"));
+    }
+}
+
+/// Sends the break, the mark after break and then the start code and
+/// slots.
+fn send(port: &mut dyn SerialPort, config: &OpenDmxConfig, data: &[u8]) -> Result<()> {
+    // The previous frame has to be on the wire before the break, or the
+    // break would cut it short.
+    port.flush()?;
+    port.set_break()?;
+    thread::sleep(Duration::from_micros(config.break_us as u64));
+    port.clear_break()?;
+    thread::sleep(Duration::from_micros(config.mab_us as u64));
+    port.write_all(data)?;
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::universe::{FrameSources, UniverseBuffer};
+    use serialport::TTYPort;
+    use std::io::{ErrorKind, Read};
+
+    fn config(port: String, max_frame_rate: f32) -> OpenDmxConfig {
+        OpenDmxConfig {
+            port,
+            break_us: MIN_BREAK_US,
+            mab_us: MIN_MAB_US,
+            max_frame_rate,
+        }
+    }
+
+    /// Reads everything written to the pseudo-terminal so far.
+    fn read_all(master: &mut TTYPort) -> Vec<u8> {
+        let mut received = Vec::new();
+        let mut buf = [0; 1024];
+        loop {
+            match master.read(&mut buf) {
+                Ok(len) => received.extend_from_slice(&buf[..len]),
+                Err(err) if err.kind() == ErrorKind::TimedOut => return received,
+                Err(err) => panic!("reading from the interface failed: {err}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejects_frame_rates_out_of_range() {
+        for max_frame_rate in [0., 1e-9, -1., 45., f32::NAN] {
+            assert!(config(String::new(), max_frame_rate).validate().is_err());
+        }
+        assert!(config(String::new(), 0.1).validate().is_ok());
+        assert!(config(String::new(), MAX_FRAME_RATE).validate().is_ok());
+    }
+
+    #[test]
+    fn test_sends_start_code_and_slots_at_capped_rate() {
+        let (mut master, slave) = TTYPort::pair().unwrap();
+        let mut output = OpenDmxOutput::new(3, config(slave.name().unwrap(), 8.)).unwrap();
+
+        let mut universes = UniverseBuffer::default();
+        let levels = universes.get_mut(3);
+        levels[0] = 255;
+        levels[UNIVERSE_SIZE - 1] = 7;
+        let mut frame = DmxFrame {
+            sequence: 0,
+            timestamp: Instant::now(),
+            universes,
+            sources: FrameSources::default(),
+        };
+        // Every 25ms for 250ms, of which every fifth is due at 8Hz. The
+        // writer only sends the latest frame, so give it time to send each.
+        for _ in 0..11 {
+            output.send_frame(&frame).unwrap();
+            frame.timestamp += Duration::from_millis(25);
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let received = read_all(&mut master);
+        assert_eq!(received.len(), 3 * (UNIVERSE_SIZE + 1));
+        for packet in received.chunks(UNIVERSE_SIZE + 1) {
+            assert_eq!(packet[0], 0);
+            assert_eq!(packet[1..], frame.universes.get(3).unwrap()[..]);
+        }
+    }
+
+    #[test]
+    fn test_sends_on_writer_thread() {
+        let (mut master, slave) = TTYPort::pair().unwrap();
+        let mut output = OpenDmxOutput::new(
+            1,
+            OpenDmxConfig {
+                break_us: 50_000,
+                ..config(slave.name().unwrap(), MAX_FRAME_RATE)
+            },
+        )
+        .unwrap();
+
+        let mut universes = UniverseBuffer::default();
+        universes.get_mut(1)[0] = 1;
+        let mut frame = DmxFrame {
+            sequence: 0,
+            timestamp: Instant::now(),
+            universes,
+            sources: FrameSources::default(),
+        };
+        // The break alone takes longer than the engine waits. Let the
+        // writer start on the first frame before handing it the others.
+        for level in 1..=3 {
+            frame.universes.get_mut(1)[0] = level;
+            let started = Instant::now();
+            output.send_frame(&frame).unwrap();
+            assert!(started.elapsed() < Duration::from_millis(20));
+            frame.timestamp += Duration::from_secs(1);
+            if level == 1 {
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+
+        // The first frame, and then the latest one in place of the second.
+        thread::sleep(Duration::from_millis(150));
+        let received = read_all(&mut master);
+        assert_eq!(received.len(), 2 * (UNIVERSE_SIZE + 1));
+        assert_eq!(received[1], 1);
+        assert_eq!(received[UNIVERSE_SIZE + 2], 3);
+    }
+
+    #[test]
+    fn test_starts_without_interface() {
+        let port =
+            std::env::temp_dir().join(format!("tungsten-no-open-dmx-{}", std::process::id()));
+        let mut output = OpenDmxOutput::new(1, config(port.display().to_string(), 8.)).unwrap();
+        let frame = DmxFrame {
+            sequence: 0,
+            timestamp: Instant::now(),
+            universes: UniverseBuffer::default(),
+            sources: FrameSources::default(),
+        };
+        let err = output.send_frame(&frame).unwrap_err();
+        assert!(err.to_string().starts_with("opening Open DMX interface"));
+    }
+}
@@ -0,0 +1,204 @@
+use gpui::{
+    actions, AnyElement, AppContext, EventEmitter, FocusHandle, FocusableView, IntoElement, Render,
+    Task, View, ViewContext, VisualContext,
+};
+use std::time::Duration;
+use ui::{
+    div, h_flex, rems, v_flex, ActiveTheme, Color, FluentBuilder, Indicator, InteractiveElement,
+    Label, LabelCommon, LabelSize, ParentElement, Styled, WindowContext,
+};
+use workspace::{
+    item::{Item, ItemEvent, TabContentParams},
+    Workspace,
+};
+
+use crate::{engine::DmxEngine, status::SinkStatus};
+
+pub(crate) const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+actions!(tungsten, [OutputStatus]);
+
+pub fn init(cx: &mut AppContext) {
+    cx.observe_new_views(move |workspace: &mut Workspace, _| {
+        workspace.register_action(move |workspace, _: &OutputStatus, cx| {
+            OutputStatusView::open(workspace, cx);
+        });
+    })
+    .detach();
+}
+
+pub(crate) fn sink_color(status: &SinkStatus) -> Color {
+    if status.has_error() {
+        Color::Error
+    } else if status.has_warning() {
+        Color::Warning
+    } else {
+        Color::Accent
+    }
+}
+
+/// Lists every sink with its frame rate, send policy, error counts and
+/// last error.
+pub struct OutputStatusView {
+    focus_handle: FocusHandle,
+    sinks: Vec<SinkStatus>,
+    engine_running: bool,
+    _refresh_task: Task<()>,
+}
+
+impl OutputStatusView {
+    pub fn open(workspace: &mut Workspace, cx: &mut ViewContext<Workspace>) {
+        let existing = workspace
+            .active_pane()
+            .read(cx)
+            .items()
+            .find_map(|item| item.downcast::<OutputStatusView>());
+
+        if let Some(existing) = existing {
+            workspace.activate_item(&existing, cx);
+        } else {
+            let view = OutputStatusView::new(cx);
+            workspace.add_item_to_active_pane(Box::new(view), None, cx)
+        }
+    }
+
+    pub fn new(cx: &mut ViewContext<Workspace>) -> View<Self> {
+        cx.new_view(|cx: &mut ViewContext<Self>| {
+            let focus_handle = cx.focus_handle();
+            let refresh_task = cx.spawn(|this, mut cx| async move {
+                loop {
+                    cx.background_executor().timer(REFRESH_INTERVAL).await;
+                    if this.update(&mut cx, |this, cx| this.refresh(cx)).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            let mut this = Self {
+                focus_handle,
+                sinks: Vec::new(),
+                engine_running: false,
+                _refresh_task: refresh_task,
+            };
+            this.refresh(cx);
+            this
+        })
+    }
+
+    fn refresh(&mut self, cx: &mut ViewContext<Self>) {
+        match DmxEngine::try_global(cx) {
+            Some(engine) => {
+                self.sinks = engine.sink_status();
+                self.engine_running = engine.is_running();
+            }
+            None => {
+                self.sinks.clear();
+                self.engine_running = false;
+            }
+        }
+        cx.notify();
+    }
+
+    fn render_row(&self, status: &SinkStatus) -> impl IntoElement {
+        let state = if !status.running {
+            "stopped"
+        } else if status.failing {
+            "failing"
+        } else if status.rate_dropped {
+            "rate dropped"
+        } else {
+            "running"
+        };
+
+        h_flex()
+            .gap_4()
+            .child(
+                h_flex()
+                    .w(rems(10.))
+                    .gap_2()
+                    .child(Indicator::dot().color(sink_color(status)))
+                    .child(Label::new(status.name.clone())),
+            )
+            .child(cell(
+                6.,
+                Label::new(status.kind.clone()).color(Color::Muted),
+            ))
+            .child(cell(7., Label::new(state)))
+            .child(cell(
+                6.,
+                Label::new(format!("{:.1} fps", status.frames_per_second)),
+            ))
+            .child(cell(
+                10.,
+                Label::new(
+                    status
+                        .policy
+                        .clone()
+                        .unwrap_or_else(|| "every frame".into()),
+                )
+                .color(Color::Muted),
+            ))
+            .child(cell(8., Label::new(status.frames_sent.to_string())))
+            .child(cell(8., Label::new(status.frames_skipped.to_string())))
+            .child(cell(5., Label::new(status.errors.to_string())))
+            .child(
+                Label::new(status.last_error.clone().unwrap_or_default())
+                    .size(LabelSize::Small)
+                    .color(Color::Error),
+            )
+    }
+}
+
+pub(crate) fn cell(width: f32, child: impl IntoElement) -> impl IntoElement {
+    div().w(rems(width)).child(child)
+}
+
+impl Render for OutputStatusView {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let header = h_flex()
+            .gap_4()
+            .child(cell(10., Label::new("Sink").color(Color::Muted)))
+            .child(cell(6., Label::new("Kind").color(Color::Muted)))
+            .child(cell(7., Label::new("State").color(Color::Muted)))
+            .child(cell(6., Label::new("Rate").color(Color::Muted)))
+            .child(cell(10., Label::new("Policy").color(Color::Muted)))
+            .child(cell(8., Label::new("Frames").color(Color::Muted)))
+            .child(cell(8., Label::new("Skipped").color(Color::Muted)))
+            .child(cell(5., Label::new("Errors").color(Color::Muted)))
+            .child(Label::new("Last error").color(Color::Muted));
+
+        v_flex()
+            .size_full()
+            .p_4()
+            .gap_2()
+            .bg(cx.theme().colors().tab_active_background)
+            .track_focus(&self.focus_handle)
+            .when(!self.engine_running, |this| {
+                this.child(Label::new("The DMX engine is not running").color(Color::Error))
+            })
+            .child(header)
+            .children(self.sinks.iter().map(|status| self.render_row(status)))
+    }
+}
+
+impl EventEmitter<ItemEvent> for OutputStatusView {}
+
+impl FocusableView for OutputStatusView {
+    fn focus_handle(&self, _: &AppContext) -> gpui::FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Item for OutputStatusView {
+    type Event = ItemEvent;
+
+    fn tab_content(&self, params: TabContentParams, _: &WindowContext) -> AnyElement {
+        Label::new("Output Status")
+            .color(if params.selected {
+                Color::Default
+            } else {
+                Color::Muted
+            })
+            .into_any_element()
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{engine::DmxEngine, recording::RecordingReader};
+
+/// The longest the playback thread sleeps at once, so that it stops
+/// promptly even when playing back slowly.
+const MAX_SLEEP: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlaybackConfig {
+    pub enabled: bool,
+    /// The recording to play.
+    pub path: PathBuf,
+    /// Whether to start again from the beginning at the end.
+    #[serde(rename = "loop")]
+    pub looping: bool,
+    /// How fast to play, where 1 is the speed it was recorded at.
+    pub speed: f32,
+}
+
+impl Default for PlaybackConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: PathBuf::from("recording.tdmx"),
+            looping: true,
+            speed: 1.,
+        }
+    }
+}
+
+/// Plays a recording back into the engine's universe buffer, as if the
+/// recorded levels were being set live.
+pub struct Playback {
+    running: Arc<AtomicBool>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Playback {
+    pub fn start(engine: Arc<DmxEngine>, config: &PlaybackConfig) -> Result<Self> {
+        if !config.speed.is_finite() || config.speed <= 0. {
+            return Err(anyhow!("invalid playback speed {}", config.speed));
+        }
+        // Open it once here so that a bad path is reported straight away.
+        RecordingReader::open(&config.path)?;
+
+        let path = config.path.clone();
+        let looping = config.looping;
+        let speed = config.speed;
+        let running = Arc::new(AtomicBool::new(true));
+
+        let thread = thread::Builder::new().name("dmx-playback".into()).spawn({
+            let running = running.clone();
+            move || {
+                while running.load(Ordering::Relaxed) {
+                    let duration = match play(&engine, &path, speed, &running) {
+                        Ok(duration) => duration,
+                        Err(err) => {
+                            log::error!("DMX playback of {} failed: {err:?}", path.display());
+                            break;
+                        }
+                    };
+                    // A recording with no frames, or with all of them at
+                    // the start, would loop without ever sleeping.
+                    if !looping || duration.is_none_or(|duration| duration.is_zero()) {
+                        break;
+                    }
+                }
+            }
+        })?;
+
+        Ok(Self {
+            running,
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.thread
+            .lock()
+            .as_ref()
+            .is_some_and(|thread| !thread.is_finished())
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.lock().take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl Drop for Playback {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Plays the recording once through, returning when its last frame was
+/// recorded, or `None` if it has no frames.
+fn play(
+    engine: &DmxEngine,
+    path: &Path,
+    speed: f32,
+    running: &AtomicBool,
+) -> Result<Option<Duration>> {
+    let mut reader = RecordingReader::open(path)?;
+    let started = Instant::now();
+    let mut duration = None;
+    while let Some((elapsed, universes)) = reader.next_frame()? {
+        duration = Some(elapsed);
+        let due = started + elapsed.div_f32(speed);
+        loop {
+            if !running.load(Ordering::Relaxed) {
+                return Ok(duration);
+            }
+            let now = Instant::now();
+            if now >= due {
+                break;
+            }
+            thread::sleep((due - now).min(MAX_SLEEP));
+        }
+
+        engine.update(|buffer| {
+            for (id, levels) in universes.iter() {
+                *buffer.get_mut(id) = levels.clone();
+            }
+        });
+    }
+    Ok(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{recording::RecordingWriter, universe::UniverseBuffer};
+    use std::fs::{self, File};
+
+    fn recording(name: &str, frames: &[(Duration, &UniverseBuffer)]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "tungsten-playback-{name}-{}.tdmx",
+            std::process::id()
+        ));
+        let mut writer = RecordingWriter::new(File::create(&path).unwrap()).unwrap();
+        for (elapsed, universes) in frames {
+            writer.write_frame(*elapsed, universes).unwrap();
+        }
+        writer.flush().unwrap();
+        path
+    }
+
+    fn config(path: PathBuf) -> PlaybackConfig {
+        PlaybackConfig {
+            enabled: true,
+            path,
+            looping: true,
+            speed: 1.,
+        }
+    }
+
+    /// Waits up to a second for playback to finish.
+    fn wait_until_stopped(playback: &Playback) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while playback.is_playing() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        !playback.is_playing()
+    }
+
+    #[test]
+    fn test_stops_looping_recordings_without_duration() {
+        let engine = Arc::new(DmxEngine::start(44., Vec::new()).unwrap());
+
+        let path = recording("empty", &[]);
+        let playback = Playback::start(engine.clone(), &config(path.clone())).unwrap();
+        assert!(wait_until_stopped(&playback));
+        fs::remove_file(path).unwrap();
+
+        let mut universes = UniverseBuffer::default();
+        universes.set(1, 1, 255);
+        let path = recording("single", &[(Duration::ZERO, &universes)]);
+        let playback = Playback::start(engine.clone(), &config(path.clone())).unwrap();
+        assert!(wait_until_stopped(&playback));
+        assert_eq!(engine.snapshot().get(1).unwrap()[0], 255);
+        fs::remove_file(path).unwrap();
+
+        let path = recording(
+            "long",
+            &[
+                (Duration::ZERO, &universes),
+                (Duration::from_millis(10), &universes),
+            ],
+        );
+        let playback = Playback::start(engine.clone(), &config(path.clone())).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(playback.is_playing(), "recordings with a duration loop");
+        playback.stop();
+        fs::remove_file(path).unwrap();
+        engine.stop();
+    }
+}
@@ -0,0 +1,425 @@
+use anyhow::{anyhow, Result};
+use std::{fmt, sync::OnceLock};
+use uuid::Uuid;
+
+/// The DMX start code of RDM packets. ArtRdm carries RDM messages without
+/// it, but it still counts towards the checksum.
+pub const START_CODE_RDM: u8 = 0xcc;
+const SUB_START_CODE_MESSAGE: u8 = 0x01;
+
+/// The length of a message with no parameter data, without the start code
+/// or checksum.
+const MESSAGE_HEADER_LEN: usize = 23;
+
+/// ESTA reserves manufacturer IDs 0x7ff0 to 0x7fff for prototypes, which
+/// is what a controller without its own ID uses.
+const PROTOTYPE_MANUFACTURER: u16 = 0x7ff0;
+
+pub const DEVICE_INFO_LEN: usize = 19;
+
+/// An RDM unique ID: a 16-bit ESTA manufacturer ID and a 32-bit device ID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uid {
+    pub manufacturer: u16,
+    pub device: u32,
+}
+
+impl Uid {
+    pub const BROADCAST: Uid = Uid {
+        manufacturer: 0xffff,
+        device: 0xffff_ffff,
+    };
+
+    pub fn from_bytes(bytes: [u8; 6]) -> Self {
+        Self {
+            manufacturer: u16::from_be_bytes([bytes[0], bytes[1]]),
+            device: u32::from_be_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]),
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; 6] {
+        let [m0, m1] = self.manufacturer.to_be_bytes();
+        let [d0, d1, d2, d3] = self.device.to_be_bytes();
+        [m0, m1, d0, d1, d2, d3]
+    }
+}
+
+impl fmt::Display for Uid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04x}:{:08x}", self.manufacturer, self.device)
+    }
+}
+
+/// The UID Tungsten sends RDM requests from for this session.
+pub fn controller_uid() -> Uid {
+    static CONTROLLER_UID: OnceLock<Uid> = OnceLock::new();
+    *CONTROLLER_UID.get_or_init(|| Uid {
+        manufacturer: PROTOTYPE_MANUFACTURER,
+        device: Uuid::new_v4().as_u128() as u32,
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CommandClass {
+    Get = 0x20,
+    GetResponse = 0x21,
+    Set = 0x30,
+    SetResponse = 0x31,
+}
+
+impl CommandClass {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x20 => Some(CommandClass::Get),
+            0x21 => Some(CommandClass::GetResponse),
+            0x30 => Some(CommandClass::Set),
+            0x31 => Some(CommandClass::SetResponse),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ResponseType {
+    Ack = 0x00,
+    AckTimer = 0x01,
+    NackReason = 0x02,
+    AckOverflow = 0x03,
+}
+
+impl ResponseType {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x00 => Some(ResponseType::Ack),
+            0x01 => Some(ResponseType::AckTimer),
+            0x02 => Some(ResponseType::NackReason),
+            0x03 => Some(ResponseType::AckOverflow),
+            _ => None,
+        }
+    }
+}
+
+/// The parameters Tungsten gets and sets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u16)]
+pub enum Pid {
+    DeviceInfo = 0x0060,
+    DmxPersonality = 0x00e0,
+    DmxStartAddress = 0x00f0,
+    IdentifyDevice = 0x1000,
+}
+
+impl Pid {
+    pub fn from_u16(value: u16) -> Option<Self> {
+        match value {
+            0x0060 => Some(Pid::DeviceInfo),
+            0x00e0 => Some(Pid::DmxPersonality),
+            0x00f0 => Some(Pid::DmxStartAddress),
+            0x1000 => Some(Pid::IdentifyDevice),
+            _ => None,
+        }
+    }
+}
+
+/// An RDM request or response, as carried in ArtRdm: from the sub-start
+/// code to the checksum, without the start code.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RdmMessage {
+    pub destination: Uid,
+    pub source: Uid,
+    pub transaction: u8,
+    /// The port ID of a request, or the response type of a response.
+    pub port_or_response: u8,
+    pub message_count: u8,
+    pub sub_device: u16,
+    pub command_class: u8,
+    pub pid: u16,
+    pub data: Vec<u8>,
+}
+
+impl RdmMessage {
+    pub fn request(
+        destination: Uid,
+        transaction: u8,
+        command_class: CommandClass,
+        pid: Pid,
+        data: Vec<u8>,
+    ) -> Self {
+        Self {
+            destination,
+            source: controller_uid(),
+            transaction,
+            port_or_response: 1,
+            message_count: 0,
+            sub_device: 0,
+            command_class: command_class as u8,
+            pid: pid as u16,
+            data,
+        }
+    }
+
+    pub fn response_type(&self) -> Option<ResponseType> {
+        ResponseType::from_u8(self.port_or_response)
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let data = &self.data[..self.data.len().min(231)];
+        let start = buf.len();
+        buf.push(SUB_START_CODE_MESSAGE);
+        // The message length counts the start code but not the checksum.
+        buf.push((MESSAGE_HEADER_LEN + 1 + data.len()) as u8);
+        buf.extend_from_slice(&self.destination.to_bytes());
+        buf.extend_from_slice(&self.source.to_bytes());
+        buf.push(self.transaction);
+        buf.push(self.port_or_response);
+        buf.push(self.message_count);
+        buf.extend_from_slice(&self.sub_device.to_be_bytes());
+        buf.push(self.command_class);
+        buf.extend_from_slice(&self.pid.to_be_bytes());
+        buf.push(data.len() as u8);
+        buf.extend_from_slice(data);
+        let checksum = checksum(&buf[start..]);
+        buf.extend_from_slice(&checksum.to_be_bytes());
+    }
+
+    pub fn parse(packet: &[u8]) -> Result<Self> {
+        if packet.len() < MESSAGE_HEADER_LEN + 2 || packet[0] != SUB_START_CODE_MESSAGE {
+            return Err(anyhow!("not an RDM message"));
+        }
+        let len = (packet[1] as usize).saturating_sub(1);
+        let data_len = packet[22] as usize;
+        if len != MESSAGE_HEADER_LEN + data_len || packet.len() < len + 2 {
+            return Err(anyhow!("RDM message length does not match its data"));
+        }
+        let expected = u16::from_be_bytes([packet[len], packet[len + 1]]);
+        if checksum(&packet[..len]) != expected {
+            return Err(anyhow!("RDM message has a bad checksum"));
+        }
+        let uid = |offset: usize| Uid::from_bytes(packet[offset..offset + 6].try_into().unwrap());
+        Ok(Self {
+            destination: uid(2),
+            source: uid(8),
+            transaction: packet[14],
+            port_or_response: packet[15],
+            message_count: packet[16],
+            sub_device: u16::from_be_bytes([packet[17], packet[18]]),
+            command_class: packet[19],
+            pid: u16::from_be_bytes([packet[20], packet[21]]),
+            data: packet[MESSAGE_HEADER_LEN..len].to_vec(),
+        })
+    }
+}
+
+/// The sum of every byte of the message, starting with the start code.
+fn checksum(message: &[u8]) -> u16 {
+    message.iter().fold(START_CODE_RDM as u16, |sum, byte| {
+        sum.wrapping_add(*byte as u16)
+    })
+}
+
+/// The reply to a GET of DEVICE_INFO.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub protocol_version: u16,
+    pub model: u16,
+    pub product_category: u16,
+    pub software_version: u32,
+    pub footprint: u16,
+    pub personality: u8,
+    pub personality_count: u8,
+    /// The first DMX address, or `None` for devices without a footprint.
+    pub start_address: Option<u16>,
+    pub sub_device_count: u16,
+    pub sensor_count: u8,
+}
+
+impl DeviceInfo {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < DEVICE_INFO_LEN {
+            return Err(anyhow!("DEVICE_INFO is {} bytes, expected 19", data.len()));
+        }
+        let u16_at = |offset: usize| u16::from_be_bytes([data[offset], data[offset + 1]]);
+        let start_address = u16_at(14);
+        Ok(Self {
+            protocol_version: u16_at(0),
+            model: u16_at(2),
+            product_category: u16_at(4),
+            software_version: u32::from_be_bytes([data[6], data[7], data[8], data[9]]),
+            footprint: u16_at(10),
+            personality: data[12],
+            personality_count: data[13],
+            start_address: (1..=512).contains(&start_address).then_some(start_address),
+            sub_device_count: u16_at(16),
+            sensor_count: data[18],
+        })
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.protocol_version.to_be_bytes());
+        buf.extend_from_slice(&self.model.to_be_bytes());
+        buf.extend_from_slice(&self.product_category.to_be_bytes());
+        buf.extend_from_slice(&self.software_version.to_be_bytes());
+        buf.extend_from_slice(&self.footprint.to_be_bytes());
+        buf.push(self.personality);
+        buf.push(self.personality_count);
+        buf.extend_from_slice(&self.start_address.unwrap_or(0xffff).to_be_bytes());
+        buf.extend_from_slice(&self.sub_device_count.to_be_bytes());
+        buf.push(self.sensor_count);
+    }
+}
+
+/// Describes the reason code of a NACK.
+pub fn nack_reason(data: &[u8]) -> &'static str {
+    let reason = match data {
+        [hi, lo, ..] => u16::from_be_bytes([*hi, *lo]),
+        _ => return "no reason given",
+    };
+    match reason {
+        0x0000 => "unknown PID",
+        0x0001 => "format error",
+        0x0002 => "hardware fault",
+        0x0003 => "proxy reject",
+        0x0004 => "write protected",
+        0x0005 => "unsupported command class",
+        0x0006 => "data out of range",
+        0x0007 => "buffer full",
+        0x0008 => "packet size unsupported",
+        0x0009 => "sub-device out of range",
+        0x000a => "proxy buffer full",
+        _ => "unknown reason",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICE: Uid = Uid {
+        manufacturer: 0x4a50,
+        device: 0x0102_0304,
+    };
+
+    fn round_trip(message: &RdmMessage) -> Vec<u8> {
+        let mut buf = Vec::new();
+        message.encode(&mut buf);
+        assert_eq!(&RdmMessage::parse(&buf).unwrap(), message);
+        buf
+    }
+
+    #[test]
+    fn test_requests_round_trip() {
+        let requests = [
+            (CommandClass::Get, Pid::DeviceInfo, vec![]),
+            (CommandClass::Get, Pid::DmxStartAddress, vec![]),
+            (CommandClass::Set, Pid::DmxStartAddress, vec![0x01, 0x2c]),
+            (CommandClass::Get, Pid::DmxPersonality, vec![]),
+            (CommandClass::Set, Pid::DmxPersonality, vec![3]),
+            (CommandClass::Get, Pid::IdentifyDevice, vec![]),
+            (CommandClass::Set, Pid::IdentifyDevice, vec![1]),
+        ];
+        for (transaction, (command_class, pid, data)) in requests.into_iter().enumerate() {
+            let request = RdmMessage::request(DEVICE, transaction as u8, command_class, pid, data);
+            let buf = round_trip(&request);
+            assert_eq!(buf[0], SUB_START_CODE_MESSAGE);
+            assert_eq!(
+                buf[1] as usize,
+                buf.len() - 1,
+                "length counts the start code"
+            );
+            assert_eq!(&buf[2..8], &[0x4a, 0x50, 0x01, 0x02, 0x03, 0x04]);
+            assert_eq!(&buf[8..14], &controller_uid().to_bytes());
+            assert_eq!(buf[19], command_class as u8);
+            assert_eq!(&buf[20..22], &(pid as u16).to_be_bytes());
+            assert_eq!(buf[22] as usize, request.data.len());
+        }
+    }
+
+    #[test]
+    fn test_responses_round_trip() {
+        let info = DeviceInfo {
+            protocol_version: 0x0100,
+            model: 0x0042,
+            product_category: 0x0101,
+            software_version: 0x0001_0203,
+            footprint: 16,
+            personality: 2,
+            personality_count: 4,
+            start_address: Some(101),
+            sub_device_count: 0,
+            sensor_count: 1,
+        };
+        let mut data = Vec::new();
+        info.encode(&mut data);
+        assert_eq!(data.len(), DEVICE_INFO_LEN);
+        assert_eq!(DeviceInfo::parse(&data).unwrap(), info);
+
+        let responses = [
+            (CommandClass::GetResponse, Pid::DeviceInfo, data),
+            (
+                CommandClass::GetResponse,
+                Pid::DmxStartAddress,
+                vec![0, 101],
+            ),
+            (CommandClass::SetResponse, Pid::DmxStartAddress, vec![]),
+            (CommandClass::GetResponse, Pid::DmxPersonality, vec![2, 4]),
+            (CommandClass::SetResponse, Pid::DmxPersonality, vec![]),
+            (CommandClass::GetResponse, Pid::IdentifyDevice, vec![0]),
+            (CommandClass::SetResponse, Pid::IdentifyDevice, vec![]),
+        ];
+        for (command_class, pid, data) in responses {
+            let response = RdmMessage {
+                destination: controller_uid(),
+                source: DEVICE,
+                transaction: 7,
+                port_or_response: ResponseType::Ack as u8,
+                message_count: 0,
+                sub_device: 0,
+                command_class: command_class as u8,
+                pid: pid as u16,
+                data,
+            };
+            round_trip(&response);
+            assert_eq!(response.response_type(), Some(ResponseType::Ack));
+            assert_eq!(
+                CommandClass::from_u8(response.command_class),
+                Some(command_class)
+            );
+            assert_eq!(Pid::from_u16(response.pid), Some(pid));
+        }
+    }
+
+    #[test]
+    fn test_checksum_counts_start_code() {
+        let mut buf = Vec::new();
+        RdmMessage::request(DEVICE, 1, CommandClass::Set, Pid::IdentifyDevice, vec![1])
+            .encode(&mut buf);
+        let (message, checksum) = buf.split_at(buf.len() - 2);
+        let sum = message
+            .iter()
+            .fold(START_CODE_RDM as u16, |sum, byte| sum + *byte as u16);
+        assert_eq!(checksum, &sum.to_be_bytes());
+
+        let mut corrupted = buf.clone();
+        corrupted[24] ^= 1;
+        assert!(RdmMessage::parse(&corrupted).is_err());
+        let mut truncated = buf.clone();
+        truncated.pop();
+        assert!(RdmMessage::parse(&truncated).is_err());
+        assert!(RdmMessage::parse(&buf[..10]).is_err());
+    }
+
+    #[test]
+    fn test_device_info_without_footprint_has_no_start_address() {
+        let info = DeviceInfo {
+            start_address: None,
+            ..Default::default()
+        };
+        let mut data = Vec::new();
+        info.encode(&mut data);
+        assert_eq!(&data[14..16], &[0xff, 0xff]);
+        assert_eq!(DeviceInfo::parse(&data).unwrap().start_address, None);
+        assert!(DeviceInfo::parse(&data[..18]).is_err());
+    }
+}
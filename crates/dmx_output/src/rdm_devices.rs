@@ -0,0 +1,329 @@
+use fixtures::ProfileKey;
+use gpui::{
+    actions, AnyElement, AppContext, EventEmitter, FocusHandle, FocusableView, IntoElement, Render,
+    Task, View, ViewContext, VisualContext,
+};
+use patch::{DmxAddress, OutputModifiers, PatchStore, PatchedFixture};
+use settings::Settings;
+use std::{collections::HashSet, sync::Arc, time::Duration};
+use ui::{
+    h_flex, rems, v_flex, ActiveTheme, Button, Clickable, Color, Disableable, FluentBuilder,
+    Indicator, InteractiveElement, Label, LabelCommon, LabelSize, ParentElement, Selectable,
+    Styled, WindowContext,
+};
+use workspace::{
+    item::{Item, ItemEvent, TabContentParams},
+    Workspace,
+};
+
+use crate::{
+    artnet_discovery::ArtNetDiscovery,
+    artnet_rdm::{ArtNetRdm, RdmDevice},
+    dmx_output_settings::DmxOuputSettings,
+    output_status::cell,
+    rdm::Uid,
+};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+actions!(tungsten, [RdmDevices]);
+
+pub fn init(cx: &mut AppContext) {
+    cx.observe_new_views(move |workspace: &mut Workspace, _| {
+        workspace.register_action(move |workspace, _: &RdmDevices, cx| {
+            RdmDevicesView::open(workspace, cx);
+        });
+    })
+    .detach();
+}
+
+/// Lists the RDM devices found behind Art-Net nodes, and lets them be
+/// identified, switched between personalities and patched.
+pub struct RdmDevicesView {
+    focus_handle: FocusHandle,
+    rdm: Option<Arc<ArtNetRdm>>,
+    patch: Option<Arc<PatchStore>>,
+    devices: Vec<RdmDevice>,
+    /// The UIDs of the devices that are already patched.
+    patched: HashSet<String>,
+    error: Option<String>,
+    _refresh_task: Task<()>,
+}
+
+impl RdmDevicesView {
+    pub fn open(workspace: &mut Workspace, cx: &mut ViewContext<Workspace>) {
+        let existing = workspace
+            .active_pane()
+            .read(cx)
+            .items()
+            .find_map(|item| item.downcast::<RdmDevicesView>());
+
+        if let Some(existing) = existing {
+            workspace.activate_item(&existing, cx);
+        } else {
+            let view = RdmDevicesView::new(cx);
+            workspace.add_item_to_active_pane(Box::new(view), None, cx)
+        }
+    }
+
+    pub fn new(cx: &mut ViewContext<Workspace>) -> View<Self> {
+        cx.new_view(|cx: &mut ViewContext<Self>| {
+            let focus_handle = cx.focus_handle();
+            let refresh_task = cx.spawn(|this, mut cx| async move {
+                loop {
+                    cx.background_executor().timer(REFRESH_INTERVAL).await;
+                    if this.update(&mut cx, |this, cx| this.refresh(cx)).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            let mut this = Self {
+                focus_handle,
+                rdm: ArtNetDiscovery::try_global(cx).and_then(|discovery| discovery.rdm()),
+                patch: PatchStore::try_global(cx),
+                devices: Vec::new(),
+                patched: HashSet::new(),
+                error: None,
+                _refresh_task: refresh_task,
+            };
+            this.refresh(cx);
+            this
+        })
+    }
+
+    fn refresh(&mut self, cx: &mut ViewContext<Self>) {
+        self.devices = self
+            .rdm
+            .as_ref()
+            .map(|rdm| rdm.devices())
+            .unwrap_or_default();
+        self.patched = self
+            .patch
+            .as_ref()
+            .map(|patch| {
+                patch.read(|patch| {
+                    patch
+                        .fixtures()
+                        .iter()
+                        .filter_map(|fixture| fixture.rdm_uid.clone())
+                        .collect()
+                })
+            })
+            .unwrap_or_default();
+        cx.notify();
+    }
+
+    fn discover(&mut self, cx: &mut ViewContext<Self>) {
+        if let Some(rdm) = &self.rdm {
+            self.error = rdm.discover().err().map(|err| format!("{err:#}"));
+        }
+        cx.notify();
+    }
+
+    fn identify(&mut self, uid: Uid, identify: bool, cx: &mut ViewContext<Self>) {
+        if let Some(rdm) = &self.rdm {
+            self.error = rdm
+                .identify(uid, identify)
+                .err()
+                .map(|err| format!("{err:#}"));
+        }
+        cx.notify();
+    }
+
+    fn next_personality(&mut self, device: &RdmDevice, cx: &mut ViewContext<Self>) {
+        let (Some(rdm), Some(info)) = (&self.rdm, &device.info) else {
+            return;
+        };
+        let personality = info.personality % info.personality_count.max(1) + 1;
+        self.error = rdm
+            .set_personality(device.uid, personality)
+            .err()
+            .map(|err| format!("{err:#}"));
+        cx.notify();
+    }
+
+    /// Patches a device at its start address, in the universe sent to the
+    /// port it was found behind.
+    fn patch_device(&mut self, device: &RdmDevice, cx: &mut ViewContext<Self>) {
+        let (Some(patch), Some(info)) = (&self.patch, device.info) else {
+            return;
+        };
+        let Some(start_address) = info.start_address else {
+            return;
+        };
+        let universe = DmxOuputSettings::get_global(cx).artnet_universe(device.port_address);
+        let uid = device.uid;
+        self.error = patch
+            .update(|patch| {
+                patch.add(PatchedFixture {
+                    id: patch.next_id(),
+                    name: String::new(),
+                    profile: ProfileKey::new(
+                        format!("RDM {:04x}", uid.manufacturer),
+                        format!("Model {:04x}", info.model),
+                    ),
+                    mode: format!("Personality {}", info.personality),
+                    footprint: info.footprint,
+                    address: Some(DmxAddress::new(universe, start_address)?),
+                    split: None,
+                    cells: Vec::new(),
+                    modifiers: OutputModifiers::default(),
+                    rdm_uid: Some(uid.to_string()),
+                })
+            })
+            .err()
+            .map(|err| format!("{err:#}"));
+        self.refresh(cx);
+    }
+
+    fn render_row(&self, device: &RdmDevice, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let color = if device.last_error.is_some() {
+            Color::Error
+        } else if device.info.is_some() {
+            Color::Success
+        } else {
+            Color::Muted
+        };
+        let info = device.info;
+        let uid = device.uid;
+        let patched = self.patched.contains(&uid.to_string());
+
+        h_flex()
+            .gap_4()
+            .child(
+                h_flex()
+                    .w(rems(10.))
+                    .gap_2()
+                    .child(Indicator::dot().color(color))
+                    .child(Label::new(uid.to_string())),
+            )
+            .child(cell(
+                12.,
+                Label::new(format!("{} {}", device.node, device.port_address)),
+            ))
+            .child(cell(
+                6.,
+                Label::new(info.map_or(String::new(), |info| format!("{:04x}", info.model))),
+            ))
+            .child(cell(
+                5.,
+                Label::new(info.map_or(String::new(), |info| info.footprint.to_string())),
+            ))
+            .child(cell(
+                5.,
+                Label::new(
+                    info.and_then(|info| info.start_address)
+                        .map_or("-".to_string(), |address| address.to_string()),
+                ),
+            ))
+            .child(cell(
+                7.,
+                Button::new(
+                    ("rdm-personality", uid.device as usize),
+                    info.map_or(String::new(), |info| {
+                        format!("{}/{}", info.personality, info.personality_count)
+                    }),
+                )
+                .on_click(cx.listener({
+                    let device = device.clone();
+                    move |this, _, cx| this.next_personality(&device, cx)
+                })),
+            ))
+            .child(cell(
+                6.,
+                Button::new(("rdm-identify", uid.device as usize), "Identify")
+                    .selected(device.identify)
+                    .on_click(cx.listener({
+                        let identify = !device.identify;
+                        move |this, _, cx| this.identify(uid, identify, cx)
+                    })),
+            ))
+            .child(cell(
+                6.,
+                Button::new(
+                    ("rdm-patch", uid.device as usize),
+                    if patched { "Patched" } else { "Patch" },
+                )
+                .disabled(
+                    patched
+                        || self.patch.is_none()
+                        || info.and_then(|info| info.start_address).is_none(),
+                )
+                .on_click(cx.listener({
+                    let device = device.clone();
+                    move |this, _, cx| this.patch_device(&device, cx)
+                })),
+            ))
+            .when_some(device.last_error.clone(), |this, error| {
+                this.child(Label::new(error).size(LabelSize::Small).color(Color::Error))
+            })
+    }
+}
+
+impl Render for RdmDevicesView {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let header = h_flex()
+            .gap_4()
+            .child(cell(10., Label::new("UID").color(Color::Muted)))
+            .child(cell(12., Label::new("Node and port").color(Color::Muted)))
+            .child(cell(6., Label::new("Model").color(Color::Muted)))
+            .child(cell(5., Label::new("Footprint").color(Color::Muted)))
+            .child(cell(5., Label::new("Address").color(Color::Muted)))
+            .child(cell(7., Label::new("Personality").color(Color::Muted)))
+            .child(cell(6., Label::new("Identify").color(Color::Muted)))
+            .child(cell(6., Label::new("Patch").color(Color::Muted)))
+            .child(Label::new("Status").color(Color::Muted));
+
+        let devices = self.devices.clone();
+        v_flex()
+            .size_full()
+            .p_4()
+            .gap_2()
+            .bg(cx.theme().colors().tab_active_background)
+            .track_focus(&self.focus_handle)
+            .when(self.rdm.is_none(), |this| {
+                this.child(Label::new("RDM is not enabled").color(Color::Error))
+            })
+            .when(self.rdm.is_some(), |this| {
+                this.child(
+                    h_flex()
+                        .gap_2()
+                        .child(
+                            Button::new("rdm-discover", "Discover")
+                                .on_click(cx.listener(|this, _, cx| this.discover(cx))),
+                        )
+                        .when_some(self.error.clone(), |this, error| {
+                            this.child(Label::new(error).color(Color::Error))
+                        }),
+                )
+            })
+            .when(self.rdm.is_some() && devices.is_empty(), |this| {
+                this.child(Label::new("No RDM devices found").color(Color::Muted))
+            })
+            .child(header)
+            .children(devices.iter().map(|device| self.render_row(device, cx)))
+    }
+}
+
+impl EventEmitter<ItemEvent> for RdmDevicesView {}
+
+impl FocusableView for RdmDevicesView {
+    fn focus_handle(&self, _: &AppContext) -> gpui::FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Item for RdmDevicesView {
+    type Event = ItemEvent;
+
+    fn tab_content(&self, params: TabContentParams, _: &WindowContext) -> AnyElement {
+        Label::new("RDM Devices")
+            .color(if params.selected {
+                Color::Default
+            } else {
+                Color::Muted
+            })
+            .into_any_element()
+    }
+}
@@ -0,0 +1,301 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    sink::{DmxSink, SinkConfig, SinkKind},
+    universe::{DmxFrame, Universe, UniverseBuffer, UniverseId, UNIVERSE_SIZE},
+};
+
+// A recording is a header followed by one record per frame:
+//
+//   header:   b"TDMX", version: u8
+//   frame:    elapsed_us: u64, count: u16, then `count` universes
+//   universe: id: u16, encoding: u8, then either
+//             ENCODING_FULL:   512 levels
+//             ENCODING_SPARSE: n: u16, then `n` of (slot: u16, level: u8)
+//
+// Integers are little-endian. Each frame only holds the universes that
+// changed since the previous one, so unchanging output costs 10 bytes a
+// frame.
+const MAGIC: &[u8; 4] = b"TDMX";
+const VERSION: u8 = 1;
+
+const ENCODING_FULL: u8 = 0;
+const ENCODING_SPARSE: u8 = 1;
+
+/// Sparse universes take 3 bytes per changed slot, so past this many
+/// changes the full universe is smaller.
+const MAX_SPARSE_SLOTS: usize = (UNIVERSE_SIZE - 2) / 3;
+
+/// How often the recorder flushes to disk.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecorderConfig {
+    /// The file to record to. It is overwritten when the recorder starts.
+    pub path: PathBuf,
+}
+
+/// Writes frames to a recording, storing only what changed since the
+/// previous frame.
+pub struct RecordingWriter<W: Write> {
+    writer: W,
+    previous: BTreeMap<UniverseId, Universe>,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> RecordingWriter<W> {
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        Ok(Self {
+            writer,
+            previous: BTreeMap::new(),
+            buf: Vec::new(),
+        })
+    }
+
+    pub fn write_frame(&mut self, elapsed: Duration, universes: &UniverseBuffer) -> Result<()> {
+        self.buf.clear();
+        self.buf
+            .extend_from_slice(&(elapsed.as_micros() as u64).to_le_bytes());
+        self.buf.extend_from_slice(&[0, 0]);
+
+        let mut count = 0u16;
+        for (id, levels) in universes.iter() {
+            let previous = self.previous.entry(id).or_default();
+            let changed = (0..UNIVERSE_SIZE)
+                .filter(|slot| levels[*slot] != previous[*slot])
+                .collect::<Vec<_>>();
+            if changed.is_empty() {
+                continue;
+            }
+
+            count += 1;
+            self.buf.extend_from_slice(&id.to_le_bytes());
+            if changed.len() <= MAX_SPARSE_SLOTS {
+                self.buf.push(ENCODING_SPARSE);
+                self.buf
+                    .extend_from_slice(&(changed.len() as u16).to_le_bytes());
+                for slot in changed {
+                    self.buf.extend_from_slice(&(slot as u16).to_le_bytes());
+                    self.buf.push(levels[slot]);
+                }
+            } else {
+                self.buf.push(ENCODING_FULL);
+                self.buf.extend_from_slice(&levels[..]);
+            }
+            *previous = levels.clone();
+        }
+        self.buf[8..10].copy_from_slice(&count.to_le_bytes());
+
+        self.writer.write_all(&self.buf)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads the frames of a recording back, each with the full levels of
+/// every universe recorded so far.
+pub struct RecordingReader<R: Read> {
+    reader: R,
+    universes: UniverseBuffer,
+}
+
+impl RecordingReader<BufReader<File>> {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("opening DMX recording {}", path.display()))?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read> RecordingReader<R> {
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(anyhow!("not a DMX recording"));
+        }
+        if header[4] != VERSION {
+            return Err(anyhow!("unsupported DMX recording version {}", header[4]));
+        }
+        Ok(Self {
+            reader,
+            universes: UniverseBuffer::default(),
+        })
+    }
+
+    /// Reads the next frame, returning `None` at the end of the recording.
+    pub fn next_frame(&mut self) -> Result<Option<(Duration, &UniverseBuffer)>> {
+        let mut header = [0; 10];
+        match self.reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+        let elapsed = Duration::from_micros(u64::from_le_bytes(header[..8].try_into()?));
+        let count = u16::from_le_bytes([header[8], header[9]]);
+
+        for _ in 0..count {
+            let mut universe_header = [0; 3];
+            self.reader.read_exact(&mut universe_header)?;
+            let id = u16::from_le_bytes([universe_header[0], universe_header[1]]);
+            let levels = self.universes.get_mut(id);
+            match universe_header[2] {
+                ENCODING_FULL => self.reader.read_exact(&mut levels[..])?,
+                ENCODING_SPARSE => {
+                    let mut len = [0; 2];
+                    self.reader.read_exact(&mut len)?;
+                    let mut slots = vec![0; u16::from_le_bytes(len) as usize * 3];
+                    self.reader.read_exact(&mut slots)?;
+                    for slot in slots.chunks_exact(3) {
+                        let index = u16::from_le_bytes([slot[0], slot[1]]) as usize;
+                        if let Some(level) = levels.get_mut(index) {
+                            *level = slot[2];
+                        }
+                    }
+                }
+                encoding => {
+                    return Err(anyhow!(
+                        "unknown encoding {encoding} for universe {id} in DMX recording"
+                    ))
+                }
+            }
+        }
+        Ok(Some((elapsed, &self.universes)))
+    }
+}
+
+/// A sink that records every frame to a file.
+pub struct RecorderSink {
+    writer: RecordingWriter<BufWriter<File>>,
+    universes: Vec<UniverseId>,
+    started: Option<Instant>,
+    last_flush: Instant,
+}
+
+impl RecorderSink {
+    pub const KIND: &'static str = "recorder";
+
+    pub fn sink_kind() -> SinkKind {
+        SinkKind::unaddressed(Self::build)
+    }
+
+    /// Records the routed universes, or every universe when there are no
+    /// routes to the recorder.
+    pub fn new(universes: Vec<UniverseId>, config: RecorderConfig) -> Result<Self> {
+        let file = File::create(&config.path)
+            .with_context(|| format!("creating DMX recording {}", config.path.display()))?;
+        Ok(Self {
+            writer: RecordingWriter::new(BufWriter::new(file))?,
+            universes,
+            started: None,
+            last_flush: Instant::now(),
+        })
+    }
+
+    pub fn build(config: &SinkConfig) -> Result<Box<dyn DmxSink>> {
+        let universes = config.routes.iter().map(|route| route.universe).collect();
+        Ok(Box::new(Self::new(universes, config.options()?)?))
+    }
+}
+
+impl DmxSink for RecorderSink {
+    fn send_frame(&mut self, frame: &DmxFrame) -> Result<()> {
+        let started = *self.started.get_or_insert(frame.timestamp);
+        let elapsed = frame.timestamp - started;
+        if self.universes.is_empty() {
+            self.writer.write_frame(elapsed, &frame.universes)?;
+        } else {
+            let mut universes = UniverseBuffer::default();
+            for id in &self.universes {
+                if let Some(levels) = frame.universe(*id) {
+                    *universes.get_mut(*id) = levels.clone();
+                }
+            }
+            self.writer.write_frame(elapsed, &universes)?;
+        }
+
+        if frame.timestamp - self.last_flush >= FLUSH_INTERVAL {
+            self.last_flush = frame.timestamp;
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RecorderSink {
+    fn drop(&mut self) {
+        self.writer.flush().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_HEADER_LEN: usize = 10;
+    const UNIVERSE_HEADER_LEN: usize = 3;
+
+    #[test]
+    fn test_round_trips_sparse_full_and_unchanged_frames() {
+        let mut sparse = UniverseBuffer::default();
+        sparse.set(1, 1, 255);
+        sparse.set(1, 512, 128);
+
+        let mut full = sparse.clone();
+        full.get_mut(2).copy_from(&[200; UNIVERSE_SIZE]);
+
+        let frames = [
+            (Duration::ZERO, &sparse),
+            (Duration::from_millis(25), &full),
+            (Duration::from_millis(50), &full),
+        ];
+        let mut recording = Vec::new();
+        let mut writer = RecordingWriter::new(&mut recording).unwrap();
+        let mut lens = Vec::new();
+        for (elapsed, universes) in frames {
+            let start = writer.writer.len();
+            writer.write_frame(elapsed, universes).unwrap();
+            lens.push(writer.writer.len() - start);
+        }
+        writer.flush().unwrap();
+
+        assert_eq!(&recording[..5], b"TDMX\x01");
+        // Two changed slots of universe 1.
+        assert_eq!(lens[0], FRAME_HEADER_LEN + UNIVERSE_HEADER_LEN + 2 + 2 * 3);
+        // Universe 2 in full, and nothing for the unchanged universe 1.
+        assert_eq!(
+            lens[1],
+            FRAME_HEADER_LEN + UNIVERSE_HEADER_LEN + UNIVERSE_SIZE
+        );
+        // Nothing changed at all.
+        assert_eq!(lens[2], FRAME_HEADER_LEN);
+
+        let mut reader = RecordingReader::new(&recording[..]).unwrap();
+        for (elapsed, universes) in frames {
+            let (read_elapsed, read_universes) = reader.next_frame().unwrap().unwrap();
+            assert_eq!(read_elapsed, elapsed);
+            assert!(read_universes == universes);
+        }
+        assert!(reader.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_rejects_other_files() {
+        assert!(RecordingReader::new(&b"RIFF\x01"[..]).is_err());
+        assert!(RecordingReader::new(&b"TDMX\x02"[..]).is_err());
+    }
+}
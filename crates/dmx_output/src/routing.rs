@@ -0,0 +1,410 @@
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    net::IpAddr,
+};
+
+use crate::{
+    artnet::PortAddress,
+    engine::FRAME_RATES,
+    sacn::MAX_PRIORITY,
+    sink::{SinkKind, SinkRegistry},
+    universe::UniverseId,
+};
+
+/// A protocol address, either a plain number such as an sACN universe or
+/// a 15-bit Art-Net port address, or an Art-Net Net, Sub-Net and Universe.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum OutputAddress {
+    Number(u16),
+    PortAddress(PortAddress),
+}
+
+impl OutputAddress {
+    pub fn to_u16(self) -> Result<u16> {
+        match self {
+            OutputAddress::Number(address) => Ok(address),
+            OutputAddress::PortAddress(port_address) => PortAddress::new(
+                port_address.net,
+                port_address.sub_net,
+                port_address.universe,
+            )
+            .map(PortAddress::to_u16),
+        }
+    }
+}
+
+/// Where one internal universe is sent.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RouteSettings {
+    /// The internal universe, starting at 1.
+    pub universe: UniverseId,
+    /// The sinks and addresses the universe is sent to.
+    pub outputs: Vec<RouteOutputSettings>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RouteOutputSettings {
+    /// The name of the sink in `dmx_output.sinks`.
+    pub sink: String,
+    /// The Art-Net port address or sACN universe to send on. Defaults
+    /// to the sink's own mapping of the internal universe.
+    #[serde(default)]
+    pub address: Option<OutputAddress>,
+    /// Hosts to send to instead of the sink's default destination.
+    #[serde(default)]
+    pub destinations: Vec<IpAddr>,
+    /// The priority of the data, from 0 to 200, for protocols that have one.
+    #[serde(default)]
+    pub priority: Option<u8>,
+    /// When the universe is sent, if not with every frame.
+    #[serde(default)]
+    pub policy: Option<SendPolicy>,
+}
+
+/// Limits on how often a universe is sent to a sink, for receivers that
+/// cannot keep up with every frame. Levels that change between sends are
+/// coalesced, so the latest ones always go out with the next send.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SendPolicy {
+    /// Send at this many frames per second, rather than the engine's
+    /// frame rate.
+    #[serde(default)]
+    pub refresh_rate: Option<f32>,
+    /// Only send when the levels change, and as a keep-alive when they
+    /// have not for `keep_alive_ms`.
+    #[serde(default)]
+    pub change_only: bool,
+    /// How often unchanged levels are resent when sending only changes.
+    /// Defaults to a second.
+    #[serde(default)]
+    pub keep_alive_ms: Option<u64>,
+    /// The most packets per second sent to each destination, shared among
+    /// all the universes it receives from the sink, however fast the
+    /// levels change. Routes without destinations share the sink's default
+    /// one. Many cheap Art-Net nodes drop packets above about 40 a second
+    /// in all.
+    #[serde(default)]
+    pub max_destination_rate: Option<f32>,
+}
+
+impl SendPolicy {
+    fn validate(&self) -> Result<()> {
+        for (name, rate) in [
+            ("refresh_rate", self.refresh_rate),
+            ("max_destination_rate", self.max_destination_rate),
+        ] {
+            if let Some(rate) = rate {
+                if !FRAME_RATES.contains(&rate) {
+                    return Err(anyhow!(
+                        "{name} must be between {} and {}, not {rate}",
+                        FRAME_RATES.start(),
+                        FRAME_RATES.end()
+                    ));
+                }
+            }
+        }
+        if self.keep_alive_ms == Some(0) {
+            return Err(anyhow!("keep_alive_ms must be above 0"));
+        }
+        Ok(())
+    }
+}
+
+/// A single universe's route to a sink, as handed to the sink when it
+/// is built.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Route {
+    pub universe: UniverseId,
+    pub address: Option<u16>,
+    pub destinations: Vec<IpAddr>,
+    pub priority: Option<u8>,
+    pub policy: Option<SendPolicy>,
+}
+
+/// Checks that every route names a configured sink, and that no sink is
+/// given an address twice or an address it cannot send on.
+pub fn validate_routes(
+    routes: &[RouteSettings],
+    sink_kinds: &BTreeMap<String, String>,
+    registry: Option<&SinkRegistry>,
+) -> Result<()> {
+    let mut used = HashSet::new();
+    for route in routes {
+        if route.universe == 0 {
+            return Err(anyhow!(
+                "routes: there is no universe 0, universes start at 1"
+            ));
+        }
+
+        for output in &route.outputs {
+            let kind = sink_kinds.get(&output.sink).ok_or_else(|| {
+                anyhow!(
+                    "routes: universe {} is sent to unknown sink \"{}\"",
+                    route.universe,
+                    output.sink
+                )
+            })?;
+            let sink_kind = match registry {
+                Some(registry) => Some(registry.get(kind).ok_or_else(|| {
+                    anyhow!(
+                        "routes: universe {} is sent to sink \"{}\" of unknown kind \"{kind}\"",
+                        route.universe,
+                        output.sink
+                    )
+                })?),
+                None => None,
+            };
+
+            let address = resolve_address(route.universe, output, sink_kind)
+                .map_err(|err| anyhow!("routes: universe {}: {err}", route.universe))?;
+            if let (Some(address), Some(addresses)) = (
+                address,
+                sink_kind.and_then(|sink_kind| sink_kind.addresses.as_ref()),
+            ) {
+                if !addresses.contains(&address) {
+                    return Err(anyhow!(
+                        "routes: universe {} is sent to address {address} on sink \"{}\", \
+                        which must be between {} and {}",
+                        route.universe,
+                        output.sink,
+                        addresses.start(),
+                        addresses.end()
+                    ));
+                }
+            }
+
+            if let Some(priority) = output.priority {
+                if priority > MAX_PRIORITY {
+                    return Err(anyhow!(
+                        "routes: universe {} has priority {priority}, which is above {MAX_PRIORITY}",
+                        route.universe
+                    ));
+                }
+            }
+
+            if let Some(policy) = &output.policy {
+                policy.validate().map_err(|err| {
+                    anyhow!(
+                        "routes: universe {} on sink \"{}\": {err}",
+                        route.universe,
+                        output.sink
+                    )
+                })?;
+            }
+
+            if !used.insert((output.sink.as_str(), address.ok_or(route.universe))) {
+                return Err(match address {
+                    Some(address) => anyhow!(
+                        "routes: address {address} on sink \"{}\" is used more than once",
+                        output.sink
+                    ),
+                    None => anyhow!(
+                        "routes: universe {} is sent to sink \"{}\" more than once",
+                        route.universe,
+                        output.sink
+                    ),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn resolve_address(
+    universe: UniverseId,
+    output: &RouteOutputSettings,
+    sink_kind: Option<&SinkKind>,
+) -> Result<Option<u16>> {
+    match output.address {
+        Some(address) => address.to_u16().map(Some),
+        None => Ok(sink_kind.and_then(|sink_kind| (sink_kind.default_address)(universe))),
+    }
+}
+
+/// Returns the routes that send to the sink called `sink`, with each
+/// address resolved against the sink's kind.
+pub fn routes_for_sink(
+    routes: &[RouteSettings],
+    sink: &str,
+    sink_kind: Option<&SinkKind>,
+) -> Vec<Route> {
+    routes
+        .iter()
+        .flat_map(|route| {
+            route
+                .outputs
+                .iter()
+                .filter(move |output| output.sink == sink)
+                .map(|output| Route {
+                    universe: route.universe,
+                    address: resolve_address(route.universe, output, sink_kind)
+                        .ok()
+                        .flatten(),
+                    destinations: output.destinations.clone(),
+                    priority: output.priority,
+                    policy: output.policy,
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{artnet::ArtNetOutput, sacn::SacnOutput};
+
+    fn registry() -> SinkRegistry {
+        let mut registry = SinkRegistry::default();
+        registry.register(ArtNetOutput::KIND, ArtNetOutput::sink_kind());
+        registry.register(SacnOutput::KIND, SacnOutput::sink_kind());
+        registry
+    }
+
+    fn sink_kinds() -> BTreeMap<String, String> {
+        [
+            ("artnet", "artnet"),
+            ("sacn", "sacn"),
+            ("front", "artnet"),
+            ("usb", "dmx_king"),
+        ]
+        .into_iter()
+        .map(|(name, kind)| (name.to_string(), kind.to_string()))
+        .collect()
+    }
+
+    fn output(sink: &str, address: Option<OutputAddress>) -> RouteOutputSettings {
+        RouteOutputSettings {
+            sink: sink.into(),
+            address,
+            destinations: Vec::new(),
+            priority: None,
+            policy: None,
+        }
+    }
+
+    fn route(universe: UniverseId, outputs: Vec<RouteOutputSettings>) -> RouteSettings {
+        RouteSettings { universe, outputs }
+    }
+
+    fn validate(routes: &[RouteSettings]) -> Result<()> {
+        validate_routes(routes, &sink_kinds(), Some(&registry()))
+    }
+
+    #[test]
+    fn test_accepts_distinct_addresses() {
+        validate(&[
+            route(1, vec![output("artnet", None), output("sacn", None)]),
+            route(2, vec![output("artnet", None), output("front", None)]),
+            route(
+                3,
+                vec![
+                    output("artnet", Some(OutputAddress::Number(0x7fff))),
+                    output("sacn", Some(OutputAddress::Number(63999))),
+                ],
+            ),
+        ])
+        .unwrap();
+    }
+
+    #[test]
+    fn test_rejects_duplicate_destination() {
+        // Universe 1 goes to port address 0 by default.
+        let err = validate(&[
+            route(1, vec![output("artnet", None)]),
+            route(2, vec![output("artnet", Some(OutputAddress::Number(0)))]),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "routes: address 0 on sink \"artnet\" is used more than once"
+        );
+
+        let port_address = PortAddress::new(0, 0, 1).unwrap();
+        assert!(validate(&[
+            route(1, vec![output("front", Some(OutputAddress::Number(1)))]),
+            route(
+                2,
+                vec![output(
+                    "front",
+                    Some(OutputAddress::PortAddress(port_address))
+                )]
+            ),
+        ])
+        .is_err());
+
+        let err = validate_routes(
+            &[route(1, vec![output("usb", None), output("usb", None)])],
+            &sink_kinds(),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "routes: universe 1 is sent to sink \"usb\" more than once"
+        );
+    }
+
+    #[test]
+    fn test_rejects_universe_out_of_range() {
+        let err = validate(&[route(0, vec![output("artnet", None)])]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "routes: there is no universe 0, universes start at 1"
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_sink_and_kind() {
+        let err = validate(&[route(1, vec![output("side", None)])]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "routes: universe 1 is sent to unknown sink \"side\""
+        );
+        let err = validate(&[route(1, vec![output("usb", None)])]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "routes: universe 1 is sent to sink \"usb\" of unknown kind \"dmx_king\""
+        );
+    }
+
+    #[test]
+    fn test_rejects_address_that_does_not_fit_kind() {
+        let err = validate(&[route(
+            1,
+            vec![output("artnet", Some(OutputAddress::Number(0x8000)))],
+        )])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "routes: universe 1 is sent to address 32768 on sink \"artnet\", \
+            which must be between 0 and 32767"
+        );
+        assert!(validate(&[route(
+            1,
+            vec![output("sacn", Some(OutputAddress::Number(0)))]
+        )])
+        .is_err());
+        assert!(validate(&[route(
+            1,
+            vec![output("sacn", Some(OutputAddress::Number(64000)))]
+        )])
+        .is_err());
+        let port_address = PortAddress {
+            net: 0,
+            sub_net: 16,
+            universe: 0,
+        };
+        assert!(validate(&[route(
+            1,
+            vec![output(
+                "artnet",
+                Some(OutputAddress::PortAddress(port_address))
+            )]
+        )])
+        .is_err());
+    }
+}
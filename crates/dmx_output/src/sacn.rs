@@ -0,0 +1,920 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+    path::Path,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+use crate::{
+    routing::Route,
+    sink::{DmxSink, SinkConfig, SinkKind},
+    universe::{DmxFrame, Universe, UniverseId, UNIVERSE_SIZE},
+};
+
+pub const SACN_PORT: u16 = 5568;
+pub const MIN_UNIVERSE: u16 = 1;
+pub const MAX_UNIVERSE: u16 = 63999;
+pub const MAX_PRIORITY: u8 = 200;
+pub const DEFAULT_PRIORITY: u8 = 100;
+
+/// How long a receiver waits for data before considering a source lost.
+pub const NETWORK_DATA_LOSS_TIMEOUT: Duration = Duration::from_millis(2500);
+
+/// The universe whose multicast group carries Universe Discovery packets.
+pub const DISCOVERY_UNIVERSE: u16 = 64214;
+/// How often a source sends its list of universes.
+pub const UNIVERSE_DISCOVERY_INTERVAL: Duration = Duration::from_secs(10);
+
+const ACN_PACKET_IDENTIFIER: &[u8; 12] = b"ASC-E1.17\0\0\0";
+const VECTOR_ROOT_E131_DATA: u32 = 0x0000_0004;
+const VECTOR_E131_DATA_PACKET: u32 = 0x0000_0002;
+const VECTOR_DMP_SET_PROPERTY: u8 = 0x02;
+const VECTOR_ROOT_E131_EXTENDED: u32 = 0x0000_0008;
+const VECTOR_E131_EXTENDED_SYNCHRONIZATION: u32 = 0x0000_0001;
+const VECTOR_E131_EXTENDED_DISCOVERY: u32 = 0x0000_0002;
+const VECTOR_UNIVERSE_DISCOVERY_UNIVERSE_LIST: u32 = 0x0000_0001;
+
+pub const OPTION_STREAM_TERMINATED: u8 = 0x40;
+
+/// The start code of packets carrying a priority for every address.
+pub const START_CODE_ADDRESS_PRIORITY: u8 = 0xdd;
+
+const DATA_PACKET_HEADER_LEN: usize = 126;
+const SOURCE_NAME_LEN: usize = 64;
+const SYNC_PACKET_LEN: usize = 49;
+const DISCOVERY_HEADER_LEN: usize = 120;
+const UNIVERSES_PER_PAGE: usize = 512;
+
+/// The number of identical packets sent before a source may fall back to
+/// keep-alives, and the number of Stream_Terminated packets sent on shutdown.
+const REPEAT_COUNT: u8 = 3;
+
+static DEFAULT_CID: OnceLock<Uuid> = OnceLock::new();
+
+/// Makes the CID kept at `path` the one used by every sACN sink that does
+/// not set its own, generating and saving it the first time, so that
+/// receivers see Tungsten as the same source across restarts.
+pub fn load_default_cid(path: &Path) -> Result<Uuid> {
+    let cid = read_or_create_cid(path)?;
+    Ok(*DEFAULT_CID.get_or_init(|| cid))
+}
+
+fn read_or_create_cid(path: &Path) -> Result<Uuid> {
+    match fs::read_to_string(path) {
+        Ok(text) => text
+            .trim()
+            .parse()
+            .with_context(|| format!("parsing sACN cid in {}", path.display())),
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            let cid = Uuid::new_v4();
+            fs::write(path, format!("{cid}\n"))
+                .with_context(|| format!("saving sACN cid to {}", path.display()))?;
+            Ok(cid)
+        }
+        Err(err) => Err(err).with_context(|| format!("reading sACN cid from {}", path.display())),
+    }
+}
+
+/// The CID used by every sACN sink that does not set its own. When none
+/// could be loaded, a random one is used for this session.
+pub fn default_cid() -> Uuid {
+    *DEFAULT_CID.get_or_init(|| {
+        log::warn!("no sACN cid is saved, receivers will see a new source every session");
+        Uuid::new_v4()
+    })
+}
+
+/// Returns the IPv4 multicast group that carries `universe`.
+pub fn multicast_address(universe: u16) -> Ipv4Addr {
+    let [hi, lo] = universe.to_be_bytes();
+    Ipv4Addr::new(239, 255, hi, lo)
+}
+
+/// Writes the ACN root layer preamble, leaving its flags and length to
+/// be patched by [`write_pdu_length`] once the packet is complete.
+pub(crate) fn write_root_layer(vector: u32, cid: &Uuid, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&0x0010u16.to_be_bytes());
+    buf.extend_from_slice(&0x0000u16.to_be_bytes());
+    buf.extend_from_slice(ACN_PACKET_IDENTIFIER);
+    buf.extend_from_slice(&[0, 0]);
+    buf.extend_from_slice(&vector.to_be_bytes());
+    buf.extend_from_slice(cid.as_bytes());
+}
+
+/// Writes the flags and length of the PDU starting at `offset`, which
+/// runs to the end of `buf`.
+pub(crate) fn write_pdu_length(offset: usize, buf: &mut [u8]) {
+    let length = (buf.len() - offset) as u16 & 0x0fff;
+    buf[offset..offset + 2].copy_from_slice(&(0x7000 | length).to_be_bytes());
+}
+
+pub(crate) fn write_source_name(source_name: &str, buf: &mut Vec<u8>) {
+    let mut end = source_name.len().min(SOURCE_NAME_LEN - 1);
+    while !source_name.is_char_boundary(end) {
+        end -= 1;
+    }
+    buf.extend_from_slice(&source_name.as_bytes()[..end]);
+    buf.resize(buf.len() + SOURCE_NAME_LEN - end, 0);
+}
+
+/// Reads a null-terminated source name, replacing one that isn't UTF-8
+/// with an empty name.
+fn read_source_name(field: &[u8]) -> &str {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    std::str::from_utf8(&field[..len]).unwrap_or_default()
+}
+
+pub struct DataPacket<'a> {
+    pub cid: &'a Uuid,
+    pub source_name: &'a str,
+    pub priority: u8,
+    /// The universe whose synchronization packets release this data, or
+    /// zero to act on it at once.
+    pub sync_address: u16,
+    pub sequence: u8,
+    pub options: u8,
+    pub universe: u16,
+    pub start_code: u8,
+    pub data: &'a [u8],
+}
+
+impl<'a> DataPacket<'a> {
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let data = &self.data[..self.data.len().min(512)];
+        let start = buf.len();
+        buf.reserve(DATA_PACKET_HEADER_LEN + data.len());
+
+        write_root_layer(VECTOR_ROOT_E131_DATA, self.cid, buf);
+
+        let framing_layer = buf.len();
+        buf.extend_from_slice(&[0, 0]);
+        buf.extend_from_slice(&VECTOR_E131_DATA_PACKET.to_be_bytes());
+        write_source_name(self.source_name, buf);
+        buf.push(self.priority);
+        buf.extend_from_slice(&self.sync_address.to_be_bytes());
+        buf.push(self.sequence);
+        buf.push(self.options);
+        buf.extend_from_slice(&self.universe.to_be_bytes());
+
+        let dmp_layer = buf.len();
+        buf.extend_from_slice(&[0, 0]);
+        buf.push(VECTOR_DMP_SET_PROPERTY);
+        buf.push(0xa1);
+        buf.extend_from_slice(&0x0000u16.to_be_bytes());
+        buf.extend_from_slice(&0x0001u16.to_be_bytes());
+        buf.extend_from_slice(&(data.len() as u16 + 1).to_be_bytes());
+        buf.push(self.start_code);
+        buf.extend_from_slice(data);
+
+        let packet = &mut buf[start..];
+        write_pdu_length(16, packet);
+        write_pdu_length(framing_layer - start, packet);
+        write_pdu_length(dmp_layer - start, packet);
+    }
+
+    pub fn parse(packet: &'a [u8]) -> Result<Self> {
+        if packet.len() < DATA_PACKET_HEADER_LEN || &packet[4..16] != ACN_PACKET_IDENTIFIER {
+            return Err(anyhow!("not an ACN packet"));
+        }
+        let vector = |offset: usize| {
+            u32::from_be_bytes([
+                packet[offset],
+                packet[offset + 1],
+                packet[offset + 2],
+                packet[offset + 3],
+            ])
+        };
+        if vector(18) != VECTOR_ROOT_E131_DATA
+            || vector(40) != VECTOR_E131_DATA_PACKET
+            || packet[117] != VECTOR_DMP_SET_PROPERTY
+        {
+            return Err(anyhow!("not an E1.31 data packet"));
+        }
+
+        // The property count includes the start code.
+        let count = u16::from_be_bytes([packet[123], packet[124]]) as usize;
+        let end =
+            (DATA_PACKET_HEADER_LEN + count.saturating_sub(1).min(UNIVERSE_SIZE)).min(packet.len());
+        Ok(Self {
+            cid: Uuid::from_bytes_ref(packet[22..38].try_into()?),
+            source_name: read_source_name(&packet[44..44 + SOURCE_NAME_LEN]),
+            priority: packet[108],
+            sync_address: u16::from_be_bytes([packet[109], packet[110]]),
+            sequence: packet[111],
+            options: packet[112],
+            universe: u16::from_be_bytes([packet[113], packet[114]]),
+            start_code: packet[125],
+            data: &packet[DATA_PACKET_HEADER_LEN..end],
+        })
+    }
+}
+
+/// Tells receivers to act on the data they have been holding for the
+/// universes synchronized on `sync_address`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyncPacket {
+    pub cid: Uuid,
+    pub sequence: u8,
+    pub sync_address: u16,
+}
+
+impl SyncPacket {
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let start = buf.len();
+        buf.reserve(SYNC_PACKET_LEN);
+
+        write_root_layer(VECTOR_ROOT_E131_EXTENDED, &self.cid, buf);
+
+        let framing_layer = buf.len();
+        buf.extend_from_slice(&[0, 0]);
+        buf.extend_from_slice(&VECTOR_E131_EXTENDED_SYNCHRONIZATION.to_be_bytes());
+        buf.push(self.sequence);
+        buf.extend_from_slice(&self.sync_address.to_be_bytes());
+        buf.extend_from_slice(&[0, 0]);
+
+        let packet = &mut buf[start..];
+        write_pdu_length(16, packet);
+        write_pdu_length(framing_layer - start, packet);
+    }
+
+    pub fn parse(packet: &[u8]) -> Result<Self> {
+        if packet.len() < SYNC_PACKET_LEN || &packet[4..16] != ACN_PACKET_IDENTIFIER {
+            return Err(anyhow!("not an ACN packet"));
+        }
+        let vector = |offset: usize| {
+            u32::from_be_bytes([
+                packet[offset],
+                packet[offset + 1],
+                packet[offset + 2],
+                packet[offset + 3],
+            ])
+        };
+        if vector(18) != VECTOR_ROOT_E131_EXTENDED
+            || vector(40) != VECTOR_E131_EXTENDED_SYNCHRONIZATION
+        {
+            return Err(anyhow!("not an E1.31 synchronization packet"));
+        }
+        Ok(Self {
+            cid: Uuid::from_slice(&packet[22..38])?,
+            sequence: packet[44],
+            sync_address: u16::from_be_bytes([packet[45], packet[46]]),
+        })
+    }
+}
+
+/// One page of a source's list of the universes it is sending.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UniverseDiscoveryPacket {
+    pub cid: Uuid,
+    pub source_name: String,
+    pub page: u8,
+    pub last_page: u8,
+    pub universes: Vec<u16>,
+}
+
+impl UniverseDiscoveryPacket {
+    /// Splits a source's universes into as many pages as it takes to
+    /// list them, sorted as receivers expect.
+    pub fn pages(cid: Uuid, source_name: &str, universes: &[u16]) -> Vec<Self> {
+        let mut universes = universes.to_vec();
+        universes.sort_unstable();
+        universes.dedup();
+        let mut chunks = universes.chunks(UNIVERSES_PER_PAGE).collect::<Vec<_>>();
+        if chunks.is_empty() {
+            // A source with no universes still sends an empty list.
+            chunks.push(&[]);
+        }
+        let last_page = (chunks.len() - 1) as u8;
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(page, universes)| Self {
+                cid,
+                source_name: source_name.to_string(),
+                page: page as u8,
+                last_page,
+                universes: universes.to_vec(),
+            })
+            .collect()
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let universes = &self.universes[..self.universes.len().min(UNIVERSES_PER_PAGE)];
+        let start = buf.len();
+        buf.reserve(DISCOVERY_HEADER_LEN + universes.len() * 2);
+
+        write_root_layer(VECTOR_ROOT_E131_EXTENDED, &self.cid, buf);
+
+        let framing_layer = buf.len();
+        buf.extend_from_slice(&[0, 0]);
+        buf.extend_from_slice(&VECTOR_E131_EXTENDED_DISCOVERY.to_be_bytes());
+        write_source_name(&self.source_name, buf);
+        buf.extend_from_slice(&[0; 4]);
+
+        let discovery_layer = buf.len();
+        buf.extend_from_slice(&[0, 0]);
+        buf.extend_from_slice(&VECTOR_UNIVERSE_DISCOVERY_UNIVERSE_LIST.to_be_bytes());
+        buf.push(self.page);
+        buf.push(self.last_page);
+        for universe in universes {
+            buf.extend_from_slice(&universe.to_be_bytes());
+        }
+
+        let packet = &mut buf[start..];
+        write_pdu_length(16, packet);
+        write_pdu_length(framing_layer - start, packet);
+        write_pdu_length(discovery_layer - start, packet);
+    }
+
+    pub fn parse(packet: &[u8]) -> Result<Self> {
+        if packet.len() < DISCOVERY_HEADER_LEN || &packet[4..16] != ACN_PACKET_IDENTIFIER {
+            return Err(anyhow!("not an ACN packet"));
+        }
+        let vector = |offset: usize| {
+            u32::from_be_bytes([
+                packet[offset],
+                packet[offset + 1],
+                packet[offset + 2],
+                packet[offset + 3],
+            ])
+        };
+        if vector(18) != VECTOR_ROOT_E131_EXTENDED
+            || vector(40) != VECTOR_E131_EXTENDED_DISCOVERY
+            || vector(114) != VECTOR_UNIVERSE_DISCOVERY_UNIVERSE_LIST
+        {
+            return Err(anyhow!("not an E1.31 Universe Discovery packet"));
+        }
+
+        let pdu_length = (u16::from_be_bytes([packet[112], packet[113]]) & 0x0fff) as usize;
+        let end = (112 + pdu_length).min(packet.len());
+        let universes = packet[DISCOVERY_HEADER_LEN..end.max(DISCOVERY_HEADER_LEN)]
+            .chunks_exact(2)
+            .map(|universe| u16::from_be_bytes([universe[0], universe[1]]))
+            .collect();
+
+        Ok(Self {
+            cid: Uuid::from_slice(&packet[22..38])?,
+            source_name: read_source_name(&packet[44..44 + SOURCE_NAME_LEN]).to_string(),
+            page: packet[118],
+            last_page: packet[119],
+            universes,
+        })
+    }
+}
+
+/// Creates a UDP socket for sending sACN from `bind_address`, using that
+/// interface for multicast when it is not the unspecified address.
+pub(crate) fn sender_socket(bind_address: Ipv4Addr) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket
+        .bind(&SocketAddr::from((bind_address, 0)).into())
+        .with_context(|| format!("binding sACN socket to {bind_address}"))?;
+    if !bind_address.is_unspecified() {
+        socket.set_multicast_if_v4(&bind_address)?;
+    }
+    socket.set_multicast_ttl_v4(8)?;
+    Ok(socket.into())
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SacnConfig {
+    /// The local interface address to send from.
+    pub bind_address: Ipv4Addr,
+    /// The component identifier receivers use to tell sources apart.
+    /// Defaults to one generated once and saved next to the patch.
+    pub cid: Option<Uuid>,
+    /// The name shown for this source on receivers.
+    pub source_name: String,
+    /// The priority of this source's data, from 0 to 200, for routes
+    /// that do not give their own.
+    pub priority: u8,
+    /// How often unchanged universes are retransmitted, in milliseconds.
+    pub keep_alive_ms: u64,
+    /// Whether to announce the universes this sink sends with E1.31
+    /// Universe Discovery packets.
+    #[serde(default)]
+    pub universe_discovery: bool,
+    /// The universe to send E1.31 synchronization packets on after each
+    /// frame's data packets, so that receivers which support it output
+    /// every universe at the same moment. Not synchronized when unset.
+    #[serde(default)]
+    pub sync_universe: Option<u16>,
+}
+
+struct UniverseState {
+    source: UniverseId,
+    priority: u8,
+    destinations: Vec<SocketAddr>,
+    sequence: u8,
+    data: Universe,
+    repeats: u8,
+    last_sent: Option<Instant>,
+}
+
+/// Transmits E1.31 data packets for a set of universes, sending them
+/// when their levels change and as keep-alives when they do not.
+/// Routes without an address send internal universe `n` as sACN
+/// universe `n`, and routes without destinations use multicast.
+pub struct SacnOutput {
+    socket: UdpSocket,
+    cid: Uuid,
+    source_name: String,
+    keep_alive: Duration,
+    universes: BTreeMap<u16, UniverseState>,
+    universe_discovery: bool,
+    last_discovery: Option<Instant>,
+    sync: Option<SyncState>,
+    buf: Vec<u8>,
+}
+
+struct SyncState {
+    universe: u16,
+    sequence: u8,
+    /// The sync universe's multicast group, if any route uses multicast,
+    /// and the unicast destinations of the routes.
+    destinations: Vec<SocketAddr>,
+}
+
+impl SacnOutput {
+    pub const KIND: &'static str = "sacn";
+
+    pub fn sink_kind() -> SinkKind {
+        SinkKind {
+            addresses: Some(MIN_UNIVERSE..=MAX_UNIVERSE),
+            default_address: Some,
+            build: Self::build,
+        }
+    }
+
+    pub fn new(routes: &[Route], config: SacnConfig) -> Result<Self> {
+        if config.priority > MAX_PRIORITY {
+            return Err(anyhow!("sACN priority {} is above 200", config.priority));
+        }
+        let keep_alive = Duration::from_millis(config.keep_alive_ms);
+        if keep_alive >= NETWORK_DATA_LOSS_TIMEOUT {
+            return Err(anyhow!(
+                "sACN keep-alive of {}ms must be shorter than the 2.5s receiver timeout",
+                config.keep_alive_ms
+            ));
+        }
+
+        let mut universes = BTreeMap::new();
+        for route in routes {
+            let universe = route.address.unwrap_or(route.universe);
+            if !(MIN_UNIVERSE..=MAX_UNIVERSE).contains(&universe) {
+                return Err(anyhow!("sACN universe {universe} is out of range"));
+            }
+            let destinations = route
+                .destinations
+                .iter()
+                .map(|ip| match ip {
+                    IpAddr::V4(_) => Ok(SocketAddr::new(*ip, SACN_PORT)),
+                    IpAddr::V6(_) => Err(anyhow!("sACN over IPv6 is not supported ({ip})")),
+                })
+                .collect::<Result<_>>()?;
+            universes.insert(
+                universe,
+                UniverseState {
+                    source: route.universe,
+                    priority: route.priority.unwrap_or(config.priority),
+                    destinations,
+                    sequence: 0,
+                    data: Universe::default(),
+                    repeats: 0,
+                    last_sent: None,
+                },
+            );
+        }
+
+        let sync = match config.sync_universe {
+            Some(universe) => {
+                if !(MIN_UNIVERSE..=MAX_UNIVERSE).contains(&universe) {
+                    return Err(anyhow!("sACN sync universe {universe} is out of range"));
+                }
+                let mut destinations = Vec::new();
+                for state in universes.values() {
+                    if state.destinations.is_empty() {
+                        destinations
+                            .push(SocketAddr::from((multicast_address(universe), SACN_PORT)));
+                    }
+                    destinations.extend(state.destinations.iter().copied());
+                }
+                destinations.sort_unstable();
+                destinations.dedup();
+                Some(SyncState {
+                    universe,
+                    sequence: 0,
+                    destinations,
+                })
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            socket: sender_socket(config.bind_address)?,
+            cid: config.cid.unwrap_or_else(default_cid),
+            source_name: config.source_name,
+            keep_alive,
+            universes,
+            universe_discovery: config.universe_discovery,
+            last_discovery: None,
+            sync,
+            buf: Vec::with_capacity(DATA_PACKET_HEADER_LEN + UNIVERSE_SIZE),
+        })
+    }
+
+    fn send(&mut self, universe: u16, options: u8) -> Result<()> {
+        let Some(state) = self.universes.get_mut(&universe) else {
+            return Ok(());
+        };
+        state.sequence = state.sequence.wrapping_add(1);
+        self.buf.clear();
+        DataPacket {
+            cid: &self.cid,
+            source_name: &self.source_name,
+            priority: state.priority,
+            sync_address: self.sync.as_ref().map_or(0, |sync| sync.universe),
+            sequence: state.sequence,
+            options,
+            universe,
+            start_code: 0,
+            data: &state.data[..],
+        }
+        .encode(&mut self.buf);
+
+        if state.destinations.is_empty() {
+            let destination = SocketAddrV4::new(multicast_address(universe), SACN_PORT);
+            self.socket
+                .send_to(&self.buf, destination)
+                .with_context(|| format!("sending sACN universe {universe} to {destination}"))?;
+            return Ok(());
+        }
+        // Keep sending to the other destinations when one fails.
+        let mut result = Ok(());
+        for destination in &state.destinations {
+            if let Err(err) = self.socket.send_to(&self.buf, destination) {
+                result = Err(err)
+                    .with_context(|| format!("sending sACN universe {universe} to {destination}"));
+            }
+        }
+        result
+    }
+
+    /// Sends the data packets for `due`, followed by a sync packet and,
+    /// when it is time, universe discovery.
+    fn send_due(&mut self, due: Vec<u16>, now: Instant) -> Result<()> {
+        let mut result = Ok(());
+        for universe in &due {
+            if let Err(err) = self.send(*universe, 0) {
+                result = Err(err);
+            }
+        }
+        if !due.is_empty() {
+            if let Err(err) = self.send_sync() {
+                result = Err(err);
+            }
+        }
+        if let Err(err) = self.send_universe_discovery_if_due(now) {
+            result = Err(err);
+        }
+        result
+    }
+
+    fn send_sync(&mut self) -> Result<()> {
+        let Some(sync) = &mut self.sync else {
+            return Ok(());
+        };
+        sync.sequence = sync.sequence.wrapping_add(1);
+        self.buf.clear();
+        SyncPacket {
+            cid: self.cid,
+            sequence: sync.sequence,
+            sync_address: sync.universe,
+        }
+        .encode(&mut self.buf);
+        let mut result = Ok(());
+        for destination in &sync.destinations {
+            if let Err(err) = self.socket.send_to(&self.buf, destination) {
+                result = Err(err).with_context(|| format!("sending sACN sync to {destination}"));
+            }
+        }
+        result
+    }
+
+    fn send_universe_discovery(&mut self) -> Result<()> {
+        let universes = self.universes.keys().copied().collect::<Vec<_>>();
+        let destination = SocketAddrV4::new(multicast_address(DISCOVERY_UNIVERSE), SACN_PORT);
+        for page in UniverseDiscoveryPacket::pages(self.cid, &self.source_name, &universes) {
+            self.buf.clear();
+            page.encode(&mut self.buf);
+            self.socket
+                .send_to(&self.buf, destination)
+                .with_context(|| format!("sending sACN universe discovery to {destination}"))?;
+        }
+        Ok(())
+    }
+
+    fn send_universe_discovery_if_due(&mut self, now: Instant) -> Result<()> {
+        let discovery_due = self
+            .last_discovery
+            .is_none_or(|last_discovery| now - last_discovery >= UNIVERSE_DISCOVERY_INTERVAL);
+        if self.universe_discovery && discovery_due {
+            self.last_discovery = Some(now);
+            self.send_universe_discovery()?;
+        }
+        Ok(())
+    }
+
+    pub fn build(config: &SinkConfig) -> Result<Box<dyn DmxSink>> {
+        Ok(Box::new(Self::new(&config.routes, config.options()?)?))
+    }
+}
+
+impl DmxSink for SacnOutput {
+    /// Sends the universes whose levels changed since the previous frame,
+    /// and keep-alives for those that did not.
+    fn send_frame(&mut self, frame: &DmxFrame) -> Result<()> {
+        let now = frame.timestamp;
+        let mut due = Vec::new();
+        for (universe, state) in &mut self.universes {
+            let levels = frame.universe(state.source).cloned().unwrap_or_default();
+            if state.data != levels {
+                state.data = levels;
+                state.repeats = 0;
+            }
+
+            let keep_alive_due = state
+                .last_sent
+                .is_none_or(|last_sent| now - last_sent >= self.keep_alive);
+            if state.repeats < REPEAT_COUNT || keep_alive_due {
+                state.repeats = state.repeats.saturating_add(1);
+                state.last_sent = Some(now);
+                due.push(*universe);
+            }
+        }
+
+        self.send_due(due, now)
+    }
+
+    /// Sends the universes whose routes are due under their send policies,
+    /// which take over from the change detection and keep-alives.
+    fn send_universes(&mut self, frame: &DmxFrame, universes: &[UniverseId]) -> Result<()> {
+        let now = frame.timestamp;
+        let mut due = Vec::new();
+        for (universe, state) in &mut self.universes {
+            if !universes.contains(&state.source) {
+                continue;
+            }
+            state.data = frame.universe(state.source).cloned().unwrap_or_default();
+            state.repeats = REPEAT_COUNT;
+            state.last_sent = Some(now);
+            due.push(*universe);
+        }
+
+        self.send_due(due, now)
+    }
+
+    fn tick(&mut self, now: Instant) -> Result<()> {
+        self.send_universe_discovery_if_due(now)
+    }
+}
+
+impl Drop for SacnOutput {
+    fn drop(&mut self) {
+        let universes = self.universes.keys().copied().collect::<Vec<_>>();
+        for universe in universes {
+            for _ in 0..REPEAT_COUNT {
+                self.send(universe, OPTION_STREAM_TERMINATED).ok();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        routing::SendPolicy,
+        universe::{FrameSources, UniverseBuffer},
+    };
+    use std::time::Duration;
+
+    fn multicast_listener(universe: u16) -> UdpSocket {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).unwrap();
+        socket.set_reuse_address(true).unwrap();
+        socket
+            .bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, SACN_PORT)).into())
+            .unwrap();
+        socket
+            .join_multicast_v4(&multicast_address(universe), &Ipv4Addr::LOCALHOST)
+            .unwrap();
+        let socket = UdpSocket::from(socket);
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        socket
+    }
+
+    /// Receives packets until a data packet for `universe`, skipping any
+    /// other sACN traffic on the port.
+    fn receive_data_packet(socket: &UdpSocket, universe: u16) -> Vec<u8> {
+        let mut buf = [0; 1024];
+        loop {
+            let (len, _) = socket.recv_from(&mut buf).unwrap();
+            let packet = &buf[..len];
+            if DataPacket::parse(packet).is_ok_and(|packet| packet.universe == universe) {
+                return packet.to_vec();
+            }
+        }
+    }
+
+    #[test]
+    fn test_sends_data_packets_to_multicast_group() {
+        let universe = 4001;
+        let socket = multicast_listener(universe);
+        let cid = Uuid::new_v4();
+        let mut output = SacnOutput::new(
+            &[Route {
+                universe: 3,
+                address: Some(universe),
+                destinations: Vec::new(),
+                priority: None,
+                policy: None,
+            }],
+            SacnConfig {
+                bind_address: Ipv4Addr::LOCALHOST,
+                cid: Some(cid),
+                source_name: "Tungsten test".into(),
+                priority: 150,
+                keep_alive_ms: 1000,
+                universe_discovery: false,
+                sync_universe: None,
+            },
+        )
+        .unwrap();
+
+        let mut universes = UniverseBuffer::default();
+        universes.get_mut(3).copy_from(&[255, 128, 1]);
+        output
+            .send_frame(&DmxFrame {
+                sequence: 0,
+                timestamp: Instant::now(),
+                universes,
+                sources: FrameSources::default(),
+            })
+            .unwrap();
+
+        let packet = receive_data_packet(&socket, universe);
+        assert_eq!(packet.len(), DATA_PACKET_HEADER_LEN + UNIVERSE_SIZE);
+        // Root layer.
+        assert_eq!(&packet[0..2], &[0x00, 0x10]);
+        assert_eq!(&packet[2..4], &[0x00, 0x00]);
+        assert_eq!(&packet[4..16], ACN_PACKET_IDENTIFIER);
+        assert_eq!(&packet[16..18], &(0x7000u16 | 622).to_be_bytes());
+        assert_eq!(&packet[18..22], &VECTOR_ROOT_E131_DATA.to_be_bytes());
+        assert_eq!(&packet[22..38], cid.as_bytes());
+        // Framing layer.
+        assert_eq!(&packet[38..40], &(0x7000u16 | 600).to_be_bytes());
+        assert_eq!(&packet[40..44], &VECTOR_E131_DATA_PACKET.to_be_bytes());
+        assert_eq!(&packet[44..57], b"Tungsten test");
+        assert!(packet[57..108].iter().all(|byte| *byte == 0));
+        assert_eq!(packet[108], 150);
+        assert_eq!(&packet[109..111], &[0, 0]);
+        assert_eq!(packet[111], 1);
+        assert_eq!(packet[112], 0);
+        assert_eq!(&packet[113..115], &universe.to_be_bytes());
+        // DMP layer.
+        assert_eq!(&packet[115..117], &(0x7000u16 | 523).to_be_bytes());
+        assert_eq!(&packet[117..119], &[VECTOR_DMP_SET_PROPERTY, 0xa1]);
+        assert_eq!(&packet[119..123], &[0, 0, 0, 1]);
+        assert_eq!(&packet[123..125], &513u16.to_be_bytes());
+        assert_eq!(packet[125], 0);
+        assert_eq!(&packet[126..129], &[255, 128, 1]);
+        assert!(packet[129..].iter().all(|level| *level == 0));
+
+        drop(output);
+        for sequence in 2..2 + REPEAT_COUNT {
+            let packet = receive_data_packet(&socket, universe);
+            assert_eq!(packet[111], sequence);
+            assert_eq!(packet[112], OPTION_STREAM_TERMINATED);
+        }
+    }
+
+    #[test]
+    fn test_keeps_sending_after_failed_destination() {
+        let destination = Ipv4Addr::new(127, 0, 0, 4);
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).unwrap();
+        socket.set_reuse_address(true).unwrap();
+        socket
+            .bind(&SocketAddr::from((destination, SACN_PORT)).into())
+            .unwrap();
+        let socket = UdpSocket::from(socket);
+        socket
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let cid = Uuid::new_v4();
+        let mut output = SacnOutput::new(
+            &[Route {
+                universe: 6,
+                address: Some(4006),
+                // A socket bound to loopback cannot reach 10.0.0.1.
+                destinations: vec![Ipv4Addr::new(10, 0, 0, 1).into(), destination.into()],
+                priority: None,
+                policy: None,
+            }],
+            SacnConfig {
+                bind_address: Ipv4Addr::LOCALHOST,
+                cid: Some(cid),
+                source_name: "Tungsten test".into(),
+                priority: 100,
+                keep_alive_ms: 1000,
+                universe_discovery: false,
+                sync_universe: Some(4007),
+            },
+        )
+        .unwrap();
+
+        let mut universes = UniverseBuffer::default();
+        universes.get_mut(6).copy_from(&[42]);
+        let err = output
+            .send_frame(&DmxFrame {
+                sequence: 0,
+                timestamp: Instant::now(),
+                universes,
+                sources: FrameSources::default(),
+            })
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("10.0.0.1"), "{err:#}");
+
+        let packet = receive_data_packet(&socket, 4006);
+        assert_eq!(packet[126], 42);
+        let mut buf = [0; 1024];
+        let sync = loop {
+            let (len, _) = socket.recv_from(&mut buf).unwrap();
+            if let Ok(sync) = SyncPacket::parse(&buf[..len]) {
+                break sync;
+            }
+        };
+        assert_eq!(sync.cid, cid);
+        assert_eq!(sync.sync_address, 4007);
+    }
+
+    #[test]
+    fn test_default_cid_is_saved() {
+        let path = std::env::temp_dir().join(format!("tungsten-sacn-cid-{}", std::process::id()));
+        fs::remove_file(&path).ok();
+        let cid = read_or_create_cid(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().trim(), cid.to_string());
+        assert_eq!(read_or_create_cid(&path).unwrap(), cid);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_tick_sends_universe_discovery() {
+        let socket = multicast_listener(DISCOVERY_UNIVERSE);
+        let cid = Uuid::new_v4();
+        let mut output = SacnOutput::new(
+            &[Route {
+                universe: 5,
+                address: Some(4005),
+                destinations: Vec::new(),
+                priority: None,
+                policy: Some(SendPolicy {
+                    change_only: true,
+                    ..Default::default()
+                }),
+            }],
+            SacnConfig {
+                bind_address: Ipv4Addr::LOCALHOST,
+                cid: Some(cid),
+                source_name: "Tungsten test".into(),
+                priority: 100,
+                keep_alive_ms: 1000,
+                universe_discovery: true,
+                sync_universe: None,
+            },
+        )
+        .unwrap();
+
+        // Scheduled sinks are ticked on frames where nothing is due.
+        output.tick(Instant::now()).unwrap();
+
+        let mut buf = [0; 1500];
+        let packet = loop {
+            let (len, _) = socket.recv_from(&mut buf).unwrap();
+            if let Ok(packet) = UniverseDiscoveryPacket::parse(&buf[..len]) {
+                if packet.cid == cid {
+                    break packet;
+                }
+            }
+        };
+        assert_eq!(packet.universes, [4005]);
+    }
+}
@@ -0,0 +1,248 @@
+use anyhow::{Context, Result};
+use gpui::{AppContext, Global};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::ErrorKind,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+use crate::{
+    input::DmxInput,
+    sacn::{multicast_address, DataPacket, UniverseDiscoveryPacket, DISCOVERY_UNIVERSE, SACN_PORT},
+};
+
+/// How often the listener wakes up to forget stale sources.
+const READ_TIMEOUT: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SacnDiscoveryConfig {
+    /// Whether to listen for other sources. The listener still runs when
+    /// sACN input is configured.
+    pub enabled: bool,
+    /// The local interface address to join the discovery group on.
+    pub bind_address: Ipv4Addr,
+    /// How long a source is kept after its last discovery packet. Sources
+    /// send one every 10 seconds.
+    pub source_timeout_ms: u64,
+}
+
+impl Default for SacnDiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            bind_address: Ipv4Addr::UNSPECIFIED,
+            source_timeout_ms: 30000,
+        }
+    }
+}
+
+/// An sACN source that has announced its universes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SacnSource {
+    pub cid: Uuid,
+    pub name: String,
+    pub address: SocketAddr,
+    /// Whether this is one of our own sinks.
+    pub local: bool,
+    /// Every universe the source sends, across all of its pages.
+    pub universes: BTreeSet<u16>,
+    pages: BTreeMap<u8, Vec<u16>>,
+    pub last_seen: Instant,
+}
+
+impl SacnSource {
+    fn update(&mut self, packet: UniverseDiscoveryPacket, address: SocketAddr) {
+        self.name = packet.source_name;
+        self.address = address;
+        self.pages.retain(|page, _| *page <= packet.last_page);
+        self.pages.insert(packet.page, packet.universes);
+        self.universes = self.pages.values().flatten().copied().collect();
+        self.last_seen = Instant::now();
+    }
+}
+
+/// Our own CIDs and the sACN universes we send, so that other sources
+/// on the same universes can be told apart.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LocalSources {
+    pub cids: BTreeSet<Uuid>,
+    pub universes: BTreeSet<u16>,
+}
+
+struct GlobalSacnDiscovery(Arc<SacnDiscovery>);
+
+impl Global for GlobalSacnDiscovery {}
+
+/// Listens for E1.31 Universe Discovery packets and keeps a table of the
+/// sources on the network and the universes they send. As it owns the
+/// sACN port, it also joins the input universes and hands their data
+/// packets to the input merge.
+pub struct SacnDiscovery {
+    sources: Arc<Mutex<BTreeMap<Uuid, SacnSource>>>,
+    local: LocalSources,
+    running: Arc<AtomicBool>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl SacnDiscovery {
+    pub fn global(cx: &AppContext) -> Arc<Self> {
+        cx.global::<GlobalSacnDiscovery>().0.clone()
+    }
+
+    pub fn try_global(cx: &AppContext) -> Option<Arc<Self>> {
+        cx.try_global::<GlobalSacnDiscovery>()
+            .map(|discovery| discovery.0.clone())
+    }
+
+    pub fn set_global(discovery: Arc<Self>, cx: &mut AppContext) {
+        cx.set_global(GlobalSacnDiscovery(discovery));
+    }
+
+    pub fn start(
+        config: &SacnDiscoveryConfig,
+        local: LocalSources,
+        input: Option<Arc<DmxInput>>,
+    ) -> Result<Self> {
+        let mut universes = Vec::new();
+        if config.enabled {
+            universes.push(DISCOVERY_UNIVERSE);
+        }
+        if let Some(input) = &input {
+            universes.extend(input.sacn_universes());
+        }
+        let socket = listener_socket(config.bind_address, &universes)?;
+        let source_timeout = Duration::from_millis(config.source_timeout_ms);
+
+        let sources = Arc::new(Mutex::new(BTreeMap::<Uuid, SacnSource>::new()));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let thread = thread::Builder::new()
+            .name("sacn-discovery".into())
+            .spawn({
+                let sources = sources.clone();
+                let running = running.clone();
+                let local_cids = local.cids.clone();
+                move || {
+                    let mut buf = [0; 1500];
+                    while running.load(Ordering::Relaxed) {
+                        let now = Instant::now();
+                        sources
+                            .lock()
+                            .retain(|_, source| now - source.last_seen < source_timeout);
+
+                        let (len, address) = match socket.recv_from(&mut buf) {
+                            Ok(received) => received,
+                            Err(err)
+                                if matches!(
+                                    err.kind(),
+                                    ErrorKind::WouldBlock | ErrorKind::TimedOut
+                                ) =>
+                            {
+                                continue
+                            }
+                            Err(err) => {
+                                log::error!("sACN discovery failed to receive: {err}");
+                                thread::sleep(READ_TIMEOUT);
+                                continue;
+                            }
+                        };
+
+                        if let Ok(packet) = DataPacket::parse(&buf[..len]) {
+                            // Our own multicast output loops back to us.
+                            if let Some(input) = &input {
+                                if !local_cids.contains(packet.cid) {
+                                    input.receive_sacn(&packet);
+                                }
+                            }
+                            continue;
+                        }
+                        let packet = match UniverseDiscoveryPacket::parse(&buf[..len]) {
+                            Ok(packet) => packet,
+                            Err(err) => {
+                                log::debug!("ignoring packet from {address}: {err}");
+                                continue;
+                            }
+                        };
+                        sources
+                            .lock()
+                            .entry(packet.cid)
+                            .or_insert_with(|| SacnSource {
+                                cid: packet.cid,
+                                name: String::new(),
+                                address,
+                                local: local_cids.contains(&packet.cid),
+                                universes: BTreeSet::new(),
+                                pages: BTreeMap::new(),
+                                last_seen: now,
+                            })
+                            .update(packet, address);
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            sources,
+            local,
+            running,
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    /// Returns every source heard from recently, including our own.
+    pub fn sources(&self) -> Vec<SacnSource> {
+        self.sources.lock().values().cloned().collect()
+    }
+
+    /// Returns the universes that `source` sends which we send too.
+    pub fn conflicts(&self, source: &SacnSource) -> Vec<u16> {
+        if source.local {
+            return Vec::new();
+        }
+        source
+            .universes
+            .intersection(&self.local.universes)
+            .copied()
+            .collect()
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.lock().take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl Drop for SacnDiscovery {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Joins the multicast groups of `universes` on `bind_address`.
+fn listener_socket(bind_address: Ipv4Addr, universes: &[u16]) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_read_timeout(Some(READ_TIMEOUT))?;
+    let address = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, SACN_PORT);
+    socket
+        .bind(&address.into())
+        .with_context(|| format!("binding sACN socket to {address}"))?;
+    for universe in universes {
+        let group = multicast_address(*universe);
+        socket
+            .join_multicast_v4(&group, &bind_address)
+            .with_context(|| format!("joining sACN group {group} on {bind_address}"))?;
+    }
+    Ok(socket.into())
+}
@@ -0,0 +1,197 @@
+use gpui::{
+    actions, AnyElement, AppContext, EventEmitter, FocusHandle, FocusableView, IntoElement, Render,
+    Task, View, ViewContext, VisualContext,
+};
+use std::{sync::Arc, time::Duration};
+use ui::{
+    h_flex, rems, v_flex, ActiveTheme, Color, FluentBuilder, Indicator, InteractiveElement, Label,
+    LabelCommon, LabelSize, ParentElement, Styled, WindowContext,
+};
+use workspace::{
+    item::{Item, ItemEvent, TabContentParams},
+    Workspace,
+};
+
+use crate::{
+    output_status::cell,
+    sacn_discovery::{SacnDiscovery, SacnSource},
+};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+actions!(tungsten, [SacnSources]);
+
+pub fn init(cx: &mut AppContext) {
+    cx.observe_new_views(move |workspace: &mut Workspace, _| {
+        workspace.register_action(move |workspace, _: &SacnSources, cx| {
+            SacnSourcesView::open(workspace, cx);
+        });
+    })
+    .detach();
+}
+
+/// Lists the sACN sources on the network, flagging any that send on
+/// universes we send too.
+pub struct SacnSourcesView {
+    focus_handle: FocusHandle,
+    discovery: Option<Arc<SacnDiscovery>>,
+    sources: Vec<(SacnSource, Vec<u16>)>,
+    _refresh_task: Task<()>,
+}
+
+impl SacnSourcesView {
+    pub fn open(workspace: &mut Workspace, cx: &mut ViewContext<Workspace>) {
+        let existing = workspace
+            .active_pane()
+            .read(cx)
+            .items()
+            .find_map(|item| item.downcast::<SacnSourcesView>());
+
+        if let Some(existing) = existing {
+            workspace.activate_item(&existing, cx);
+        } else {
+            let view = SacnSourcesView::new(cx);
+            workspace.add_item_to_active_pane(Box::new(view), None, cx)
+        }
+    }
+
+    pub fn new(cx: &mut ViewContext<Workspace>) -> View<Self> {
+        cx.new_view(|cx: &mut ViewContext<Self>| {
+            let focus_handle = cx.focus_handle();
+            let refresh_task = cx.spawn(|this, mut cx| async move {
+                loop {
+                    cx.background_executor().timer(REFRESH_INTERVAL).await;
+                    if this.update(&mut cx, |this, cx| this.refresh(cx)).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            let mut this = Self {
+                focus_handle,
+                discovery: SacnDiscovery::try_global(cx),
+                sources: Vec::new(),
+                _refresh_task: refresh_task,
+            };
+            this.refresh(cx);
+            this
+        })
+    }
+
+    fn refresh(&mut self, cx: &mut ViewContext<Self>) {
+        self.sources = self
+            .discovery
+            .as_ref()
+            .map(|discovery| {
+                discovery
+                    .sources()
+                    .into_iter()
+                    .map(|source| {
+                        let conflicts = discovery.conflicts(&source);
+                        (source, conflicts)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        cx.notify();
+    }
+
+    fn render_row(&self, source: &SacnSource, conflicts: &[u16]) -> impl IntoElement {
+        let universes = source
+            .universes
+            .iter()
+            .map(u16::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let color = if !conflicts.is_empty() {
+            Color::Error
+        } else if source.local {
+            Color::Accent
+        } else {
+            Color::Muted
+        };
+
+        h_flex()
+            .gap_4()
+            .child(
+                h_flex()
+                    .w(rems(16.))
+                    .gap_2()
+                    .child(Indicator::dot().color(color))
+                    .child(Label::new(source.name.clone()))
+                    .when(source.local, |this| {
+                        this.child(Label::new("(this console)").color(Color::Muted))
+                    }),
+            )
+            .child(cell(8., Label::new(source.address.ip().to_string())))
+            .child(cell(
+                20.,
+                Label::new(source.cid.to_string())
+                    .size(LabelSize::Small)
+                    .color(Color::Muted),
+            ))
+            .child(cell(16., Label::new(universes)))
+            .when(!conflicts.is_empty(), |this| {
+                let conflicts = conflicts
+                    .iter()
+                    .map(u16::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                this.child(Label::new(format!("also sent by us: {conflicts}")).color(Color::Error))
+            })
+    }
+}
+
+impl Render for SacnSourcesView {
+    fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let header = h_flex()
+            .gap_4()
+            .child(cell(16., Label::new("Source").color(Color::Muted)))
+            .child(cell(8., Label::new("IP").color(Color::Muted)))
+            .child(cell(20., Label::new("CID").color(Color::Muted)))
+            .child(cell(16., Label::new("Universes").color(Color::Muted)))
+            .child(Label::new("Conflicts").color(Color::Muted));
+
+        v_flex()
+            .size_full()
+            .p_4()
+            .gap_2()
+            .bg(cx.theme().colors().tab_active_background)
+            .track_focus(&self.focus_handle)
+            .when(self.discovery.is_none(), |this| {
+                this.child(Label::new("sACN discovery is not running").color(Color::Error))
+            })
+            .when(
+                self.discovery.is_some() && self.sources.is_empty(),
+                |this| this.child(Label::new("No sACN sources found").color(Color::Muted)),
+            )
+            .child(header)
+            .children(
+                self.sources
+                    .iter()
+                    .map(|(source, conflicts)| self.render_row(source, conflicts)),
+            )
+    }
+}
+
+impl EventEmitter<ItemEvent> for SacnSourcesView {}
+
+impl FocusableView for SacnSourcesView {
+    fn focus_handle(&self, _: &AppContext) -> gpui::FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Item for SacnSourcesView {
+    type Event = ItemEvent;
+
+    fn tab_content(&self, params: TabContentParams, _: &WindowContext) -> AnyElement {
+        Label::new("sACN Sources")
+            .color(if params.selected {
+                Color::Default
+            } else {
+                Color::Muted
+            })
+            .into_any_element()
+    }
+}
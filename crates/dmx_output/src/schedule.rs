@@ -0,0 +1,425 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    routing::{Route, SendPolicy},
+    universe::{DmxFrame, Universe, UniverseId},
+};
+
+/// How often unchanged levels are resent by routes that only send
+/// changes, unless they say otherwise.
+const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(1);
+
+struct UniverseSchedule {
+    /// The shortest time between sends, from the refresh rate.
+    min_interval: Option<Duration>,
+    change_only: bool,
+    keep_alive: Duration,
+    /// Where the universe is sent, for sharing out their packet budgets.
+    destinations: BTreeSet<Destination>,
+    next_due: Option<Instant>,
+    last_sent: Option<Instant>,
+    levels: Option<Universe>,
+}
+
+impl UniverseSchedule {
+    fn new(policy: &SendPolicy) -> Self {
+        Self {
+            min_interval: policy
+                .refresh_rate
+                .map(|rate| Duration::from_secs_f32(1. / rate)),
+            change_only: policy.change_only,
+            keep_alive: policy
+                .keep_alive_ms
+                .map_or(DEFAULT_KEEP_ALIVE, Duration::from_millis),
+            destinations: BTreeSet::new(),
+            next_due: None,
+            last_sent: None,
+            levels: None,
+        }
+    }
+
+    /// Whether the universe would go out now, budgets allowing.
+    fn wants_send(&self, levels: Option<&Universe>, now: Instant) -> bool {
+        if self.next_due.is_some_and(|due| now < due) {
+            return false;
+        }
+        if self.change_only {
+            let keep_alive_due = self
+                .last_sent
+                .is_none_or(|last_sent| now - last_sent >= self.keep_alive);
+            let unchanged = match (&self.levels, levels) {
+                (Some(sent), Some(levels)) => sent == levels,
+                (Some(sent), None) => sent.iter().all(|level| *level == 0),
+                (None, _) => false,
+            };
+            if unchanged && !keep_alive_due {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn mark_sent(&mut self, levels: Option<&Universe>, now: Instant) {
+        if self.change_only {
+            self.levels = Some(levels.cloned().unwrap_or_default());
+        }
+        // Keep to the rate on average, even when the engine's frames do not
+        // line up with it, but do not try to catch up after falling behind.
+        if let Some(min_interval) = self.min_interval {
+            self.next_due = Some(match self.next_due {
+                Some(due) if now - due < min_interval => due + min_interval,
+                _ => now + min_interval,
+            });
+        }
+        self.last_sent = Some(now);
+    }
+
+    /// The most times a second the universe is sent.
+    fn rate(&self, frame_rate: f32) -> f32 {
+        self.min_interval.map_or(frame_rate, |min_interval| {
+            frame_rate.min(1. / min_interval.as_secs_f32())
+        })
+    }
+}
+
+/// A host a sink sends to. Routes without destinations of their own all
+/// count as the sink's default destination, such as its broadcast address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Destination {
+    Default,
+    Host(IpAddr),
+}
+
+/// Shares out the packets one destination may receive each second among
+/// the universes sent to it.
+struct DestinationBudget {
+    rate: f32,
+    /// Packets that may be sent now. At most one unused packet is carried
+    /// over, so the budget is spread out rather than sent in bursts.
+    available: f32,
+    last_refill: Option<Instant>,
+}
+
+impl DestinationBudget {
+    fn new(rate: f32) -> Self {
+        Self {
+            rate,
+            available: 1.,
+            last_refill: None,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let Some(last_refill) = self.last_refill {
+            let earned = self.rate * (now - last_refill).as_secs_f32();
+            self.available = (self.available + earned).min(earned + 1.);
+        }
+        self.last_refill = Some(now);
+    }
+}
+
+/// Decides which of a sink's universes go out with each frame, following
+/// the send policies of the routes to it. When a sink has more than one
+/// route for a universe, the first one's policy is used. Universes that
+/// are held back by a destination's packet budget go first next time.
+#[derive(Default)]
+pub struct OutputSchedule {
+    universes: BTreeMap<UniverseId, UniverseSchedule>,
+    budgets: BTreeMap<Destination, DestinationBudget>,
+    policies: BTreeSet<String>,
+    due: Vec<UniverseId>,
+}
+
+impl OutputSchedule {
+    pub fn new(routes: &[Route]) -> Self {
+        let mut universes = BTreeMap::new();
+        let mut budgets = BTreeMap::<Destination, DestinationBudget>::new();
+        let mut policies = BTreeSet::new();
+        for route in routes {
+            if let Some(policy) = &route.policy {
+                policies.insert(describe_policy(policy));
+            }
+            let schedule = universes
+                .entry(route.universe)
+                .or_insert_with(|| UniverseSchedule::new(&route.policy.unwrap_or_default()));
+            let destinations = if route.destinations.is_empty() {
+                vec![Destination::Default]
+            } else {
+                route
+                    .destinations
+                    .iter()
+                    .copied()
+                    .map(Destination::Host)
+                    .collect()
+            };
+            let rate = route.policy.and_then(|policy| policy.max_destination_rate);
+            for destination in destinations {
+                schedule.destinations.insert(destination);
+                // The strictest route to a destination sets its budget.
+                if let Some(rate) = rate {
+                    budgets
+                        .entry(destination)
+                        .and_modify(|budget| budget.rate = budget.rate.min(rate))
+                        .or_insert_with(|| DestinationBudget::new(rate));
+                }
+            }
+        }
+        Self {
+            universes,
+            budgets,
+            policies,
+            due: Vec::new(),
+        }
+    }
+
+    /// Whether any route to the sink has a send policy. Sinks without
+    /// one are sent every frame whole.
+    pub fn is_scheduled(&self) -> bool {
+        !self.policies.is_empty()
+    }
+
+    /// Returns the universes to send with `frame`.
+    pub fn due(&mut self, frame: &DmxFrame) -> &[UniverseId] {
+        let now = frame.timestamp;
+        for budget in self.budgets.values_mut() {
+            budget.refill(now);
+        }
+
+        let mut wanted = self
+            .universes
+            .iter()
+            .filter(|(universe, schedule)| schedule.wants_send(frame.universe(**universe), now))
+            .map(|(universe, schedule)| (schedule.last_sent, *universe))
+            .collect::<Vec<_>>();
+        // The universes that have waited longest go first.
+        wanted.sort_unstable();
+
+        self.due.clear();
+        for (_, universe) in wanted {
+            let schedule = self.universes.get_mut(&universe).unwrap();
+            let within_budget = schedule.destinations.iter().all(|destination| {
+                self.budgets
+                    .get(destination)
+                    .is_none_or(|budget| budget.available >= 1.)
+            });
+            if !within_budget {
+                continue;
+            }
+            for destination in &schedule.destinations {
+                if let Some(budget) = self.budgets.get_mut(destination) {
+                    budget.available -= 1.;
+                }
+            }
+            schedule.mark_sent(frame.universe(universe), now);
+            self.due.push(universe);
+        }
+        self.due.sort_unstable();
+        &self.due
+    }
+
+    /// The frames per second the sink should manage, or `None` when it
+    /// only sends changes and so has no steady rate.
+    pub fn expected_rate(&self, frame_rate: f32) -> Option<f32> {
+        if !self.is_scheduled() {
+            return Some(frame_rate);
+        }
+        if self.universes.values().any(|schedule| schedule.change_only) {
+            return None;
+        }
+        // Every destination gets the packets its universes want, up to its
+        // budget, and a frame goes out whenever any destination has one.
+        let mut wanted = BTreeMap::<Destination, f32>::new();
+        for schedule in self.universes.values() {
+            for destination in &schedule.destinations {
+                *wanted.entry(*destination).or_default() += schedule.rate(frame_rate);
+            }
+        }
+        wanted
+            .into_iter()
+            .map(|(destination, rate)| {
+                let budget = self
+                    .budgets
+                    .get(&destination)
+                    .map_or(rate, |budget| budget.rate);
+                rate.min(budget).min(frame_rate)
+            })
+            .reduce(f32::max)
+    }
+
+    /// A short description of the send policies, for showing with the
+    /// sink's status.
+    pub fn describe(&self) -> Option<String> {
+        self.is_scheduled()
+            .then(|| self.policies.iter().cloned().collect::<Vec<_>>().join("; "))
+    }
+}
+
+fn describe_policy(policy: &SendPolicy) -> String {
+    let mut parts = Vec::new();
+    if let Some(refresh_rate) = policy.refresh_rate {
+        parts.push(format!("{refresh_rate} fps"));
+    }
+    if policy.change_only {
+        parts.push("on change".to_string());
+    }
+    if let Some(max_destination_rate) = policy.max_destination_rate {
+        parts.push(format!("max {max_destination_rate}/s per destination"));
+    }
+    if parts.is_empty() {
+        "every frame".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::universe::{FrameSources, UniverseBuffer};
+    use std::net::Ipv4Addr;
+
+    fn route(universe: UniverseId, destinations: Vec<IpAddr>, policy: SendPolicy) -> Route {
+        Route {
+            universe,
+            address: None,
+            destinations,
+            priority: None,
+            policy: Some(policy),
+        }
+    }
+
+    /// Runs the schedule for `seconds` at `frame_rate`, returning how many
+    /// times each universe was sent.
+    fn run(
+        schedule: &mut OutputSchedule,
+        frame_rate: f32,
+        seconds: f32,
+    ) -> BTreeMap<UniverseId, usize> {
+        let mut frame = DmxFrame {
+            sequence: 0,
+            timestamp: Instant::now(),
+            universes: UniverseBuffer::default(),
+            sources: FrameSources::default(),
+        };
+        let interval = Duration::from_secs_f32(1. / frame_rate);
+        let mut sent = BTreeMap::<UniverseId, usize>::new();
+        for _ in 0..(frame_rate * seconds).round() as usize {
+            for universe in schedule.due(&frame) {
+                *sent.entry(*universe).or_default() += 1;
+            }
+            frame.timestamp += interval;
+        }
+        sent
+    }
+
+    #[test]
+    fn test_unscheduled_sinks_send_every_frame() {
+        let schedule = OutputSchedule::new(&[Route {
+            universe: 1,
+            address: None,
+            destinations: Vec::new(),
+            priority: None,
+            policy: None,
+        }]);
+        assert!(!schedule.is_scheduled());
+        assert_eq!(schedule.expected_rate(44.), Some(44.));
+        assert_eq!(schedule.describe(), None);
+    }
+
+    #[test]
+    fn test_sends_at_refresh_rate() {
+        let policy = SendPolicy {
+            refresh_rate: Some(25.),
+            ..Default::default()
+        };
+        let mut schedule = OutputSchedule::new(&[route(1, Vec::new(), policy)]);
+        assert_eq!(schedule.expected_rate(100.), Some(25.));
+        assert_eq!(schedule.expected_rate(10.), Some(10.));
+        assert_eq!(schedule.describe().as_deref(), Some("25 fps"));
+
+        let sent = run(&mut schedule, 100., 1.);
+        assert_eq!(sent[&1], 25);
+    }
+
+    #[test]
+    fn test_sends_changes_and_keep_alives() {
+        let policy = SendPolicy {
+            change_only: true,
+            keep_alive_ms: Some(100),
+            ..Default::default()
+        };
+        let mut schedule = OutputSchedule::new(&[route(1, Vec::new(), policy)]);
+        assert_eq!(schedule.expected_rate(44.), None);
+        assert_eq!(schedule.describe().as_deref(), Some("on change"));
+
+        let mut frame = DmxFrame {
+            sequence: 0,
+            timestamp: Instant::now(),
+            universes: UniverseBuffer::default(),
+            sources: FrameSources::default(),
+        };
+        assert_eq!(schedule.due(&frame), [1]);
+        frame.timestamp += Duration::from_millis(20);
+        assert!(schedule.due(&frame).is_empty());
+        frame.universes.set(1, 1, 255);
+        assert_eq!(schedule.due(&frame), [1]);
+        frame.timestamp += Duration::from_millis(20);
+        assert!(schedule.due(&frame).is_empty());
+        frame.timestamp += Duration::from_millis(100);
+        assert_eq!(schedule.due(&frame), [1], "keep-alive");
+    }
+
+    #[test]
+    fn test_shares_destination_budget_among_universes() {
+        let policy = SendPolicy {
+            max_destination_rate: Some(40.),
+            ..Default::default()
+        };
+        let routes = (1..=8)
+            .map(|universe| route(universe, Vec::new(), policy))
+            .collect::<Vec<_>>();
+        let mut schedule = OutputSchedule::new(&routes);
+        assert_eq!(schedule.expected_rate(44.), Some(40.));
+        assert_eq!(
+            schedule.describe().as_deref(),
+            Some("max 40/s per destination")
+        );
+
+        let sent = run(&mut schedule, 44., 2.);
+        let total = sent.values().sum::<usize>();
+        assert!((78..=81).contains(&total), "{total} packets in 2s");
+        // Universes held back go first next time, so none is starved.
+        for (universe, count) in &sent {
+            assert!((9..=11).contains(count), "universe {universe}: {count}");
+        }
+    }
+
+    #[test]
+    fn test_budgets_each_destination() {
+        let node = |last| IpAddr::from(Ipv4Addr::new(10, 0, 0, last));
+        let policy = SendPolicy {
+            max_destination_rate: Some(20.),
+            ..Default::default()
+        };
+        let mut schedule = OutputSchedule::new(&[
+            route(1, vec![node(1)], policy),
+            route(2, vec![node(1)], policy),
+            route(3, vec![node(2)], policy),
+            // Sent to both nodes, so it uses up both budgets.
+            route(4, vec![node(2), node(3)], policy),
+            // Nothing caps the fourth node.
+            route(5, vec![node(4)], SendPolicy::default()),
+        ]);
+        assert_eq!(schedule.expected_rate(40.), Some(40.));
+
+        let sent = run(&mut schedule, 40., 2.);
+        assert_eq!(sent[&1] + sent[&2], 40);
+        assert_eq!(sent[&3] + sent[&4], 40);
+        assert!(sent[&4] >= 19, "{}", sent[&4]);
+        assert_eq!(sent[&5], 80);
+    }
+}
@@ -0,0 +1,133 @@
+use anyhow::{anyhow, Context, Result};
+use gpui::{AppContext, Global};
+use serde::de::DeserializeOwned;
+use std::{collections::BTreeMap, ops::RangeInclusive, time::Instant};
+
+use crate::{
+    routing::Route,
+    schedule::OutputSchedule,
+    status::SinkStatus,
+    universe::{DmxFrame, UniverseId},
+};
+
+/// Something that DMX frames can be sent to, such as a network protocol
+/// or a USB interface.
+pub trait DmxSink: Send {
+    /// Sends the universes this sink is mapped to from `frame`.
+    fn send_frame(&mut self, frame: &DmxFrame) -> Result<()>;
+
+    /// Sends only `universes` from `frame`, when the routes to the sink
+    /// have send policies. Sinks that cannot send a universe on its own
+    /// send the whole frame.
+    fn send_universes(&mut self, frame: &DmxFrame, universes: &[UniverseId]) -> Result<()> {
+        let _ = universes;
+        self.send_frame(frame)
+    }
+
+    /// Called instead of `send_universes` for frames where none of the
+    /// sink's universes are due, so that it can still send packets of its
+    /// own on time, such as sACN universe discovery.
+    fn tick(&mut self, now: Instant) -> Result<()> {
+        let _ = now;
+        Ok(())
+    }
+}
+
+/// A sink that does nothing with the frames it is given, for running the
+/// engine without any hardware attached.
+pub struct NullSink;
+
+impl DmxSink for NullSink {
+    fn send_frame(&mut self, _: &DmxFrame) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The settings a sink is built from.
+pub struct SinkConfig {
+    pub name: String,
+    pub routes: Vec<Route>,
+    pub options: serde_json::Map<String, serde_json::Value>,
+}
+
+impl SinkConfig {
+    /// Deserializes the sink-specific options.
+    pub fn options<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_value(serde_json::Value::Object(self.options.clone()))
+            .with_context(|| format!("invalid options for sink \"{}\"", self.name))
+    }
+}
+
+/// A sink that is running inside the engine.
+pub struct SinkHandle {
+    pub name: String,
+    pub kind: String,
+    pub sink: Box<dyn DmxSink>,
+    pub schedule: OutputSchedule,
+    pub status: SinkStatus,
+}
+
+pub type BuildSink = fn(&SinkConfig) -> Result<Box<dyn DmxSink>>;
+
+pub struct SinkKind {
+    /// The protocol addresses routes can send on, for sinks that have them.
+    pub addresses: Option<RangeInclusive<u16>>,
+    /// The address an internal universe is sent on when its route does
+    /// not give one.
+    pub default_address: fn(UniverseId) -> Option<u16>,
+    pub build: BuildSink,
+}
+
+impl SinkKind {
+    /// A kind of sink with no addressing of its own, such as a USB
+    /// interface with a single universe.
+    pub fn unaddressed(build: BuildSink) -> Self {
+        Self {
+            addresses: None,
+            default_address: |_| None,
+            build,
+        }
+    }
+}
+
+/// The kinds of sink that can be enabled from settings.
+#[derive(Default)]
+pub struct SinkRegistry {
+    kinds: BTreeMap<&'static str, SinkKind>,
+}
+
+impl Global for SinkRegistry {}
+
+impl SinkRegistry {
+    pub fn global(cx: &AppContext) -> &Self {
+        cx.global::<Self>()
+    }
+
+    pub fn register(&mut self, name: &'static str, kind: SinkKind) {
+        self.kinds.insert(name, kind);
+    }
+
+    pub fn get(&self, kind: &str) -> Option<&SinkKind> {
+        self.kinds.get(kind)
+    }
+
+    pub fn kinds(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.kinds.keys().copied()
+    }
+
+    pub fn build(&self, kind: &str, config: &SinkConfig) -> Result<SinkHandle> {
+        let sink_kind = self
+            .get(kind)
+            .ok_or_else(|| anyhow!("unknown sink kind \"{kind}\""))?;
+        let schedule = OutputSchedule::new(&config.routes);
+        let mut status = SinkStatus::new(&config.name, kind);
+        status.policy = schedule.describe();
+        Ok(SinkHandle {
+            name: config.name.clone(),
+            kind: kind.to_string(),
+            sink: (sink_kind.build)(config)?,
+            schedule,
+            status,
+        })
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+/// How long frames are counted for before the frame rate is updated.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// A sink is warned about when it sends fewer than this fraction of the
+/// frames per second it should.
+const RATE_DROP_THRESHOLD: f32 = 0.9;
+
+/// The live health of a single sink.
+#[derive(Clone, Debug)]
+pub struct SinkStatus {
+    pub name: String,
+    pub kind: String,
+    pub running: bool,
+    pub frames_sent: u64,
+    /// Frames the sink's send policies held back.
+    pub frames_skipped: u64,
+    pub frames_per_second: f32,
+    /// The frames per second the sink should send, or `None` when it only
+    /// sends changes.
+    pub expected_rate: Option<f32>,
+    /// The send policies of the routes to the sink, if any.
+    pub policy: Option<String>,
+    pub errors: u64,
+    pub last_error: Option<String>,
+    /// Whether the most recent frame failed to send.
+    pub failing: bool,
+    /// Whether the measured frame rate is below the expected one.
+    pub rate_dropped: bool,
+    window_start: Option<Instant>,
+    window_frames: u64,
+}
+
+impl SinkStatus {
+    pub fn new(name: impl Into<String>, kind: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            kind: kind.into(),
+            running: true,
+            frames_sent: 0,
+            frames_skipped: 0,
+            frames_per_second: 0.,
+            expected_rate: None,
+            policy: None,
+            errors: 0,
+            last_error: None,
+            failing: false,
+            rate_dropped: false,
+            window_start: None,
+            window_frames: 0,
+        }
+    }
+
+    /// The status of a sink that could not be started.
+    pub fn failed(name: impl Into<String>, kind: impl Into<String>, error: String) -> Self {
+        Self {
+            running: false,
+            errors: 1,
+            last_error: Some(error),
+            failing: true,
+            ..Self::new(name, kind)
+        }
+    }
+
+    pub(crate) fn record(&mut self, result: &Result<()>, now: Instant) {
+        match result {
+            Ok(()) => {
+                self.frames_sent += 1;
+                self.window_frames += 1;
+                self.failing = false;
+            }
+            Err(err) => {
+                self.errors += 1;
+                self.last_error = Some(format!("{err:#}"));
+                self.failing = true;
+            }
+        }
+        self.update_rate(now);
+    }
+
+    /// Records a frame that the sink's send policies held back.
+    pub(crate) fn record_skipped(&mut self, now: Instant) {
+        self.frames_skipped += 1;
+        self.update_rate(now);
+    }
+
+    fn update_rate(&mut self, now: Instant) {
+        let window_start = *self.window_start.get_or_insert(now);
+        let elapsed = now - window_start;
+        if elapsed >= RATE_WINDOW {
+            self.frames_per_second = self.window_frames as f32 / elapsed.as_secs_f32();
+            self.rate_dropped = self.expected_rate.is_some_and(|expected_rate| {
+                self.frames_per_second < expected_rate * RATE_DROP_THRESHOLD
+            });
+            self.window_start = Some(now);
+            self.window_frames = 0;
+        }
+    }
+
+    pub fn has_error(&self) -> bool {
+        !self.running || self.failing
+    }
+
+    pub fn has_warning(&self) -> bool {
+        self.running && !self.failing && self.rate_dropped
+    }
+}
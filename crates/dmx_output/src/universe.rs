@@ -0,0 +1,204 @@
+use std::{
+    collections::BTreeMap,
+    ops::{Deref, DerefMut},
+    time::Instant,
+};
+
+pub const UNIVERSE_SIZE: usize = 512;
+
+/// Identifies one of Tungsten's internal universes, starting at 1.
+pub type UniverseId = u16;
+
+/// The 512 slot levels of a single DMX universe.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Universe([u8; UNIVERSE_SIZE]);
+
+impl Default for Universe {
+    fn default() -> Self {
+        Self([0; UNIVERSE_SIZE])
+    }
+}
+
+impl Universe {
+    /// Returns the level of the 1-based DMX `address`.
+    pub fn get(&self, address: u16) -> Option<u8> {
+        let index = (address as usize).checked_sub(1)?;
+        self.0.get(index).copied()
+    }
+
+    /// Sets the level of the 1-based DMX `address`, returning whether
+    /// the address is within the universe.
+    pub fn set(&mut self, address: u16, level: u8) -> bool {
+        match (address as usize)
+            .checked_sub(1)
+            .and_then(|index| self.0.get_mut(index))
+        {
+            Some(slot) => {
+                *slot = level;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Copies `levels` into the universe starting at the first slot.
+    pub fn copy_from(&mut self, levels: &[u8]) {
+        let len = levels.len().min(UNIVERSE_SIZE);
+        self.0[..len].copy_from_slice(&levels[..len]);
+    }
+}
+
+impl Deref for Universe {
+    type Target = [u8; UNIVERSE_SIZE];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Universe {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// The output levels of every universe that has been written to.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct UniverseBuffer {
+    universes: BTreeMap<UniverseId, Universe>,
+}
+
+impl UniverseBuffer {
+    pub fn get(&self, universe: UniverseId) -> Option<&Universe> {
+        self.universes.get(&universe)
+    }
+
+    /// Returns the universe with the given id, creating it at zero if it
+    /// has not been written to yet.
+    pub fn get_mut(&mut self, universe: UniverseId) -> &mut Universe {
+        self.universes.entry(universe).or_default()
+    }
+
+    pub fn set(&mut self, universe: UniverseId, address: u16, level: u8) -> bool {
+        self.get_mut(universe).set(address, level)
+    }
+
+    pub fn remove(&mut self, universe: UniverseId) -> Option<Universe> {
+        self.universes.remove(&universe)
+    }
+
+    pub fn clear(&mut self) {
+        self.universes.clear();
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = UniverseId> + '_ {
+        self.universes.keys().copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (UniverseId, &Universe)> {
+        self.universes
+            .iter()
+            .map(|(universe, levels)| (*universe, levels))
+    }
+}
+
+/// Where the level of a slot in a frame came from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SlotSource {
+    /// Nothing set the slot above zero.
+    #[default]
+    None,
+    /// The universe buffer, which cues and recordings play back into.
+    Playback,
+    /// The levels programmed into patched fixtures.
+    Programmer,
+    /// Levels received from the network and merged in.
+    Input,
+}
+
+/// The source of every slot of the universes in a frame.
+#[derive(Clone, Default)]
+pub struct FrameSources {
+    universes: BTreeMap<UniverseId, [SlotSource; UNIVERSE_SIZE]>,
+}
+
+impl FrameSources {
+    /// Marks the slots of `levels` that are above zero as played back.
+    pub fn playback(levels: &UniverseBuffer) -> Self {
+        let mut sources = Self::default();
+        for (id, universe) in levels.iter() {
+            let slots = sources.get_mut(id);
+            for (source, level) in slots.iter_mut().zip(universe.iter()) {
+                if *level > 0 {
+                    *source = SlotSource::Playback;
+                }
+            }
+        }
+        sources
+    }
+
+    /// Returns the source of the 1-based DMX `address`.
+    pub fn get(&self, universe: UniverseId, address: u16) -> SlotSource {
+        let Some(index) = (address as usize).checked_sub(1) else {
+            return SlotSource::None;
+        };
+        self.universes
+            .get(&universe)
+            .and_then(|slots| slots.get(index))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set(&mut self, universe: UniverseId, address: u16, source: SlotSource) {
+        if let Some(slot) = (address as usize)
+            .checked_sub(1)
+            .and_then(|index| self.get_mut(universe).get_mut(index))
+        {
+            *slot = source;
+        }
+    }
+
+    /// Marks the slots whose levels differ between `before` and `after`.
+    pub fn mark_changes(
+        &mut self,
+        before: &UniverseBuffer,
+        after: &UniverseBuffer,
+        source: SlotSource,
+    ) {
+        let unset = Universe::default();
+        for (id, levels) in after.iter() {
+            let previous = before.get(id).unwrap_or(&unset);
+            let slots = self.get_mut(id);
+            for (slot, (level, previous)) in levels.iter().zip(previous.iter()).enumerate() {
+                if level != previous {
+                    slots[slot] = source;
+                }
+            }
+        }
+    }
+
+    fn get_mut(&mut self, universe: UniverseId) -> &mut [SlotSource; UNIVERSE_SIZE] {
+        self.universes
+            .entry(universe)
+            .or_insert([SlotSource::None; UNIVERSE_SIZE])
+    }
+}
+
+/// A snapshot of the universe buffer taken by the frame scheduler and
+/// handed to every output.
+#[derive(Clone)]
+pub struct DmxFrame {
+    pub sequence: u64,
+    pub timestamp: Instant,
+    pub universes: UniverseBuffer,
+    /// Where each level came from, for monitoring.
+    pub sources: FrameSources,
+}
+
+impl DmxFrame {
+    /// Returns the levels of `universe`, or `None` if nothing has
+    /// written to it.
+    pub fn universe(&self, universe: UniverseId) -> Option<&Universe> {
+        self.universes.get(universe)
+    }
+}
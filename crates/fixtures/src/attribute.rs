@@ -0,0 +1,200 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// What a channel controls. Channels that drive the same attribute on
+/// different fixtures can be programmed together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Attribute {
+    Intensity,
+    Shutter,
+    Strobe,
+
+    Pan,
+    Tilt,
+    PanTiltSpeed,
+
+    Red,
+    Green,
+    Blue,
+    White,
+    WarmWhite,
+    ColdWhite,
+    Amber,
+    Lime,
+    Uv,
+    Cyan,
+    Magenta,
+    Yellow,
+    Cto,
+    Hue,
+    Saturation,
+    ColorWheel,
+    ColorMacro,
+
+    Gobo,
+    GoboRotation,
+    GoboWheelRotation,
+    Prism,
+    PrismRotation,
+    Iris,
+    Frost,
+    Zoom,
+    Focus,
+    Effect,
+    EffectSpeed,
+
+    Control,
+    Maintenance,
+    Other,
+}
+
+/// The feature groups attributes are shown and programmed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeGroup {
+    Intensity,
+    Position,
+    Color,
+    Beam,
+    Focus,
+    Control,
+}
+
+impl Attribute {
+    pub const ALL: [Attribute; 37] = [
+        Attribute::Intensity,
+        Attribute::Shutter,
+        Attribute::Strobe,
+        Attribute::Pan,
+        Attribute::Tilt,
+        Attribute::PanTiltSpeed,
+        Attribute::Red,
+        Attribute::Green,
+        Attribute::Blue,
+        Attribute::White,
+        Attribute::WarmWhite,
+        Attribute::ColdWhite,
+        Attribute::Amber,
+        Attribute::Lime,
+        Attribute::Uv,
+        Attribute::Cyan,
+        Attribute::Magenta,
+        Attribute::Yellow,
+        Attribute::Cto,
+        Attribute::Hue,
+        Attribute::Saturation,
+        Attribute::ColorWheel,
+        Attribute::ColorMacro,
+        Attribute::Gobo,
+        Attribute::GoboRotation,
+        Attribute::GoboWheelRotation,
+        Attribute::Prism,
+        Attribute::PrismRotation,
+        Attribute::Iris,
+        Attribute::Frost,
+        Attribute::Zoom,
+        Attribute::Focus,
+        Attribute::Effect,
+        Attribute::EffectSpeed,
+        Attribute::Control,
+        Attribute::Maintenance,
+        Attribute::Other,
+    ];
+
+    pub fn group(self) -> AttributeGroup {
+        use Attribute::*;
+        match self {
+            Intensity | Shutter | Strobe => AttributeGroup::Intensity,
+            Pan | Tilt | PanTiltSpeed => AttributeGroup::Position,
+            Red | Green | Blue | White | WarmWhite | ColdWhite | Amber | Lime | Uv | Cyan
+            | Magenta | Yellow | Cto | Hue | Saturation | ColorWheel | ColorMacro => {
+                AttributeGroup::Color
+            }
+            Gobo | GoboRotation | GoboWheelRotation | Prism | PrismRotation | Iris | Frost
+            | Effect | EffectSpeed => AttributeGroup::Beam,
+            Zoom | Focus => AttributeGroup::Focus,
+            Control | Maintenance | Other => AttributeGroup::Control,
+        }
+    }
+
+    /// Whether the attribute is a light source that adds to the fixture's
+    /// output, and so dims along with it.
+    pub fn is_additive_color(self) -> bool {
+        use Attribute::*;
+        matches!(
+            self,
+            Red | Green | Blue | White | WarmWhite | ColdWhite | Amber | Lime | Uv
+        )
+    }
+
+    /// Whether the channel's levels blend smoothly from one to the next,
+    /// as opposed to selecting a slot or function by range.
+    pub fn is_continuous(self) -> bool {
+        use Attribute::*;
+        !matches!(
+            self,
+            ColorWheel | ColorMacro | Gobo | Prism | Effect | Control | Maintenance
+        )
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Attribute::*;
+        let name = match self {
+            Intensity => "Intensity",
+            Shutter => "Shutter",
+            Strobe => "Strobe",
+            Pan => "Pan",
+            Tilt => "Tilt",
+            PanTiltSpeed => "Pan/Tilt Speed",
+            Red => "Red",
+            Green => "Green",
+            Blue => "Blue",
+            White => "White",
+            WarmWhite => "Warm White",
+            ColdWhite => "Cold White",
+            Amber => "Amber",
+            Lime => "Lime",
+            Uv => "UV",
+            Cyan => "Cyan",
+            Magenta => "Magenta",
+            Yellow => "Yellow",
+            Cto => "CTO",
+            Hue => "Hue",
+            Saturation => "Saturation",
+            ColorWheel => "Color Wheel",
+            ColorMacro => "Color Macro",
+            Gobo => "Gobo",
+            GoboRotation => "Gobo Rotation",
+            GoboWheelRotation => "Gobo Wheel Rotation",
+            Prism => "Prism",
+            PrismRotation => "Prism Rotation",
+            Iris => "Iris",
+            Frost => "Frost",
+            Zoom => "Zoom",
+            Focus => "Focus",
+            Effect => "Effect",
+            EffectSpeed => "Effect Speed",
+            Control => "Control",
+            Maintenance => "Maintenance",
+            Other => "Other",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Parses the name an attribute is shown with, ignoring case.
+impl FromStr for Attribute {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        Attribute::ALL
+            .into_iter()
+            .find(|attribute| attribute.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| anyhow!("unknown attribute \"{s}\""))
+    }
+}
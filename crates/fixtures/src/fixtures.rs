@@ -0,0 +1,12 @@
+pub mod attribute;
+pub mod gdtf;
+pub mod library;
+pub mod ofl;
+pub mod profile;
+
+pub use attribute::{Attribute, AttributeGroup};
+pub use library::{FixtureLibrary, ImportReport, ProfileKey};
+pub use profile::{
+    Capability, Channel, DmxRange, DmxValue, Emitter, FixtureProfile, Head, Mode, Physical,
+    PhysicalRange, Resolution, Unit, Wheel, WheelSlot, WheelSlotRef,
+};
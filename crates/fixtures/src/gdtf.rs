@@ -0,0 +1,720 @@
+use anyhow::{anyhow, Context, Result};
+use roxmltree::{Document, Node};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{Read, Seek},
+    path::{Path, PathBuf},
+};
+use zip::ZipArchive;
+
+use crate::{
+    attribute::Attribute,
+    library::{FixtureLibrary, ImportReport, ProfileKey},
+    profile::{
+        Capability, Channel, DmxRange, DmxValue, Emitter, FixtureProfile, Head, Mode, Physical,
+        PhysicalRange, Resolution, Unit, Wheel, WheelSlot, WheelSlotRef,
+    },
+};
+
+const DESCRIPTION_FILE: &str = "description.xml";
+/// Where GDTF archives keep the pictures of wheel slots.
+const WHEELS_DIR: &str = "wheels";
+
+/// A geometry reference in a mode's geometry tree: a copy of another
+/// geometry, like one pixel of a batten, whose channels start at its own
+/// offset.
+struct GeometryReference {
+    name: String,
+    geometry: String,
+    /// The DMX offset of each break, counted from 1.
+    offsets: BTreeMap<u32, u16>,
+}
+
+/// The parts of an archive that the modes refer to.
+struct Definitions {
+    /// The physical unit of each attribute.
+    units: BTreeMap<String, Option<Unit>>,
+    wheels: Vec<Wheel>,
+}
+
+/// Imports every `.gdtf` file under `dir` into `library`, saving the
+/// pictures of their wheel slots under `images_dir`.
+pub fn import_dir(
+    dir: &Path,
+    images_dir: &Path,
+    library: &mut FixtureLibrary,
+) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    let mut files = Vec::new();
+    collect_files(dir, &mut files).with_context(|| format!("reading {}", dir.display()))?;
+    files.sort();
+
+    for path in files {
+        match import_file(&path, images_dir) {
+            Ok((profile, warnings)) => {
+                let key = ProfileKey::new(&profile.manufacturer, &profile.model);
+                match library.insert(profile) {
+                    Ok(()) => {
+                        report.imported.push(key);
+                        report.warnings.extend(
+                            warnings
+                                .into_iter()
+                                .map(|warning| format!("{}: {warning}", path.display())),
+                        );
+                    }
+                    Err(err) => report.failed.push((path, format!("{err:#}"))),
+                }
+            }
+            Err(err) => report.failed.push((path, format!("{err:#}"))),
+        }
+    }
+    Ok(report)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("gdtf"))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Reads a GDTF archive into a profile, with warnings about anything that
+/// could not be carried over. The pictures of its wheel slots are saved in
+/// a directory of their own under `images_dir`.
+pub fn import_file(path: &Path, images_dir: &Path) -> Result<(FixtureProfile, Vec<String>)> {
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    import(file, images_dir)
+}
+
+pub fn import(
+    reader: impl Read + Seek,
+    images_dir: &Path,
+) -> Result<(FixtureProfile, Vec<String>)> {
+    let mut archive = ZipArchive::new(reader).context("reading GDTF archive")?;
+    let mut description = String::new();
+    archive
+        .by_name(DESCRIPTION_FILE)
+        .with_context(|| format!("GDTF archive has no {DESCRIPTION_FILE}"))?
+        .read_to_string(&mut description)?;
+    let (mut profile, warnings) = parse(&description)?;
+
+    let images_dir = images_dir.join(sanitize(&format!(
+        "{} {}",
+        profile.manufacturer, profile.model
+    )));
+    for wheel in &mut profile.wheels {
+        for slot in &mut wheel.slots {
+            let Some(media) = slot.image.take() else {
+                continue;
+            };
+            slot.image = extract_image(&mut archive, &media, &images_dir)?;
+        }
+    }
+    Ok((profile, warnings))
+}
+
+/// Saves the picture a wheel slot names, returning where it went, or
+/// `None` when the archive does not have it.
+fn extract_image<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    media: &Path,
+    images_dir: &Path,
+) -> Result<Option<PathBuf>> {
+    let media = media.to_string_lossy();
+    let prefix = format!("{WHEELS_DIR}/{media}.");
+    let Some(name) = archive
+        .file_names()
+        .find(|name| name.starts_with(&prefix))
+        .map(str::to_string)
+    else {
+        return Ok(None);
+    };
+    let mut image = Vec::new();
+    archive.by_name(&name)?.read_to_end(&mut image)?;
+    let file_name = Path::new(&name)
+        .file_name()
+        .ok_or_else(|| anyhow!("invalid wheel image name {name}"))?;
+    fs::create_dir_all(images_dir).with_context(|| format!("creating {}", images_dir.display()))?;
+    let path = images_dir.join(file_name);
+    fs::write(&path, image).with_context(|| format!("writing {}", path.display()))?;
+    Ok(Some(path))
+}
+
+/// Reads a GDTF description into a profile. Wheel slots with pictures
+/// are left holding the archive's name for them.
+pub fn parse(description: &str) -> Result<(FixtureProfile, Vec<String>)> {
+    let document = Document::parse(description).context("parsing description.xml")?;
+    let fixture_type = document
+        .root_element()
+        .children()
+        .find(|node| node.has_tag_name("FixtureType"))
+        .ok_or_else(|| anyhow!("description.xml has no FixtureType"))?;
+    let mut warnings = Vec::new();
+
+    let definitions = Definitions {
+        units: child(fixture_type, "AttributeDefinitions")
+            .and_then(|definitions| child(definitions, "Attributes"))
+            .map(|attributes| {
+                elements(attributes, "Attribute")
+                    .filter_map(|attribute| {
+                        let name = attribute.attribute("Name")?;
+                        Some((
+                            name.to_string(),
+                            physical_unit(attribute.attribute("PhysicalUnit")),
+                        ))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        wheels: child(fixture_type, "Wheels")
+            .map(|wheels| elements(wheels, "Wheel").map(parse_wheel).collect())
+            .unwrap_or_default(),
+    };
+
+    let geometries = child(fixture_type, "Geometries");
+    let modes = child(fixture_type, "DMXModes")
+        .map(|modes| {
+            elements(modes, "DMXMode")
+                .map(|mode| parse_mode(mode, geometries, &definitions, &mut warnings))
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+    if modes.is_empty() {
+        return Err(anyhow!("fixture type has no DMX modes"));
+    }
+    warnings.dedup();
+
+    let name = |attribute: &str| {
+        fixture_type
+            .attribute(attribute)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+    };
+    Ok((
+        FixtureProfile {
+            manufacturer: name("Manufacturer").unwrap_or_default(),
+            model: name("Name")
+                .or_else(|| name("LongName"))
+                .unwrap_or_default(),
+            short_name: name("ShortName"),
+            modes,
+            wheels: definitions.wheels,
+            physical: Some(parse_physical(fixture_type)),
+        },
+        warnings,
+    ))
+}
+
+fn parse_wheel(wheel: Node) -> Wheel {
+    Wheel {
+        name: wheel.attribute("Name").unwrap_or_default().to_string(),
+        slots: elements(wheel, "Slot")
+            .map(|slot| WheelSlot {
+                name: slot.attribute("Name").unwrap_or_default().to_string(),
+                color: slot.attribute("Color").and_then(parse_color),
+                image: slot
+                    .attribute("MediaFileName")
+                    .filter(|media| !media.is_empty())
+                    .map(PathBuf::from),
+            })
+            .collect(),
+    }
+}
+
+fn parse_physical(fixture_type: Node) -> Physical {
+    let descriptions = child(fixture_type, "PhysicalDescriptions");
+    let property = |name: &str| {
+        descriptions
+            .and_then(|descriptions| child(descriptions, "Properties"))
+            .and_then(|properties| child(properties, name))
+            .and_then(|property| property.attribute("Value"))
+            .and_then(|value| value.parse::<f32>().ok())
+    };
+    // The dimensions of the fixture are those of the model of its root
+    // geometry, in meters.
+    let dimensions = child(fixture_type, "Geometries")
+        .and_then(|geometries| geometries.children().find(Node::is_element))
+        .and_then(|root| root.attribute("Model"))
+        .and_then(|model| {
+            let models = child(fixture_type, "Models")?;
+            elements(models, "Model").find(|node| node.attribute("Name") == Some(model))
+        })
+        .and_then(|model| {
+            let size = |name: &str| model.attribute(name)?.parse::<f32>().ok();
+            Some([
+                size("Width")? * 1000.,
+                size("Height")? * 1000.,
+                size("Length")? * 1000.,
+            ])
+        });
+
+    Physical {
+        dimensions,
+        weight: property("Weight"),
+        power: property("PowerConsumption"),
+        emitters: descriptions
+            .and_then(|descriptions| child(descriptions, "Emitters"))
+            .map(|emitters| {
+                elements(emitters, "Emitter")
+                    .map(|emitter| Emitter {
+                        name: emitter.attribute("Name").unwrap_or_default().to_string(),
+                        color: emitter.attribute("Color").and_then(parse_color),
+                        dominant_wavelength: emitter
+                            .attribute("DominantWaveLength")
+                            .and_then(|wavelength| wavelength.parse().ok()),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn parse_mode(
+    mode: Node,
+    geometries: Option<Node>,
+    definitions: &Definitions,
+    warnings: &mut Vec<String>,
+) -> Result<Mode> {
+    let name = mode.attribute("Name").unwrap_or_default().to_string();
+    let root = mode.attribute("Geometry").and_then(|root| {
+        geometries?
+            .children()
+            .find(|node| node.is_element() && node.attribute("Name") == Some(root))
+    });
+    let mut references = Vec::new();
+    if let Some(root) = root {
+        collect_references(root, &mut references);
+    }
+
+    // Virtual channels have no DMX of their own, so are left out.
+    let dmx_channels = child(mode, "DMXChannels")
+        .map(|channels| {
+            elements(channels, "DMXChannel")
+                .filter(|channel| {
+                    channel
+                        .attribute("Offset")
+                        .is_some_and(|offset| offset != "None" && !offset.is_empty())
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let mut channels = Vec::new();
+    let mut heads = Vec::<Head>::new();
+    for dmx_channel in &dmx_channels {
+        let offsets = dmx_channel.attribute("Offset").unwrap_or_default();
+        let offsets = offsets
+            .split(',')
+            .map(|offset| offset.trim().parse::<u16>())
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("mode \"{name}\": invalid channel offset {offsets}"))?;
+        let dmx_break = dmx_channel
+            .attribute("DMXBreak")
+            .and_then(|dmx_break| dmx_break.parse::<u32>().ok())
+            .unwrap_or(1);
+        let geometry = dmx_channel.attribute("Geometry").unwrap_or_default();
+        let Some(channel) = parse_channel(*dmx_channel, definitions, warnings) else {
+            continue;
+        };
+
+        // Channels of a referenced geometry are repeated for each
+        // reference, from the reference's offset.
+        let instances = references
+            .iter()
+            .filter(|reference| reference.geometry == geometry)
+            .collect::<Vec<_>>();
+        if instances.is_empty() {
+            if dmx_break != 1 {
+                warnings.push(format!(
+                    "mode \"{name}\": channel \"{}\" is on DMX break {dmx_break} and was left out",
+                    channel.name
+                ));
+                continue;
+            }
+            // Tell apart channels for the same attribute on different
+            // geometries, like the dimmers of a fixture with two beams.
+            let name = if dmx_channels.iter().any(|other| {
+                other != dmx_channel
+                    && other.attribute("Geometry") != Some(geometry)
+                    && channel_attribute(*other) == channel_attribute(*dmx_channel)
+            }) {
+                format!("{geometry} {}", channel.name)
+            } else {
+                channel.name.clone()
+            };
+            channels.push(Channel {
+                name,
+                offsets: offsets
+                    .iter()
+                    .map(|offset| offset.saturating_sub(1))
+                    .collect(),
+                ..channel
+            });
+            continue;
+        }
+        for reference in instances {
+            // Channels marked to take the reference's break have none of
+            // their own.
+            let Some((reference_break, reference_offset)) = reference
+                .offsets
+                .get_key_value(&dmx_break)
+                .or_else(|| reference.offsets.iter().next())
+            else {
+                continue;
+            };
+            if *reference_break != 1 {
+                warnings.push(format!(
+                    "mode \"{name}\": \"{}\" is on DMX break {reference_break} and was left out",
+                    reference.name
+                ));
+                continue;
+            }
+            let head_channel = channels.len();
+            channels.push(Channel {
+                name: format!("{} {}", reference.name, channel.name),
+                offsets: offsets
+                    .iter()
+                    .map(|offset| (offset + reference_offset).saturating_sub(2))
+                    .collect(),
+                ..channel.clone()
+            });
+            match heads.iter_mut().find(|head| head.name == reference.name) {
+                Some(head) => head.channels.push(head_channel),
+                None => heads.push(Head {
+                    name: reference.name.clone(),
+                    channels: vec![head_channel],
+                }),
+            }
+        }
+    }
+
+    Ok(Mode {
+        name,
+        channels,
+        heads,
+    })
+}
+
+fn collect_references(geometry: Node, references: &mut Vec<GeometryReference>) {
+    for node in geometry.children().filter(Node::is_element) {
+        if node.has_tag_name("GeometryReference") {
+            references.push(GeometryReference {
+                name: node.attribute("Name").unwrap_or_default().to_string(),
+                geometry: node.attribute("Geometry").unwrap_or_default().to_string(),
+                offsets: elements(node, "Break")
+                    .filter_map(|dmx_break| {
+                        Some((
+                            dmx_break.attribute("DMXBreak")?.parse().ok()?,
+                            dmx_break.attribute("DMXOffset")?.parse().ok()?,
+                        ))
+                    })
+                    .collect(),
+            });
+        } else {
+            collect_references(node, references);
+        }
+    }
+}
+
+fn channel_attribute<'a>(dmx_channel: Node<'a, '_>) -> Option<&'a str> {
+    elements(dmx_channel, "LogicalChannel")
+        .next()?
+        .attribute("Attribute")
+}
+
+/// Turns a DMX channel into a profile channel with offsets counted from 1,
+/// for the caller to place.
+fn parse_channel(
+    dmx_channel: Node,
+    definitions: &Definitions,
+    warnings: &mut Vec<String>,
+) -> Option<Channel> {
+    let offsets = dmx_channel.attribute("Offset")?.split(',').count();
+    let resolution = Resolution::from_bytes(offsets)?;
+    let attribute_name = channel_attribute(dmx_channel)?;
+    let attribute = attribute(attribute_name).unwrap_or_else(|| {
+        warnings.push(format!("unsupported attribute {attribute_name}"));
+        Attribute::Other
+    });
+
+    // The functions of every logical channel split the channel's range
+    // between them, each running up to where the next starts.
+    let mut functions = elements(dmx_channel, "LogicalChannel")
+        .flat_map(|logical| elements(logical, "ChannelFunction"))
+        .map(|function| {
+            let from = function
+                .attribute("DMXFrom")
+                .and_then(|value| dmx_value(value, resolution))
+                .unwrap_or(0);
+            (from, function)
+        })
+        .collect::<Vec<_>>();
+    functions.sort_by_key(|(from, _)| *from);
+
+    let max_value = resolution.max_value();
+    let initial = dmx_channel.attribute("InitialFunction");
+    let mut default = dmx_channel
+        .attribute("Default")
+        .and_then(|value| dmx_value(value, resolution));
+    let mut capabilities = Vec::new();
+    let mut physical = None;
+    for (index, (from, function)) in functions.iter().enumerate() {
+        let to = functions
+            .get(index + 1)
+            .map_or(max_value, |(next, _)| next.saturating_sub(1).max(*from));
+        let function_name = function.attribute("Name").unwrap_or_default();
+        let is_initial =
+            initial.is_some_and(|initial| initial.ends_with(&format!(".{function_name}")));
+        if default.is_none() || is_initial {
+            if let Some(value) = function
+                .attribute("Default")
+                .and_then(|value| dmx_value(value, resolution))
+            {
+                default = Some(value);
+            }
+        }
+
+        let function_attribute = function.attribute("Attribute").unwrap_or(attribute_name);
+        let unit = definitions.units.get(function_attribute).copied().flatten();
+        let function_physical = physical_range(*function, unit);
+        if functions.len() == 1 {
+            physical = function_physical;
+        }
+        let wheel = function
+            .attribute("Wheel")
+            .filter(|wheel| definitions.wheels.iter().any(|other| other.name == *wheel));
+
+        let sets = elements(*function, "ChannelSet")
+            .filter_map(|set| {
+                let set_from = dmx_value(set.attribute("DMXFrom")?, resolution)?;
+                Some((set_from.clamp(*from, to), set))
+            })
+            .collect::<Vec<_>>();
+        if sets.is_empty() {
+            if functions.len() > 1 {
+                capabilities.push(Capability {
+                    range: DmxRange::new(*from, to),
+                    name: function_name.to_string(),
+                    physical: function_physical,
+                    wheel_slot: None,
+                });
+            }
+            continue;
+        }
+        // Levels before the first set still belong to the function.
+        if sets[0].0 > *from {
+            capabilities.push(Capability {
+                range: DmxRange::new(*from, sets[0].0 - 1),
+                name: function_name.to_string(),
+                physical: function_physical,
+                wheel_slot: None,
+            });
+        }
+        for (set_index, (set_from, set)) in sets.iter().enumerate() {
+            let set_to = sets
+                .get(set_index + 1)
+                .map_or(to, |(next, _)| next.saturating_sub(1).max(*set_from));
+            let wheel_slot = wheel.and_then(|wheel| {
+                let slot = set
+                    .attribute("WheelSlotIndex")?
+                    .parse::<usize>()
+                    .ok()?
+                    .checked_sub(1)?;
+                Some(WheelSlotRef {
+                    wheel: wheel.to_string(),
+                    slot,
+                })
+            });
+            let name = set
+                .attribute("Name")
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .or_else(|| {
+                    let slot = wheel_slot.as_ref()?;
+                    let wheel = definitions
+                        .wheels
+                        .iter()
+                        .find(|wheel| wheel.name == slot.wheel)?;
+                    Some(wheel.slots.get(slot.slot)?.name.clone())
+                })
+                .unwrap_or_else(|| function_name.to_string());
+            capabilities.push(Capability {
+                range: DmxRange::new(*set_from, set_to),
+                name,
+                physical: physical_range(*set, unit).or(function_physical),
+                wheel_slot,
+            });
+        }
+    }
+    // Sets at the same level leave empty ranges behind them.
+    capabilities.retain(|capability| capability.range.from <= capability.range.to);
+    capabilities.dedup_by(|next, previous| next.range.from <= previous.range.to);
+
+    let highlight = dmx_channel
+        .attribute("Highlight")
+        .and_then(|value| dmx_value(value, resolution));
+    Some(Channel {
+        name: attribute_name.to_string(),
+        attribute,
+        offsets: dmx_channel
+            .attribute("Offset")?
+            .split(',')
+            .filter_map(|offset| offset.trim().parse().ok())
+            .collect(),
+        default: default.unwrap_or(0).min(max_value),
+        highlight: highlight.map(|highlight| highlight.min(max_value)),
+        physical,
+        capabilities,
+    })
+}
+
+fn physical_range(node: Node, unit: Option<Unit>) -> Option<PhysicalRange> {
+    let from = node.attribute("PhysicalFrom")?.parse::<f32>().ok()?;
+    let to = node.attribute("PhysicalTo")?.parse::<f32>().ok()?;
+    let unit = unit?;
+    Some(match unit {
+        // GDTF gives percentages as fractions.
+        Unit::Percent => PhysicalRange {
+            from: from * 100.,
+            to: to * 100.,
+            unit,
+        },
+        _ => PhysicalRange { from, to, unit },
+    })
+}
+
+/// Reads a GDTF DMX value, written as "value/bytes", at `resolution`.
+/// Values without a byte count are taken to be at `resolution` already.
+fn dmx_value(value: &str, resolution: Resolution) -> Option<DmxValue> {
+    if value == "None" {
+        return None;
+    }
+    match value.split_once('/') {
+        Some((value, bytes)) => {
+            let value = value.parse::<DmxValue>().ok()?;
+            let bytes = Resolution::from_bytes(bytes.parse().ok()?)?;
+            Some(bytes.convert(value, resolution))
+        }
+        None => value.parse().ok(),
+    }
+}
+
+fn physical_unit(unit: Option<&str>) -> Option<Unit> {
+    match unit? {
+        "Percent" => Some(Unit::Percent),
+        "Angle" => Some(Unit::Degrees),
+        "AngularSpeed" => Some(Unit::DegreesPerSecond),
+        "Frequency" => Some(Unit::Hertz),
+        "Time" => Some(Unit::Seconds),
+        "ColorTemperature" | "Temperature" => Some(Unit::Kelvin),
+        "Length" => Some(Unit::Meters),
+        _ => None,
+    }
+}
+
+/// Maps a GDTF attribute to ours. Numbered attributes like "Gobo2" or
+/// "Shutter1Strobe" are matched without their numbers.
+fn attribute(name: &str) -> Option<Attribute> {
+    let base = name
+        .chars()
+        .filter(|c| !c.is_ascii_digit())
+        .collect::<String>();
+    let attribute = match base.as_str() {
+        "Dimmer" => Attribute::Intensity,
+        "Shutter" => Attribute::Shutter,
+        "Pan" | "PanRotate" => Attribute::Pan,
+        "Tilt" | "TiltRotate" => Attribute::Tilt,
+        "PositionMSpeed" | "PositionMTime" => Attribute::PanTiltSpeed,
+        "ColorAdd_R" => Attribute::Red,
+        "ColorAdd_G" => Attribute::Green,
+        "ColorAdd_B" => Attribute::Blue,
+        "ColorAdd_W" => Attribute::White,
+        "ColorAdd_WW" => Attribute::WarmWhite,
+        "ColorAdd_CW" => Attribute::ColdWhite,
+        "ColorAdd_RY" => Attribute::Amber,
+        "ColorAdd_GY" => Attribute::Lime,
+        "ColorAdd_UV" => Attribute::Uv,
+        "ColorAdd_C" | "ColorSub_C" => Attribute::Cyan,
+        "ColorAdd_M" | "ColorSub_M" => Attribute::Magenta,
+        "ColorAdd_Y" | "ColorSub_Y" => Attribute::Yellow,
+        "CTO" | "CTC" | "CTB" => Attribute::Cto,
+        "HSB_Hue" => Attribute::Hue,
+        "HSB_Saturation" => Attribute::Saturation,
+        "Color" => Attribute::ColorWheel,
+        "Gobo" => Attribute::Gobo,
+        "Prism" => Attribute::Prism,
+        "Iris" => Attribute::Iris,
+        "Frost" => Attribute::Frost,
+        "Zoom" => Attribute::Zoom,
+        "Focus" => Attribute::Focus,
+        "Effects" => Attribute::Effect,
+        base if base.starts_with("ColorMacro") => Attribute::ColorMacro,
+        base if base.starts_with("Color") && base.contains("Wheel") => Attribute::ColorWheel,
+        base if base.starts_with("Gobo") && base.contains("Wheel") => Attribute::GoboWheelRotation,
+        base if base.starts_with("Gobo") && base.contains("Pos") => Attribute::GoboRotation,
+        base if base.starts_with("Prism") && base.contains("Pos") => Attribute::PrismRotation,
+        base if base.starts_with("Shutter") || base.starts_with("Strobe") => Attribute::Strobe,
+        base if base.starts_with("Iris") => Attribute::Iris,
+        base if base.starts_with("Frost") => Attribute::Frost,
+        base if base.starts_with("Zoom") => Attribute::Zoom,
+        base if base.starts_with("Focus") => Attribute::Focus,
+        base if base.starts_with("Effects") => Attribute::EffectSpeed,
+        base if base.starts_with("Dimmer")
+            || base.starts_with("Control")
+            || base.starts_with("Reset")
+            || base.starts_with("Lamp")
+            || base.starts_with("Function")
+            || base.starts_with("Display")
+            || base.starts_with("Fixture") =>
+        {
+            Attribute::Control
+        }
+        _ => return None,
+    };
+    Some(attribute)
+}
+
+/// Reads a CIE 1931 color written as "x,y,Y".
+fn parse_color(color: &str) -> Option<[f32; 3]> {
+    let mut parts = color.split(',').map(|part| part.trim().parse::<f32>());
+    let color = [
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    ];
+    Some(color)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn elements<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |child| child.has_tag_name(name))
+}
+
+/// Makes a name safe to use as a directory name.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
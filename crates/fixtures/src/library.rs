@@ -0,0 +1,92 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+
+use crate::profile::FixtureProfile;
+
+/// Identifies a profile in a library.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ProfileKey {
+    pub manufacturer: String,
+    pub model: String,
+}
+
+impl ProfileKey {
+    pub fn new(manufacturer: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            manufacturer: manufacturer.into(),
+            model: model.into(),
+        }
+    }
+}
+
+impl fmt::Display for ProfileKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.manufacturer.is_empty() {
+            f.write_str(&self.model)
+        } else {
+            write!(f, "{} {}", self.manufacturer, self.model)
+        }
+    }
+}
+
+/// The fixture profiles available to patch, ordered by manufacturer and
+/// model.
+#[derive(Clone, Debug, Default)]
+pub struct FixtureLibrary {
+    profiles: BTreeMap<ProfileKey, FixtureProfile>,
+}
+
+impl FixtureLibrary {
+    /// Adds `profile` after checking it, replacing any profile with the
+    /// same manufacturer and model.
+    pub fn insert(&mut self, profile: FixtureProfile) -> Result<()> {
+        profile.validate()?;
+        self.profiles.insert(
+            ProfileKey::new(&profile.manufacturer, &profile.model),
+            profile,
+        );
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &ProfileKey) -> Option<FixtureProfile> {
+        self.profiles.remove(key)
+    }
+
+    pub fn get(&self, key: &ProfileKey) -> Option<&FixtureProfile> {
+        self.profiles.get(key)
+    }
+
+    pub fn profiles(&self) -> impl Iterator<Item = &FixtureProfile> {
+        self.profiles.values()
+    }
+
+    pub fn manufacturers(&self) -> impl Iterator<Item = &str> {
+        let mut manufacturers = self
+            .profiles
+            .keys()
+            .map(|key| key.manufacturer.as_str())
+            .collect::<Vec<_>>();
+        manufacturers.dedup();
+        manufacturers.into_iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.profiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+    }
+}
+
+/// What came of importing a directory of fixture files.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub imported: Vec<ProfileKey>,
+    /// Parts of files that were left out or guessed at, prefixed with the
+    /// file they came from.
+    pub warnings: Vec<String>,
+    /// Files that could not be imported at all, with why.
+    pub failed: Vec<(PathBuf, String)>,
+}
@@ -0,0 +1,895 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    attribute::Attribute,
+    library::{FixtureLibrary, ImportReport, ProfileKey},
+    profile::{
+        Capability, Channel, DmxRange, DmxValue, FixtureProfile, Head, Mode, Physical,
+        PhysicalRange, Resolution, Unit, Wheel, WheelSlot, WheelSlotRef,
+    },
+};
+
+/// Where the Open Fixture Library lists the display names of the
+/// manufacturers its directories are named after.
+const MANUFACTURERS_FILE: &str = "manufacturers.json";
+const PIXEL_KEY: &str = "$pixelKey";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OflFixture {
+    name: String,
+    short_name: Option<String>,
+    /// Set on fixtures that were renamed or merged into another.
+    redirect_to: Option<Value>,
+    #[serde(default)]
+    available_channels: BTreeMap<String, OflChannel>,
+    #[serde(default)]
+    template_channels: BTreeMap<String, OflChannel>,
+    matrix: Option<OflMatrix>,
+    #[serde(default)]
+    wheels: BTreeMap<String, OflWheel>,
+    physical: Option<OflPhysical>,
+    #[serde(default)]
+    modes: Vec<OflMode>,
+}
+
+#[derive(Deserialize)]
+struct OflPhysical {
+    dimensions: Option<[f32; 3]>,
+    weight: Option<f32>,
+    power: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OflChannel {
+    #[serde(default)]
+    fine_channel_aliases: Vec<String>,
+    switch_channels: Option<Value>,
+    dmx_value_resolution: Option<String>,
+    default_value: Option<Value>,
+    highlight_value: Option<Value>,
+    capability: Option<Map<String, Value>>,
+    #[serde(default)]
+    capabilities: Vec<Map<String, Value>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OflMatrix {
+    pixel_count: Option<[usize; 3]>,
+    pixel_keys: Option<Vec<Vec<Vec<Option<String>>>>>,
+    #[serde(default)]
+    pixel_groups: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct OflWheel {
+    #[serde(default)]
+    slots: Vec<Map<String, Value>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OflMode {
+    name: String,
+    #[serde(default)]
+    channels: Vec<Value>,
+}
+
+/// A pixel of a matrix, with its position counted from 1.
+struct Pixel {
+    key: String,
+    position: [usize; 3],
+}
+
+/// A byte of a mode, resolved to the channel it belongs to.
+struct Slot<'a> {
+    /// The key of the coarse channel, with the pixel key filled in for
+    /// template channels.
+    key: String,
+    /// 0 for the coarse byte, 1 for fine and 2 for ultra.
+    byte: usize,
+    definition: &'a OflChannel,
+    pixel: Option<String>,
+}
+
+/// Imports every Open Fixture Library fixture file under `dir` into
+/// `library`. Fixture files are expected in a directory named after their
+/// manufacturer, as they are in the library itself.
+pub fn import_dir(dir: &Path, library: &mut FixtureLibrary) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    let mut manufacturers = Map::new();
+    let mut files = Vec::new();
+    collect_files(dir, &mut files, &mut manufacturers)
+        .with_context(|| format!("reading {}", dir.display()))?;
+    files.sort();
+
+    for path in files {
+        let key = path
+            .parent()
+            .and_then(|parent| parent.file_name())
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let manufacturer = manufacturers
+            .get(key)
+            .and_then(|manufacturer| manufacturer.get("name"))
+            .and_then(Value::as_str)
+            .map_or_else(|| title_case(key), str::to_string);
+
+        let result = fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| parse(&json, &manufacturer));
+        match result {
+            Ok(Some((profile, warnings))) => {
+                let key = ProfileKey::new(&profile.manufacturer, &profile.model);
+                match library.insert(profile) {
+                    Ok(()) => {
+                        report.imported.push(key);
+                        report.warnings.extend(
+                            warnings
+                                .into_iter()
+                                .map(|warning| format!("{}: {warning}", path.display())),
+                        );
+                    }
+                    Err(err) => report.failed.push((path, format!("{err:#}"))),
+                }
+            }
+            Ok(None) => {}
+            Err(err) => report.failed.push((path, format!("{err:#}"))),
+        }
+    }
+    Ok(report)
+}
+
+fn collect_files(
+    dir: &Path,
+    files: &mut Vec<PathBuf>,
+    manufacturers: &mut Map<String, Value>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files, manufacturers)?;
+        } else if path
+            .file_name()
+            .is_some_and(|name| name == MANUFACTURERS_FILE)
+        {
+            let json = fs::read_to_string(&path)?;
+            let list = serde_json::from_str::<Map<String, Value>>(&json)
+                .with_context(|| format!("parsing {}", path.display()))?;
+            manufacturers.extend(list);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Turns an Open Fixture Library fixture file into a profile, with
+/// warnings about anything that could not be carried over. Returns `None`
+/// for files that only redirect to another fixture.
+pub fn parse(json: &str, manufacturer: &str) -> Result<Option<(FixtureProfile, Vec<String>)>> {
+    let fixture = serde_json::from_str::<OflFixture>(json).context("parsing fixture")?;
+    if fixture.redirect_to.is_some() {
+        return Ok(None);
+    }
+    let mut warnings = Vec::new();
+    let pixels = fixture
+        .matrix
+        .as_ref()
+        .map(pixels)
+        .transpose()?
+        .unwrap_or_default();
+
+    let modes = fixture
+        .modes
+        .iter()
+        .map(|mode| parse_mode(&fixture, &pixels, mode, &mut warnings))
+        .collect::<Result<Vec<_>>>()?;
+    if modes.is_empty() {
+        return Err(anyhow!("fixture has no modes"));
+    }
+    warnings.dedup();
+
+    Ok(Some((
+        FixtureProfile {
+            manufacturer: manufacturer.to_string(),
+            model: fixture.name.clone(),
+            short_name: fixture.short_name.clone(),
+            modes,
+            wheels: fixture
+                .wheels
+                .iter()
+                .map(|(name, wheel)| Wheel {
+                    name: name.clone(),
+                    slots: wheel
+                        .slots
+                        .iter()
+                        .map(|slot| WheelSlot {
+                            name: slot_name(slot).unwrap_or_default(),
+                            color: None,
+                            image: None,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            physical: fixture.physical.as_ref().map(|physical| Physical {
+                dimensions: physical.dimensions,
+                weight: physical.weight,
+                power: physical.power,
+                emitters: Vec::new(),
+            }),
+        },
+        warnings,
+    )))
+}
+
+fn parse_mode(
+    fixture: &OflFixture,
+    pixels: &[Pixel],
+    mode: &OflMode,
+    warnings: &mut Vec<String>,
+) -> Result<Mode> {
+    let mut names = Vec::new();
+    for entry in &mode.channels {
+        match entry {
+            Value::String(name) => names.push(Some(name.clone())),
+            Value::Null => names.push(None),
+            Value::Object(insert) => names.extend(
+                matrix_channels(fixture, pixels, insert)
+                    .with_context(|| format!("mode \"{}\"", mode.name))?
+                    .into_iter()
+                    .map(Some),
+            ),
+            _ => return Err(anyhow!("mode \"{}\" has an invalid channel", mode.name)),
+        }
+    }
+
+    // Gather the bytes of each channel, which need not be next to each
+    // other, in the order the channels first appear.
+    let mut channels = Vec::<(Slot, Vec<Option<u16>>)>::new();
+    for (offset, name) in names.iter().enumerate() {
+        let Some(name) = name else {
+            continue;
+        };
+        let Some(slot) = resolve(fixture, pixels, name) else {
+            warnings.push(format!(
+                "mode \"{}\": unknown or switching channel \"{name}\" left out",
+                mode.name
+            ));
+            continue;
+        };
+        let index = match channels.iter().position(|(other, _)| other.key == slot.key) {
+            Some(index) => index,
+            None => {
+                channels.push((Slot { byte: 0, ..slot }, Vec::new()));
+                channels.len() - 1
+            }
+        };
+        let bytes = &mut channels[index].1;
+        if bytes.len() <= slot.byte {
+            bytes.resize(slot.byte + 1, None);
+        }
+        bytes[slot.byte] = Some(offset as u16);
+    }
+
+    let mut parsed = Vec::new();
+    let mut heads = Vec::<Head>::new();
+    for (slot, bytes) in channels {
+        let offsets = bytes.iter().map_while(|offset| *offset).collect::<Vec<_>>();
+        if offsets.is_empty() || offsets.len() < bytes.len() {
+            warnings.push(format!(
+                "mode \"{}\": channel \"{}\" is missing its coarse or fine byte and was left out",
+                mode.name, slot.key
+            ));
+            continue;
+        }
+        let channel = parse_channel(fixture, &slot, offsets, warnings);
+        if let Some(pixel) = slot.pixel {
+            match heads.iter_mut().find(|head| head.name == pixel) {
+                Some(head) => head.channels.push(parsed.len()),
+                None => heads.push(Head {
+                    name: pixel,
+                    channels: vec![parsed.len()],
+                }),
+            }
+        }
+        parsed.push(channel);
+    }
+
+    Ok(Mode {
+        name: mode.name.clone(),
+        channels: parsed,
+        heads,
+    })
+}
+
+/// Finds the channel `name` refers to, as a channel, a fine channel alias
+/// or either of those filled in from a template.
+fn resolve<'a>(fixture: &'a OflFixture, pixels: &[Pixel], name: &str) -> Option<Slot<'a>> {
+    for (key, definition) in &fixture.available_channels {
+        if let Some(byte) = byte_of(key, definition, name, None) {
+            return Some(Slot {
+                key: key.clone(),
+                byte,
+                definition,
+                pixel: None,
+            });
+        }
+    }
+
+    let groups = fixture
+        .matrix
+        .as_ref()
+        .map(|matrix| matrix.pixel_groups.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let keys = pixels.iter().map(|pixel| pixel.key.clone()).chain(groups);
+    for pixel in keys {
+        for (key, definition) in &fixture.template_channels {
+            if let Some(byte) = byte_of(key, definition, name, Some(&pixel)) {
+                return Some(Slot {
+                    key: key.replace(PIXEL_KEY, &pixel),
+                    byte,
+                    definition,
+                    pixel: Some(pixel),
+                });
+            }
+        }
+    }
+    None
+}
+
+fn byte_of(key: &str, definition: &OflChannel, name: &str, pixel: Option<&str>) -> Option<usize> {
+    let fill = |name: &str| match pixel {
+        Some(pixel) => name.replace(PIXEL_KEY, pixel),
+        None => name.to_string(),
+    };
+    if fill(key) == name {
+        return Some(0);
+    }
+    definition
+        .fine_channel_aliases
+        .iter()
+        .position(|alias| fill(alias) == name)
+        .map(|index| index + 1)
+}
+
+fn pixels(matrix: &OflMatrix) -> Result<Vec<Pixel>> {
+    let mut pixels = Vec::new();
+    if let Some([x_count, y_count, z_count]) = matrix.pixel_count {
+        for z in 1..=z_count {
+            for y in 1..=y_count {
+                for x in 1..=x_count {
+                    let key = match (y_count, z_count) {
+                        (1, 1) => format!("{x}"),
+                        (_, 1) => format!("({x}, {y})"),
+                        _ => format!("({x}, {y}, {z})"),
+                    };
+                    pixels.push(Pixel {
+                        key,
+                        position: [x, y, z],
+                    });
+                }
+            }
+        }
+    } else if let Some(keys) = &matrix.pixel_keys {
+        for (z, layer) in keys.iter().enumerate() {
+            for (y, row) in layer.iter().enumerate() {
+                for (x, key) in row.iter().enumerate() {
+                    if let Some(key) = key {
+                        pixels.push(Pixel {
+                            key: key.clone(),
+                            position: [x + 1, y + 1, z + 1],
+                        });
+                    }
+                }
+            }
+        }
+    } else {
+        return Err(anyhow!("matrix has neither pixelCount nor pixelKeys"));
+    }
+    Ok(pixels)
+}
+
+/// Expands a `matrixChannels` insert into the names of the channels it
+/// stands for.
+fn matrix_channels(
+    fixture: &OflFixture,
+    pixels: &[Pixel],
+    insert: &Map<String, Value>,
+) -> Result<Vec<String>> {
+    if insert.get("insert").and_then(Value::as_str) != Some("matrixChannels") {
+        return Err(anyhow!("unknown channel insert"));
+    }
+    let matrix = fixture
+        .matrix
+        .as_ref()
+        .ok_or_else(|| anyhow!("matrix channels in a fixture without a matrix"))?;
+    let templates = insert
+        .get("templateChannels")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("matrix channels without templateChannels"))?
+        .iter()
+        .map(|template| template.as_str().unwrap_or_default())
+        .collect::<Vec<_>>();
+
+    let keys = match insert.get("repeatFor") {
+        Some(Value::String(order)) if order == "eachPixelABC" => {
+            let mut keys = pixels
+                .iter()
+                .map(|pixel| pixel.key.clone())
+                .collect::<Vec<_>>();
+            keys.sort_by(|a, b| natural_cmp(a, b));
+            keys
+        }
+        Some(Value::String(order)) if order == "eachPixelGroup" => {
+            matrix.pixel_groups.keys().cloned().collect()
+        }
+        Some(Value::String(order)) if order.starts_with("eachPixel") => {
+            // The first axis named changes fastest.
+            let axes = order
+                .trim_start_matches("eachPixel")
+                .chars()
+                .map(|axis| match axis {
+                    'X' => Ok(0),
+                    'Y' => Ok(1),
+                    'Z' => Ok(2),
+                    _ => Err(anyhow!("unknown repeatFor \"{order}\"")),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let mut pixels = pixels.iter().collect::<Vec<_>>();
+            pixels.sort_by_key(|pixel| {
+                axes.iter()
+                    .rev()
+                    .map(|axis| pixel.position[*axis])
+                    .collect::<Vec<_>>()
+            });
+            pixels.into_iter().map(|pixel| pixel.key.clone()).collect()
+        }
+        Some(Value::Array(keys)) => keys
+            .iter()
+            .filter_map(|key| key.as_str().map(str::to_string))
+            .collect(),
+        _ => return Err(anyhow!("matrix channels without a valid repeatFor")),
+    };
+
+    let fill = |template: &str, key: &str| template.replace(PIXEL_KEY, key);
+    let per_channel = insert.get("channelOrder").and_then(Value::as_str) == Some("perChannel");
+    let names = if per_channel {
+        templates
+            .iter()
+            .flat_map(|template| keys.iter().map(|key| fill(template, key)))
+            .collect()
+    } else {
+        keys.iter()
+            .flat_map(|key| templates.iter().map(|template| fill(template, key)))
+            .collect()
+    };
+    Ok(names)
+}
+
+/// Orders strings with the numbers in them compared by value, so that
+/// pixel 2 comes before pixel 10.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    fn chunks(s: &str) -> Vec<(String, u64)> {
+        let mut chunks = Vec::new();
+        let mut text = String::new();
+        let mut digits = String::new();
+        for c in s.chars() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+            } else {
+                if !digits.is_empty() {
+                    chunks.push((std::mem::take(&mut text), digits.parse().unwrap_or(0)));
+                    digits.clear();
+                }
+                text.push(c);
+            }
+        }
+        chunks.push((text, digits.parse().unwrap_or(0)));
+        chunks
+    }
+    chunks(a).cmp(&chunks(b))
+}
+
+fn parse_channel(
+    fixture: &OflFixture,
+    slot: &Slot,
+    offsets: Vec<u16>,
+    warnings: &mut Vec<String>,
+) -> Channel {
+    let definition = slot.definition;
+    if definition.switch_channels.is_some() {
+        warnings.push(format!(
+            "channel \"{}\": switching channels are not supported",
+            slot.key
+        ));
+    }
+    let resolution = Resolution::from_bytes(offsets.len()).unwrap_or(Resolution::Coarse);
+    let value_resolution = definition
+        .dmx_value_resolution
+        .as_deref()
+        .and_then(|resolution| match resolution {
+            "8bit" => Some(Resolution::Coarse),
+            "16bit" => Some(Resolution::Fine),
+            "24bit" => Some(Resolution::Ultra),
+            _ => None,
+        })
+        .or_else(|| Resolution::from_bytes(definition.fine_channel_aliases.len() + 1))
+        .unwrap_or(Resolution::Coarse);
+    let value = |value: &Value| -> Option<DmxValue> {
+        match value {
+            Value::Number(number) => number
+                .as_u64()
+                .map(|value| value_resolution.convert(value as DmxValue, resolution)),
+            Value::String(percent) => {
+                let percent = percent.strip_suffix('%')?.parse::<f32>().ok()?;
+                Some((percent / 100. * resolution.max_value() as f32).round() as DmxValue)
+            }
+            _ => None,
+        }
+    };
+    // Ranges are written at the channel's value resolution, and reach to
+    // the end of their last step when carried over to a finer one.
+    let range = |from: u64, to: u64| -> Option<DmxRange> {
+        let max_value = value_resolution.max_value() as u64;
+        if from > to || to > max_value {
+            return None;
+        }
+        let (from, to) = (from as DmxValue, to as DmxValue);
+        let to = if resolution > value_resolution {
+            value_resolution.convert(to.checked_add(1)?, resolution) - 1
+        } else {
+            value_resolution.convert(to, resolution)
+        };
+        Some(DmxRange::new(
+            value_resolution.convert(from, resolution),
+            to,
+        ))
+    };
+
+    let mut attribute = None;
+    let mut physical = None;
+    let mut capabilities = Vec::new();
+    let single = definition.capability.iter();
+    for (index, capability) in single.chain(&definition.capabilities).enumerate() {
+        let kind = capability
+            .get("type")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let capability_attribute = match capability_attribute(fixture, &slot.key, capability) {
+            Ok(capability_attribute) => capability_attribute,
+            Err(()) => {
+                warnings.push(format!(
+                    "channel \"{}\": unsupported capability type {kind}",
+                    slot.key
+                ));
+                None
+            }
+        };
+        if attribute.is_none() {
+            attribute = capability_attribute;
+        }
+
+        let capability_physical = capability_physical(capability);
+        if definition.capability.is_some() {
+            physical = capability_physical;
+            break;
+        }
+        let Some([from, to]) = capability
+            .get("dmxRange")
+            .and_then(Value::as_array)
+            .and_then(|range| Some([range.first()?.as_u64()?, range.get(1)?.as_u64()?]))
+        else {
+            warnings.push(format!(
+                "channel \"{}\": capability {} has no DMX range and was left out",
+                slot.key,
+                index + 1
+            ));
+            continue;
+        };
+        let Some(range) = range(from, to) else {
+            warnings.push(format!(
+                "channel \"{}\": capability {} has an invalid DMX range {from}-{to} and was left out",
+                slot.key,
+                index + 1
+            ));
+            continue;
+        };
+        capabilities.push(Capability {
+            range,
+            name: capability_name(fixture, &slot.key, capability),
+            physical: capability_physical,
+            wheel_slot: wheel_slot(fixture, &slot.key, capability),
+        });
+    }
+
+    let max_value = resolution.max_value();
+    Channel {
+        name: slot.key.clone(),
+        attribute: attribute.unwrap_or(Attribute::Other),
+        offsets,
+        default: definition
+            .default_value
+            .as_ref()
+            .and_then(value)
+            .unwrap_or(0)
+            .min(max_value),
+        highlight: Some(
+            definition
+                .highlight_value
+                .as_ref()
+                .and_then(value)
+                .unwrap_or(max_value)
+                .min(max_value),
+        ),
+        physical,
+        capabilities,
+    }
+}
+
+/// The attribute a capability belongs to. Returns `Ok(None)` for
+/// capabilities that do nothing, and `Err` for types we do not support.
+fn capability_attribute(
+    fixture: &OflFixture,
+    channel: &str,
+    capability: &Map<String, Value>,
+) -> Result<Option<Attribute>, ()> {
+    let kind = capability
+        .get("type")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let attribute = match kind {
+        "NoFunction" => return Ok(None),
+        "Intensity" => Attribute::Intensity,
+        "ColorIntensity" => {
+            match capability
+                .get("color")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+            {
+                "Red" => Attribute::Red,
+                "Green" => Attribute::Green,
+                "Blue" => Attribute::Blue,
+                "White" => Attribute::White,
+                "Warm White" => Attribute::WarmWhite,
+                "Cold White" => Attribute::ColdWhite,
+                "Amber" => Attribute::Amber,
+                "Lime" => Attribute::Lime,
+                "UV" => Attribute::Uv,
+                "Cyan" => Attribute::Cyan,
+                "Magenta" => Attribute::Magenta,
+                "Yellow" => Attribute::Yellow,
+                _ => Attribute::Other,
+            }
+        }
+        "ShutterStrobe" => Attribute::Shutter,
+        "StrobeSpeed" | "StrobeDuration" => Attribute::Strobe,
+        "Pan" | "PanContinuous" => Attribute::Pan,
+        "Tilt" | "TiltContinuous" => Attribute::Tilt,
+        "PanTiltSpeed" => Attribute::PanTiltSpeed,
+        "ColorPreset" => Attribute::ColorMacro,
+        "ColorTemperature" => Attribute::Cto,
+        "WheelSlot" | "WheelShake" => wheel_attribute(fixture, channel, capability),
+        "WheelSlotRotation" => match wheel_attribute(fixture, channel, capability) {
+            Attribute::Prism => Attribute::PrismRotation,
+            _ => Attribute::GoboRotation,
+        },
+        "WheelRotation" => match wheel_attribute(fixture, channel, capability) {
+            Attribute::ColorWheel => Attribute::ColorWheel,
+            Attribute::Prism => Attribute::PrismRotation,
+            _ => Attribute::GoboWheelRotation,
+        },
+        "Prism" => Attribute::Prism,
+        "PrismRotation" => Attribute::PrismRotation,
+        "Effect" | "EffectParameter" | "SoundSensitivity" => Attribute::Effect,
+        "EffectSpeed" | "EffectDuration" => Attribute::EffectSpeed,
+        "BeamAngle" | "Zoom" => Attribute::Zoom,
+        "Focus" => Attribute::Focus,
+        "Iris" | "IrisEffect" => Attribute::Iris,
+        "Frost" | "FrostEffect" => Attribute::Frost,
+        "Maintenance" => Attribute::Maintenance,
+        "Speed" | "Time" | "Generic" => Attribute::Control,
+        _ => return Err(()),
+    };
+    Ok(Some(attribute))
+}
+
+/// Guesses what a wheel holds from its name, and failing that from its
+/// slots.
+fn wheel_attribute(
+    fixture: &OflFixture,
+    channel: &str,
+    capability: &Map<String, Value>,
+) -> Attribute {
+    let name = capability
+        .get("wheel")
+        .and_then(Value::as_str)
+        .unwrap_or(channel);
+    let lowercase = name.to_lowercase();
+    if lowercase.contains("gobo") {
+        return Attribute::Gobo;
+    }
+    if lowercase.contains("color") || lowercase.contains("colour") {
+        return Attribute::ColorWheel;
+    }
+    if lowercase.contains("prism") {
+        return Attribute::Prism;
+    }
+    let slot_types = fixture
+        .wheels
+        .get(name)
+        .map(|wheel| {
+            wheel
+                .slots
+                .iter()
+                .filter_map(|slot| slot.get("type").and_then(Value::as_str))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if slot_types.contains(&"Color") {
+        Attribute::ColorWheel
+    } else if slot_types.contains(&"Prism") {
+        Attribute::Prism
+    } else if slot_types.iter().any(|kind| kind.starts_with("Animation")) {
+        Attribute::Effect
+    } else {
+        Attribute::Gobo
+    }
+}
+
+fn capability_name(fixture: &OflFixture, channel: &str, capability: &Map<String, Value>) -> String {
+    if let Some(comment) = capability.get("comment").and_then(Value::as_str) {
+        return comment.to_string();
+    }
+    let kind = capability
+        .get("type")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    match kind {
+        "ColorIntensity" => {
+            if let Some(color) = capability.get("color").and_then(Value::as_str) {
+                return color.to_string();
+            }
+        }
+        "ShutterStrobe" => {
+            if let Some(effect) = capability.get("shutterEffect").and_then(Value::as_str) {
+                return effect.to_string();
+            }
+        }
+        "WheelSlot" => {
+            let slot = wheel_slot(fixture, channel, capability)
+                .and_then(|slot| fixture.wheels.get(&slot.wheel)?.slots.get(slot.slot));
+            if let Some(name) = slot.and_then(slot_name) {
+                return name;
+            }
+            if let Some(number) = capability.get("slotNumber").and_then(Value::as_f64) {
+                return format!("Slot {number}");
+            }
+        }
+        _ => {}
+    }
+    split_words(kind)
+}
+
+/// The wheel slot a capability selects. Capabilities between two slots
+/// select neither.
+fn wheel_slot(
+    fixture: &OflFixture,
+    channel: &str,
+    capability: &Map<String, Value>,
+) -> Option<WheelSlotRef> {
+    let wheel = capability
+        .get("wheel")
+        .and_then(Value::as_str)
+        .unwrap_or(channel);
+    let number = capability.get("slotNumber").and_then(Value::as_f64)?;
+    let slot = (number.fract() == 0.)
+        .then(|| (number as usize).checked_sub(1))
+        .flatten()?;
+    fixture
+        .wheels
+        .get(wheel)?
+        .slots
+        .get(slot)
+        .map(|_| WheelSlotRef {
+            wheel: wheel.to_string(),
+            slot,
+        })
+}
+
+fn slot_name(slot: &Map<String, Value>) -> Option<String> {
+    let name = slot.get("name").and_then(Value::as_str);
+    let kind = slot.get("type").and_then(Value::as_str);
+    name.or(kind).map(str::to_string)
+}
+
+/// Reads the first physical range a capability gives: an angle, a speed,
+/// a brightness and so on. Ranges described in words, like "slow" to
+/// "fast", are left out.
+fn capability_physical(capability: &Map<String, Value>) -> Option<PhysicalRange> {
+    const PROPERTIES: &[&str] = &[
+        "angle",
+        "speed",
+        "brightness",
+        "colorTemperature",
+        "duration",
+        "distance",
+        "soundSensitivity",
+        "parameter",
+    ];
+    PROPERTIES.iter().find_map(|property| {
+        let (from, to) = match capability.get(*property) {
+            Some(value) => (value, value),
+            None => (
+                capability.get(&format!("{property}Start"))?,
+                capability.get(&format!("{property}End"))?,
+            ),
+        };
+        let (from, from_unit) = physical_value(from.as_str()?)?;
+        let (to, to_unit) = physical_value(to.as_str()?)?;
+        (from_unit == to_unit).then_some(PhysicalRange {
+            from,
+            to,
+            unit: from_unit,
+        })
+    })
+}
+
+fn physical_value(value: &str) -> Option<(f32, Unit)> {
+    const UNITS: &[(&str, Unit, f32)] = &[
+        ("deg/s", Unit::DegreesPerSecond, 1.),
+        ("deg", Unit::Degrees, 1.),
+        ("rpm", Unit::DegreesPerSecond, 6.),
+        ("bpm", Unit::Hertz, 1. / 60.),
+        ("Hz", Unit::Hertz, 1.),
+        ("ms", Unit::Seconds, 0.001),
+        ("s", Unit::Seconds, 1.),
+        ("K", Unit::Kelvin, 1.),
+        ("%", Unit::Percent, 1.),
+        ("m", Unit::Meters, 1.),
+    ];
+    UNITS.iter().find_map(|(suffix, unit, scale)| {
+        let number = value.strip_suffix(suffix)?.parse::<f32>().ok()?;
+        Some((number * scale, *unit))
+    })
+}
+
+/// "WheelSlotRotation" becomes "Wheel Slot Rotation".
+fn split_words(name: &str) -> String {
+    let mut words = String::new();
+    for (index, c) in name.chars().enumerate() {
+        if index > 0 && c.is_uppercase() {
+            words.push(' ');
+        }
+        words.push(c);
+    }
+    words
+}
+
+/// "chauvet-dj" becomes "Chauvet Dj", for manufacturers missing from the
+/// manufacturers file.
+fn title_case(key: &str) -> String {
+    key.split('-')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,450 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, path::PathBuf};
+
+use crate::attribute::Attribute;
+
+/// A level at a channel's resolution: up to 255 for 8-bit channels, 65535
+/// for 16-bit channels and 16777215 for 24-bit channels.
+pub type DmxValue = u32;
+
+/// How many bytes a channel spans: coarse only, coarse and fine, or
+/// coarse, fine and ultra.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    Coarse,
+    Fine,
+    Ultra,
+}
+
+impl Resolution {
+    pub fn from_bytes(bytes: usize) -> Option<Self> {
+        match bytes {
+            1 => Some(Resolution::Coarse),
+            2 => Some(Resolution::Fine),
+            3 => Some(Resolution::Ultra),
+            _ => None,
+        }
+    }
+
+    pub fn bytes(self) -> usize {
+        match self {
+            Resolution::Coarse => 1,
+            Resolution::Fine => 2,
+            Resolution::Ultra => 3,
+        }
+    }
+
+    pub fn max_value(self) -> DmxValue {
+        (1 << (8 * self.bytes())) - 1
+    }
+
+    /// Converts a level at this resolution to `to`, the way fixture
+    /// libraries do: by adding or dropping the low bytes.
+    pub fn convert(self, value: DmxValue, to: Resolution) -> DmxValue {
+        let from_bits = 8 * self.bytes() as u32;
+        let to_bits = 8 * to.bytes() as u32;
+        if to_bits >= from_bits {
+            value << (to_bits - from_bits)
+        } else {
+            value >> (from_bits - to_bits)
+        }
+    }
+}
+
+/// An inclusive range of levels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DmxRange {
+    pub from: DmxValue,
+    pub to: DmxValue,
+}
+
+impl DmxRange {
+    pub fn new(from: DmxValue, to: DmxValue) -> Self {
+        Self { from, to }
+    }
+
+    pub fn contains(&self, value: DmxValue) -> bool {
+        (self.from..=self.to).contains(&value)
+    }
+
+    /// Where `value` falls in the range, from 0 at `from` to 1 at `to`.
+    pub fn fraction(&self, value: DmxValue) -> f32 {
+        if self.to == self.from {
+            return 0.;
+        }
+        (value.saturating_sub(self.from) as f32 / (self.to - self.from) as f32).min(1.)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Unit {
+    Percent,
+    Degrees,
+    DegreesPerSecond,
+    Hertz,
+    Seconds,
+    Kelvin,
+    Meters,
+}
+
+/// What the ends of a range of levels do physically, like a pan from -270
+/// to 270 degrees or a strobe from 1 to 25 Hz.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PhysicalRange {
+    pub from: f32,
+    pub to: f32,
+    pub unit: Unit,
+}
+
+impl PhysicalRange {
+    pub fn at(&self, fraction: f32) -> f32 {
+        self.from + (self.to - self.from) * fraction
+    }
+}
+
+/// What a channel does over part of its levels, like a single gobo slot
+/// or a strobe speed range.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Capability {
+    pub range: DmxRange,
+    pub name: String,
+    #[serde(default)]
+    pub physical: Option<PhysicalRange>,
+    /// The wheel slot the range selects.
+    #[serde(default)]
+    pub wheel_slot: Option<WheelSlotRef>,
+}
+
+/// Points at a slot of one of the profile's wheels.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WheelSlotRef {
+    pub wheel: String,
+    /// The index of the slot, counted from 0.
+    pub slot: usize,
+}
+
+/// A color, gobo, prism or animation wheel.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Wheel {
+    pub name: String,
+    pub slots: Vec<WheelSlot>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WheelSlot {
+    pub name: String,
+    /// The color of the slot as CIE 1931 x, y and Y.
+    #[serde(default)]
+    pub color: Option<[f32; 3]>,
+    /// Where a picture of the slot, like a gobo thumbnail, was saved.
+    #[serde(default)]
+    pub image: Option<PathBuf>,
+}
+
+/// A light source of a fixture, like the red LEDs of an RGB wash.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Emitter {
+    pub name: String,
+    /// The color of the emitter as CIE 1931 x, y and Y.
+    #[serde(default)]
+    pub color: Option<[f32; 3]>,
+    #[serde(default)]
+    pub dominant_wavelength: Option<f32>,
+}
+
+/// The physical properties of a fixture type.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Physical {
+    /// Width, height and depth in millimeters.
+    #[serde(default)]
+    pub dimensions: Option<[f32; 3]>,
+    /// In kilograms.
+    #[serde(default)]
+    pub weight: Option<f32>,
+    /// In watts.
+    #[serde(default)]
+    pub power: Option<f32>,
+    #[serde(default)]
+    pub emitters: Vec<Emitter>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Channel {
+    pub name: String,
+    pub attribute: Attribute,
+    /// The offsets of the channel's bytes from the fixture's start
+    /// address, coarse first.
+    pub offsets: Vec<u16>,
+    /// The level the channel rests at when nothing is programmed.
+    #[serde(default)]
+    pub default: DmxValue,
+    /// The level used to pick the fixture out while programming.
+    #[serde(default)]
+    pub highlight: Option<DmxValue>,
+    /// What the lowest and highest levels do physically.
+    #[serde(default)]
+    pub physical: Option<PhysicalRange>,
+    /// The ranges of levels with a distinct function, in order.
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+}
+
+impl Channel {
+    pub fn resolution(&self) -> Resolution {
+        Resolution::from_bytes(self.offsets.len()).unwrap_or(Resolution::Coarse)
+    }
+
+    pub fn max_value(&self) -> DmxValue {
+        self.resolution().max_value()
+    }
+
+    /// Returns the capability that `value` falls in.
+    pub fn capability(&self, value: DmxValue) -> Option<&Capability> {
+        self.capabilities
+            .iter()
+            .find(|capability| capability.range.contains(value))
+    }
+
+    /// What `value` does physically, from its capability's physical range
+    /// when it has one, and otherwise the channel's.
+    pub fn physical_value(&self, value: DmxValue) -> Option<(f32, Unit)> {
+        if let Some(capability) = self.capability(value) {
+            if let Some(physical) = capability.physical {
+                return Some((physical.at(capability.range.fraction(value)), physical.unit));
+            }
+        }
+        let physical = self.physical?;
+        let range = DmxRange::new(0, self.max_value());
+        Some((physical.at(range.fraction(value)), physical.unit))
+    }
+
+    /// Writes `value` into a fixture's slots, which start at its start
+    /// address. Offsets past the end of `slots` are skipped.
+    pub fn write(&self, value: DmxValue, slots: &mut [u8]) {
+        let value = value.min(self.max_value());
+        let bytes = self.offsets.len();
+        for (index, offset) in self.offsets.iter().enumerate() {
+            if let Some(slot) = slots.get_mut(*offset as usize) {
+                *slot = (value >> (8 * (bytes - 1 - index))) as u8;
+            }
+        }
+    }
+
+    /// Reads the channel's level back out of a fixture's slots.
+    pub fn read(&self, slots: &[u8]) -> DmxValue {
+        self.offsets.iter().fold(0, |value, offset| {
+            (value << 8) | slots.get(*offset as usize).copied().unwrap_or(0) as DmxValue
+        })
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if Resolution::from_bytes(self.offsets.len()).is_none() {
+            return Err(anyhow!(
+                "has {} bytes, expected between 1 and 3",
+                self.offsets.len()
+            ));
+        }
+        let max_value = self.max_value();
+        if self.default > max_value {
+            return Err(anyhow!(
+                "default {} is above the maximum {max_value}",
+                self.default
+            ));
+        }
+        if let Some(highlight) = self.highlight.filter(|highlight| *highlight > max_value) {
+            return Err(anyhow!(
+                "highlight {highlight} is above the maximum {max_value}"
+            ));
+        }
+        let mut previous = None::<DmxRange>;
+        for capability in &self.capabilities {
+            let range = capability.range;
+            if range.from > range.to || range.to > max_value {
+                return Err(anyhow!(
+                    "capability \"{}\" has an invalid range {}-{}",
+                    capability.name,
+                    range.from,
+                    range.to
+                ));
+            }
+            if previous.is_some_and(|previous| range.from <= previous.to) {
+                return Err(anyhow!(
+                    "capability \"{}\" overlaps or is out of order",
+                    capability.name
+                ));
+            }
+            previous = Some(range);
+        }
+        Ok(())
+    }
+}
+
+/// One of the cells of a matrix fixture, like a pixel of a batten, and the
+/// channels that control it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Head {
+    pub name: String,
+    /// Indices into the mode's channels.
+    pub channels: Vec<usize>,
+}
+
+/// One of the channel layouts a fixture can be switched to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Mode {
+    pub name: String,
+    pub channels: Vec<Channel>,
+    /// The cells of matrix fixtures. Channels that are in no head control
+    /// the fixture as a whole.
+    #[serde(default)]
+    pub heads: Vec<Head>,
+}
+
+impl Mode {
+    /// The number of DMX addresses the mode takes up.
+    pub fn footprint(&self) -> u16 {
+        self.channels
+            .iter()
+            .flat_map(|channel| &channel.offsets)
+            .max()
+            .map_or(0, |offset| offset + 1)
+    }
+
+    /// Returns the first channel that controls `attribute`.
+    pub fn channel(&self, attribute: Attribute) -> Option<&Channel> {
+        self.channels
+            .iter()
+            .find(|channel| channel.attribute == attribute)
+    }
+
+    /// Whether the mode has no intensity channel but has emitters that can
+    /// be scaled instead, like an RGB par without a master dimmer.
+    pub fn has_virtual_intensity(&self) -> bool {
+        self.channel(Attribute::Intensity).is_none()
+            && self
+                .channels
+                .iter()
+                .any(|channel| channel.attribute.is_additive_color())
+    }
+
+    /// The attributes the mode can be programmed with, including a virtual
+    /// intensity.
+    pub fn attributes(&self) -> BTreeSet<Attribute> {
+        let mut attributes = self
+            .channels
+            .iter()
+            .map(|channel| channel.attribute)
+            .collect::<BTreeSet<_>>();
+        if self.has_virtual_intensity() {
+            attributes.insert(Attribute::Intensity);
+        }
+        attributes
+    }
+
+    /// The fixture's slots with every channel at its default.
+    pub fn defaults(&self) -> Vec<u8> {
+        let mut slots = vec![0; self.footprint() as usize];
+        for channel in &self.channels {
+            channel.write(channel.default, &mut slots);
+        }
+        slots
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let mut offsets = BTreeSet::new();
+        for channel in &self.channels {
+            channel
+                .validate()
+                .with_context(|| format!("channel \"{}\"", channel.name))?;
+            for offset in &channel.offsets {
+                if !offsets.insert(*offset) {
+                    return Err(anyhow!(
+                        "channel \"{}\" uses offset {offset}, which is already taken",
+                        channel.name
+                    ));
+                }
+            }
+        }
+        for head in &self.heads {
+            if let Some(index) = head
+                .channels
+                .iter()
+                .find(|index| **index >= self.channels.len())
+            {
+                return Err(anyhow!(
+                    "head \"{}\" refers to channel {index}, which does not exist",
+                    head.name
+                ));
+            }
+        }
+        if self.footprint() > 512 {
+            return Err(anyhow!(
+                "footprint {} is larger than a universe",
+                self.footprint()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A fixture type: what it is and the modes it can be run in.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FixtureProfile {
+    pub manufacturer: String,
+    pub model: String,
+    #[serde(default)]
+    pub short_name: Option<String>,
+    pub modes: Vec<Mode>,
+    #[serde(default)]
+    pub wheels: Vec<Wheel>,
+    #[serde(default)]
+    pub physical: Option<Physical>,
+}
+
+impl FixtureProfile {
+    pub fn mode(&self, name: &str) -> Option<&Mode> {
+        self.modes.iter().find(|mode| mode.name == name)
+    }
+
+    pub fn wheel_slot(&self, slot: &WheelSlotRef) -> Option<&WheelSlot> {
+        self.wheels
+            .iter()
+            .find(|wheel| wheel.name == slot.wheel)?
+            .slots
+            .get(slot.slot)
+    }
+
+    /// The name to show where space is short.
+    pub fn display_name(&self) -> &str {
+        self.short_name.as_deref().unwrap_or(&self.model)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.manufacturer.is_empty() || self.model.is_empty() {
+            return Err(anyhow!("fixture profiles need a manufacturer and a model"));
+        }
+        if self.modes.is_empty() {
+            return Err(anyhow!("{} {} has no modes", self.manufacturer, self.model));
+        }
+        let mut names = BTreeSet::new();
+        for mode in &self.modes {
+            if !names.insert(mode.name.as_str()) {
+                return Err(anyhow!(
+                    "{} {} has more than one mode named \"{}\"",
+                    self.manufacturer,
+                    self.model,
+                    mode.name
+                ));
+            }
+            mode.validate().with_context(|| {
+                format!(
+                    "{} {}, mode \"{}\"",
+                    self.manufacturer, self.model, mode.name
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use fixtures::{gdtf, Attribute, DmxRange, Head, Resolution, Unit, WheelSlotRef};
+use std::{fs, path::Path};
+
+const ARCHIVE: &str = "tests/test_spot.gdtf";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+#[test]
+fn test_imports_archive() {
+    let images_dir = std::env::temp_dir().join(format!("tungsten-gdtf-{}", std::process::id()));
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(ARCHIVE);
+    let (profile, warnings) = gdtf::import_file(&path, &images_dir).unwrap();
+    assert_eq!(warnings, Vec::<String>::new());
+    profile.validate().unwrap();
+
+    assert_eq!(profile.manufacturer, "Tungsten");
+    assert_eq!(profile.model, "Test Spot");
+    assert_eq!(profile.display_name(), "TSpot");
+    let physical = profile.physical.as_ref().unwrap();
+    assert_eq!(physical.dimensions, Some([250., 500., 125.]));
+    assert_eq!(physical.weight, Some(7.5));
+    assert_eq!(physical.power, Some(150.));
+
+    let names = profile
+        .modes
+        .iter()
+        .map(|mode| mode.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Standard", "Cells"]);
+
+    let standard = profile.mode("Standard").unwrap();
+    assert_eq!(standard.footprint(), 6);
+    assert!(standard.heads.is_empty());
+    let dimmer = standard.channel(Attribute::Intensity).unwrap();
+    assert_eq!(dimmer.offsets, [0]);
+    assert_eq!(dimmer.highlight, Some(255));
+    let pan = standard.channel(Attribute::Pan).unwrap();
+    assert_eq!(pan.offsets, [1, 2]);
+    assert_eq!(pan.resolution(), Resolution::Fine);
+    assert_eq!(pan.default, 32768);
+    assert_eq!(pan.physical_value(0), Some((-270., Unit::Degrees)));
+    assert_eq!(standard.channel(Attribute::Tilt).unwrap().offsets, [3, 4]);
+
+    let gobo = standard.channel(Attribute::Gobo).unwrap();
+    assert_eq!(gobo.offsets, [5]);
+    let capabilities = gobo
+        .capabilities
+        .iter()
+        .map(|capability| {
+            (
+                capability.range,
+                capability.name.as_str(),
+                capability.wheel_slot.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        capabilities,
+        [
+            (
+                DmxRange::new(0, 127),
+                "Open",
+                Some(WheelSlotRef {
+                    wheel: "Gobo1".into(),
+                    slot: 0,
+                }),
+            ),
+            (
+                DmxRange::new(128, 255),
+                "Dots",
+                Some(WheelSlotRef {
+                    wheel: "Gobo1".into(),
+                    slot: 1,
+                }),
+            ),
+        ]
+    );
+
+    // Each geometry reference repeats the cell's channel from its own
+    // offset, as a head of its own.
+    let cells = profile.mode("Cells").unwrap();
+    assert_eq!(cells.footprint(), 8);
+    let red = cells
+        .channels
+        .iter()
+        .filter(|channel| channel.attribute == Attribute::Red)
+        .map(|channel| (channel.name.as_str(), channel.offsets.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        red,
+        [
+            ("Cell 1 ColorAdd_R", vec![6]),
+            ("Cell 2 ColorAdd_R", vec![7])
+        ]
+    );
+    assert_eq!(
+        cells.heads,
+        [
+            Head {
+                name: "Cell 1".into(),
+                channels: vec![4],
+            },
+            Head {
+                name: "Cell 2".into(),
+                channels: vec![5],
+            },
+        ]
+    );
+
+    // Only the slot with a picture has one, saved out of the archive.
+    let wheel = &profile.wheels[0];
+    assert_eq!(wheel.name, "Gobo1");
+    assert_eq!(wheel.slots[0].image, None);
+    let image = wheel.slots[1].image.as_ref().unwrap();
+    assert_eq!(
+        image,
+        &images_dir.join("Tungsten_Test_Spot").join("gobo_dots.png")
+    );
+    assert!(fs::read(image).unwrap().starts_with(PNG_SIGNATURE));
+
+    fs::remove_dir_all(&images_dir).unwrap();
+}
@@ -0,0 +1,134 @@
+use fixtures::{ofl, Attribute, DmxRange, FixtureLibrary, Head, ProfileKey, Resolution, Unit};
+use std::path::Path;
+
+const FIXTURES_DIR: &str = "tests/ofl";
+
+#[test]
+fn test_imports_dir() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(FIXTURES_DIR);
+    let mut library = FixtureLibrary::default();
+    let report = ofl::import_dir(&dir, &mut library).unwrap();
+    assert!(report.failed.is_empty(), "{:?}", report.failed);
+    let key = ProfileKey::new("Tungsten", "Test Bar");
+    assert_eq!(report.imported, std::slice::from_ref(&key));
+
+    // The capability reaching past the channel's levels is left out, and
+    // the unsupported one kept without an attribute.
+    let path = dir.join("tungsten").join("test-bar.json");
+    assert_eq!(
+        report.warnings,
+        [
+            format!(
+                "{}: channel \"Macro\": capability 2 has an invalid DMX range 128-4294967295 and was left out",
+                path.display()
+            ),
+            format!(
+                "{}: channel \"Fog\": unsupported capability type Fog",
+                path.display()
+            ),
+        ]
+    );
+
+    let profile = library.get(&key).unwrap();
+    assert_eq!(profile.display_name(), "TBar");
+    let physical = profile.physical.as_ref().unwrap();
+    assert_eq!(physical.dimensions, Some([1000., 120., 150.]));
+    assert_eq!(physical.weight, Some(4.5));
+    assert_eq!(physical.power, Some(90.));
+    let names = profile
+        .modes
+        .iter()
+        .map(|mode| mode.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Basic", "Extended"]);
+
+    // Without their fine bytes, channels are coarse.
+    let basic = profile.mode("Basic").unwrap();
+    assert_eq!(basic.footprint(), 3);
+    assert!(basic.heads.is_empty());
+    let pan = basic.channel(Attribute::Pan).unwrap();
+    assert_eq!(pan.offsets, [1]);
+    assert_eq!(pan.default, 128);
+
+    let extended = profile.mode("Extended").unwrap();
+    assert_eq!(extended.footprint(), 12);
+    let dimmer = extended.channel(Attribute::Intensity).unwrap();
+    assert_eq!(dimmer.offsets, [0, 1]);
+    assert_eq!(dimmer.resolution(), Resolution::Fine);
+    assert_eq!(dimmer.highlight, Some(65535));
+    let pan = extended.channel(Attribute::Pan).unwrap();
+    assert_eq!(pan.offsets, [2, 3]);
+    assert_eq!(pan.default, 32768);
+    assert_eq!(pan.physical_value(65535), Some((540., Unit::Degrees)));
+
+    // 8-bit ranges on a 16-bit channel reach to the end of their last
+    // step.
+    let macro_channel = extended.channel(Attribute::Effect).unwrap();
+    assert_eq!(macro_channel.offsets, [4, 5]);
+    let capabilities = macro_channel
+        .capabilities
+        .iter()
+        .map(|capability| (capability.range, capability.name.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(capabilities, [(DmxRange::new(0, 32767), "Off")]);
+
+    let gobo = extended.channel(Attribute::Gobo).unwrap();
+    assert_eq!(gobo.offsets, [6]);
+    let slots = gobo
+        .capabilities
+        .iter()
+        .map(|capability| {
+            (
+                capability.range,
+                capability.name.as_str(),
+                capability.wheel_slot.as_ref().map(|slot| slot.slot),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        slots,
+        [
+            (DmxRange::new(0, 127), "Open", Some(0)),
+            (DmxRange::new(128, 255), "Dots", Some(1)),
+        ]
+    );
+
+    let fog = &extended.channels[4];
+    assert_eq!(fog.name, "Fog");
+    assert_eq!(fog.attribute, Attribute::Other);
+    assert_eq!(fog.capabilities.len(), 1);
+
+    // Each pixel of the matrix is a head of its own.
+    let cells = extended.channels[5..]
+        .iter()
+        .map(|channel| {
+            (
+                channel.name.as_str(),
+                channel.attribute,
+                channel.offsets.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        cells,
+        [
+            ("Red 1", Attribute::Red, vec![8]),
+            ("Green 1", Attribute::Green, vec![9]),
+            ("Red 2", Attribute::Red, vec![10]),
+            ("Green 2", Attribute::Green, vec![11]),
+        ]
+    );
+    assert_eq!(
+        extended.heads,
+        [
+            Head {
+                name: "1".into(),
+                channels: vec![5, 6],
+            },
+            Head {
+                name: "2".into(),
+                channels: vec![7, 8],
+            },
+        ]
+    );
+}
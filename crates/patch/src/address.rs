@@ -0,0 +1,89 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// The number of addresses in a DMX universe.
+pub const UNIVERSE_SIZE: u16 = 512;
+
+/// A 1-based DMX address in one of Tungsten's internal universes, shown
+/// as `universe/address`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct DmxAddress {
+    pub universe: u16,
+    pub address: u16,
+}
+
+impl DmxAddress {
+    pub fn new(universe: u16, address: u16) -> Result<Self> {
+        if universe == 0 {
+            return Err(anyhow!("universes start at 1"));
+        }
+        if !(1..=UNIVERSE_SIZE).contains(&address) {
+            return Err(anyhow!("address {address} is not between 1 and 512"));
+        }
+        Ok(Self { universe, address })
+    }
+
+    /// Checks that `footprint` addresses starting here fit in the
+    /// universe.
+    pub fn check_fits(self, footprint: u16) -> Result<()> {
+        if self.last(footprint) > UNIVERSE_SIZE {
+            return Err(anyhow!(
+                "{footprint} addresses from {self} run past the end of the universe"
+            ));
+        }
+        Ok(())
+    }
+
+    /// The last address taken by `footprint` addresses starting here,
+    /// saturating rather than wrapping for footprints that cannot fit.
+    pub fn last(self, footprint: u16) -> u16 {
+        self.address.saturating_add(footprint.max(1) - 1)
+    }
+
+    /// The address a linear DMX number like 513 stands for, as consoles
+    /// that count dimmers across universes number them.
+    pub fn from_linear(number: u32) -> Result<Self> {
+        let index = number
+            .checked_sub(1)
+            .ok_or_else(|| anyhow!("DMX numbers start at 1"))?;
+        let universe = u16::try_from(index / u32::from(UNIVERSE_SIZE) + 1)
+            .map_err(|_| anyhow!("DMX number {number} is past the last universe"))?;
+        Self::new(universe, (index % u32::from(UNIVERSE_SIZE)) as u16 + 1)
+    }
+
+    /// The address counted on from the start of the first universe.
+    pub fn linear(self) -> u32 {
+        u32::from(self.universe - 1) * u32::from(UNIVERSE_SIZE) + u32::from(self.address)
+    }
+}
+
+impl fmt::Display for DmxAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.universe, self.address)
+    }
+}
+
+/// Parses `universe/address`, or a bare address in universe 1.
+impl FromStr for DmxAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (universe, address) = match s.split_once('/') {
+            Some((universe, address)) => (
+                universe
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("invalid universe \"{universe}\""))?,
+                address,
+            ),
+            None => (1, s),
+        };
+        let address = address
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid address \"{address}\""))?;
+        Self::new(universe, address)
+    }
+}
@@ -0,0 +1,429 @@
+use anyhow::{anyhow, Context, Result};
+use fixtures::FixtureLibrary;
+use std::{fmt, mem, str::FromStr};
+
+use crate::{
+    address::DmxAddress,
+    exchange::{FixtureRecord, PatchImport},
+    fixture::{FixtureId, PatchedFixture},
+    model::Patch,
+};
+
+/// What a column of a CSV patch holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsvField {
+    Id,
+    Name,
+    Manufacturer,
+    Model,
+    Mode,
+    Universe,
+    /// An address in the universe, `universe/address`, or a DMX number
+    /// counted across universes when there is no universe column.
+    Address,
+    Footprint,
+    Ignored,
+}
+
+impl CsvField {
+    pub fn title(self) -> &'static str {
+        match self {
+            CsvField::Id => "ID",
+            CsvField::Name => "Name",
+            CsvField::Manufacturer => "Manufacturer",
+            CsvField::Model => "Fixture type",
+            CsvField::Mode => "Mode",
+            CsvField::Universe => "Universe",
+            CsvField::Address => "Address",
+            CsvField::Footprint => "Footprint",
+            CsvField::Ignored => "-",
+        }
+    }
+
+    /// Recognizes the headers Tungsten exports and those common in other
+    /// consoles' and Lightwright's exports.
+    fn from_header(header: &str) -> Self {
+        match header.trim().to_lowercase().as_str() {
+            "id" | "fixture" | "fixture id" | "fid" | "channel" | "chan" | "ch" => CsvField::Id,
+            "name" | "label" | "purpose" => CsvField::Name,
+            "manufacturer" | "make" | "brand" => CsvField::Manufacturer,
+            "fixture type" | "type" | "model" | "instrument type" => CsvField::Model,
+            "mode" | "personality" | "dmx mode" => CsvField::Mode,
+            "universe" | "univ" => CsvField::Universe,
+            "address" | "dmx" | "dmx address" | "start address" | "dimmer" => CsvField::Address,
+            "footprint" | "channels" | "addresses" => CsvField::Footprint,
+            _ => CsvField::Ignored,
+        }
+    }
+}
+
+impl fmt::Display for CsvField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.title())
+    }
+}
+
+impl FromStr for CsvField {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        match CsvField::from_header(s) {
+            CsvField::Ignored if s != "-" && !s.is_empty() => {
+                Err(anyhow!("unknown column \"{s}\""))
+            }
+            field => Ok(field),
+        }
+    }
+}
+
+/// The field each column of a CSV patch holds, in order, shown like
+/// `ID, Name, -, Fixture type`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsvColumns(pub Vec<CsvField>);
+
+impl CsvColumns {
+    /// The columns patches are exported with when no others are given.
+    pub fn standard() -> Self {
+        Self(vec![
+            CsvField::Id,
+            CsvField::Name,
+            CsvField::Manufacturer,
+            CsvField::Model,
+            CsvField::Mode,
+            CsvField::Universe,
+            CsvField::Address,
+            CsvField::Footprint,
+        ])
+    }
+
+    /// Reads the columns from a header row, or returns `None` when it
+    /// names no fixture ID column.
+    pub fn from_header(header: &[String]) -> Option<Self> {
+        let columns = Self(
+            header
+                .iter()
+                .map(|title| CsvField::from_header(title))
+                .collect(),
+        );
+        columns.index(CsvField::Id).map(|_| columns)
+    }
+
+    fn index(&self, field: CsvField) -> Option<usize> {
+        self.0.iter().position(|column| *column == field)
+    }
+}
+
+impl fmt::Display for CsvColumns {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let titles = self.0.iter().map(|field| field.title()).collect::<Vec<_>>();
+        f.write_str(&titles.join(", "))
+    }
+}
+
+impl FromStr for CsvColumns {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let columns = Self(s.split(',').map(str::parse).collect::<Result<_>>()?);
+        if columns.index(CsvField::Id).is_none() {
+            return Err(anyhow!("one of the columns must be the ID"));
+        }
+        Ok(columns)
+    }
+}
+
+/// Reads a patch with a row per fixture. Without `columns`, the first row
+/// is taken as a header naming them. With them, a first row whose ID is
+/// not a number is skipped as a header.
+pub(crate) fn import(
+    text: &str,
+    columns: Option<&CsvColumns>,
+    library: &FixtureLibrary,
+) -> Result<PatchImport> {
+    let mut rows = parse(text)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, row)| row.iter().any(|field| !field.trim().is_empty()))
+        .peekable();
+    let header_columns;
+    let columns = match columns {
+        Some(columns) => {
+            rows.next_if(|(_, row)| {
+                field(row, columns, CsvField::Id)
+                    .parse::<FixtureId>()
+                    .is_err()
+            });
+            columns
+        }
+        None => {
+            let (_, header) = rows.next().ok_or_else(|| anyhow!("the file is empty"))?;
+            header_columns = CsvColumns::from_header(&header)
+                .ok_or_else(|| anyhow!("the header row has no fixture ID column"))?;
+            &header_columns
+        }
+    };
+
+    let mut import = PatchImport::default();
+    for (ix, row) in rows {
+        let location = format!("row {}", ix + 1);
+        match record(&row, columns) {
+            Ok(record) => import.push(&location, record, library),
+            Err(err) => import.warnings.push(format!("{location}: {err:#}")),
+        }
+    }
+    Ok(import)
+}
+
+fn record(row: &[String], columns: &CsvColumns) -> Result<FixtureRecord> {
+    let field = |column| field(row, columns, column);
+    let address = match (field(CsvField::Universe), field(CsvField::Address)) {
+        (_, "") => None,
+        (_, address) if address.contains('/') => Some(address.parse()?),
+        ("", number) => Some(DmxAddress::from_linear(
+            number
+                .parse()
+                .with_context(|| format!("invalid address \"{number}\""))?,
+        )?),
+        (universe, address) => Some(format!("{universe}/{address}").parse()?),
+    };
+    let footprint = match field(CsvField::Footprint) {
+        "" => None,
+        footprint => Some(
+            footprint
+                .parse()
+                .with_context(|| format!("invalid footprint \"{footprint}\""))?,
+        ),
+    };
+    Ok(FixtureRecord {
+        id: Some(field(CsvField::Id).parse()?),
+        name: field(CsvField::Name).to_string(),
+        manufacturer: field(CsvField::Manufacturer).to_string(),
+        model: field(CsvField::Model).to_string(),
+        mode: field(CsvField::Mode).to_string(),
+        address,
+        footprint,
+    })
+}
+
+fn field<'a>(row: &'a [String], columns: &CsvColumns, field: CsvField) -> &'a str {
+    columns
+        .index(field)
+        .and_then(|index| row.get(index))
+        .map_or("", |value| value.trim())
+}
+
+/// Writes a header row and a row per fixture. Splits and output modifiers
+/// are left out, since other consoles have no columns for them.
+pub(crate) fn export(patch: &Patch, columns: &CsvColumns, delimiter: char) -> String {
+    let mut text = String::new();
+    let header = columns
+        .0
+        .iter()
+        .map(|field| match field {
+            CsvField::Ignored => String::new(),
+            field => field.title().to_string(),
+        })
+        .collect::<Vec<_>>();
+    push_row(&mut text, &header, delimiter);
+    for fixture in patch.fixtures() {
+        let row = columns
+            .0
+            .iter()
+            .map(|field| export_field(fixture, *field))
+            .collect::<Vec<_>>();
+        push_row(&mut text, &row, delimiter);
+    }
+    text
+}
+
+fn export_field(fixture: &PatchedFixture, field: CsvField) -> String {
+    match field {
+        CsvField::Id => fixture.id.to_string(),
+        CsvField::Name => fixture.name.clone(),
+        CsvField::Manufacturer => fixture.profile.manufacturer.clone(),
+        CsvField::Model => fixture.profile.model.clone(),
+        CsvField::Mode => fixture.mode.clone(),
+        CsvField::Universe => fixture
+            .address
+            .map(|address| address.universe.to_string())
+            .unwrap_or_default(),
+        CsvField::Address => fixture
+            .address
+            .map(|address| address.address.to_string())
+            .unwrap_or_default(),
+        CsvField::Footprint => fixture.footprint.to_string(),
+        CsvField::Ignored => String::new(),
+    }
+}
+
+fn push_row(text: &mut String, row: &[String], delimiter: char) {
+    let fields = row
+        .iter()
+        .map(|field| {
+            if field.contains([delimiter, '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<_>>();
+    text.push_str(&fields.join(&delimiter.to_string()));
+    text.push_str("\r\n");
+}
+
+/// Splits CSV text into rows of fields, separated by whichever of commas,
+/// semicolons and tabs the first line has most of.
+fn parse(text: &str) -> Vec<Vec<String>> {
+    let text = text.trim_start_matches('\u{feff}');
+    let first_line = text.lines().next().unwrap_or_default();
+    let delimiter = ['\t', ';', ',']
+        .into_iter()
+        .max_by_key(|delimiter| first_line.matches(*delimiter).count())
+        .unwrap_or(',');
+
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c != '"' {
+                field.push(c);
+            } else if chars.next_if_eq(&'"').is_some() {
+                field.push('"');
+            } else {
+                quoted = false;
+            }
+        } else if c == '"' && field.is_empty() {
+            quoted = true;
+        } else if c == delimiter {
+            row.push(mem::take(&mut field));
+        } else if c == '\n' {
+            row.push(mem::take(&mut field));
+            rows.push(mem::take(&mut row));
+        } else if c != '\r' {
+            field.push(c);
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generic::generic_profiles, modifiers::OutputModifiers};
+    use fixtures::ProfileKey;
+
+    fn library() -> FixtureLibrary {
+        let mut library = FixtureLibrary::default();
+        for profile in generic_profiles() {
+            library.insert(profile).unwrap();
+        }
+        library
+    }
+
+    fn fixture(id: u32, model: &str, mode: &str, footprint: u16) -> PatchedFixture {
+        PatchedFixture {
+            id: FixtureId(id),
+            name: String::new(),
+            profile: ProfileKey::new("Generic", model),
+            mode: mode.into(),
+            footprint,
+            address: None,
+            split: None,
+            cells: Vec::new(),
+            modifiers: OutputModifiers::default(),
+            rdm_uid: None,
+        }
+    }
+
+    fn patch() -> Patch {
+        let mut dimmer = fixture(1, "Dimmer", "16 bit", 2);
+        dimmer.name = "Stage left, \"special\"".into();
+        dimmer.address = Some(DmxAddress::new(1, 1).unwrap());
+        let mut wash = fixture(101, "RGB", "3 channel", 3);
+        wash.name = "Cyc\nwash".into();
+        wash.address = Some(DmxAddress::new(3, 510).unwrap());
+        let spare = fixture(102, "RGBW", "4 channel", 4);
+        Patch::from_fixtures(vec![dimmer, wash, spare]).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let patch = patch();
+        for delimiter in [',', '\t'] {
+            let text = export(&patch, &CsvColumns::standard(), delimiter);
+            let import = import(&text, None, &library()).unwrap();
+            assert_eq!(import.warnings, Vec::<String>::new());
+            assert_eq!(import.fixtures, patch.fixtures());
+        }
+
+        // Addresses are written as DMX numbers without a universe column.
+        let columns = "ID, -, Fixture type, Mode, Address"
+            .parse::<CsvColumns>()
+            .unwrap();
+        let text = export(&patch, &columns, ';');
+        assert!(text.starts_with("ID;;Fixture type;Mode;Address\r\n"));
+        let import = import(&text, Some(&columns), &library()).unwrap();
+        let addresses = import
+            .fixtures
+            .iter()
+            .map(|fixture| fixture.address)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            addresses,
+            [
+                Some(DmxAddress::new(1, 1).unwrap()),
+                Some(DmxAddress::new(1, 510).unwrap()),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import() {
+        let text = "\u{feff}Chan;Purpose;Type;Dimmer;Notes\n\
+            5;Front;;513;x\n\
+            6;Back;RGB;2/7;\n\
+            seven;;;;\n\
+            8;;Mover;1;\n\
+            ;;;;\n\
+            5;Again;;1;\n";
+        let import = import(text, None, &library()).unwrap();
+        let fixtures = import
+            .fixtures
+            .iter()
+            .map(|fixture| {
+                (
+                    fixture.id.0,
+                    fixture.name.as_str(),
+                    fixture.profile.model.as_str(),
+                    fixture.address.map(|address| address.to_string()),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fixtures,
+            [
+                (5, "Front", "Dimmer", Some("2/1".to_string())),
+                (6, "Back", "RGB", Some("2/7".to_string())),
+                (8, "", "Mover", Some("1/1".to_string())),
+            ]
+        );
+        assert_eq!(import.warnings.len(), 3, "{:?}", import.warnings);
+        assert!(import.warnings[0].starts_with("row 4: "));
+        assert_eq!(import.warnings[1], "row 5: Mover is not in the library");
+        assert_eq!(
+            import.warnings[2],
+            "row 7: fixture 5 is listed more than once"
+        );
+
+        assert!(super::import("Name,Address\nFront,1\n", None, &library()).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+use anyhow::{anyhow, Context, Result};
+use fixtures::{FixtureLibrary, FixtureProfile, ProfileKey};
+use std::{fs, path::Path};
+
+use crate::{
+    address::DmxAddress,
+    csv::{self, CsvColumns},
+    fixture::{FixtureId, PatchedFixture},
+    generic::GENERIC_MANUFACTURER,
+    model::{Conflict, Patch},
+    modifiers::OutputModifiers,
+    usitt,
+};
+
+/// The file formats patches are exchanged with other consoles and
+/// paperwork in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatchFormat {
+    Csv,
+    UsittAscii,
+}
+
+impl PatchFormat {
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "csv" | "tsv" => Some(PatchFormat::Csv),
+            "asc" => Some(PatchFormat::UsittAscii),
+            _ => None,
+        }
+    }
+
+    /// Picks the format by extension, or by whether the file starts like
+    /// a USITT ASCII show file.
+    fn detect(path: &Path, text: &str) -> Self {
+        Self::from_extension(path).unwrap_or_else(|| {
+            let first_word = text
+                .lines()
+                .map(str::trim)
+                .find(|line| !line.is_empty() && !line.starts_with('!'))
+                .and_then(|line| line.split_whitespace().next())
+                .unwrap_or_default()
+                .to_lowercase();
+            match first_word.as_str() {
+                "ident" | "manufacturer" | "console" | "patch" | "$patch" => {
+                    PatchFormat::UsittAscii
+                }
+                _ => PatchFormat::Csv,
+            }
+        })
+    }
+}
+
+/// A fixture as another console's file describes it.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FixtureRecord {
+    pub(crate) id: Option<FixtureId>,
+    pub(crate) name: String,
+    pub(crate) manufacturer: String,
+    pub(crate) model: String,
+    pub(crate) mode: String,
+    pub(crate) address: Option<DmxAddress>,
+    pub(crate) footprint: Option<u16>,
+}
+
+/// Fixtures read from a file, to be looked over before they go into the
+/// patch.
+#[derive(Clone, Debug, Default)]
+pub struct PatchImport {
+    pub fixtures: Vec<PatchedFixture>,
+    /// Records that were left out or guessed at, prefixed with where they
+    /// are in the file.
+    pub warnings: Vec<String>,
+}
+
+/// What applying an import would do to the patch.
+#[derive(Clone, Debug, Default)]
+pub struct ImportPreview {
+    pub added: Vec<FixtureId>,
+    /// Patched fixtures that imported ones with the same ID replace.
+    pub replaced: Vec<FixtureId>,
+    /// The overlaps imported fixtures would have, with each other or with
+    /// the rest of the patch.
+    pub conflicts: Vec<Conflict>,
+    /// Why the import cannot be applied, if it cannot.
+    pub error: Option<String>,
+}
+
+impl PatchImport {
+    /// Reads a CSV or USITT ASCII file. CSV files are read with `columns`,
+    /// or with the columns their header row names.
+    pub fn read(
+        path: &Path,
+        columns: Option<&CsvColumns>,
+        library: &FixtureLibrary,
+    ) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        match PatchFormat::detect(path, &text) {
+            PatchFormat::Csv => csv::import(&text, columns, library),
+            PatchFormat::UsittAscii => usitt::import(&text, library),
+        }
+        .with_context(|| format!("importing {}", path.display()))
+    }
+
+    pub fn preview(&self, patch: &Patch) -> ImportPreview {
+        let mut preview = ImportPreview::default();
+        for fixture in &self.fixtures {
+            if patch.get(fixture.id).is_some() {
+                preview.replaced.push(fixture.id);
+            } else {
+                preview.added.push(fixture.id);
+            }
+        }
+        let mut updated = patch.clone();
+        match self.apply(&mut updated) {
+            Ok(()) => {
+                preview.conflicts = updated
+                    .conflicts()
+                    .into_iter()
+                    .filter(|conflict| {
+                        let (first, second) = conflict.fixtures;
+                        self.fixtures
+                            .iter()
+                            .any(|fixture| fixture.id == first || fixture.id == second)
+                    })
+                    .collect();
+            }
+            Err(err) => preview.error = Some(format!("{err:#}")),
+        }
+        preview
+    }
+
+    /// Adds the fixtures to the patch, replacing any with the same IDs.
+    pub fn apply(&self, patch: &mut Patch) -> Result<()> {
+        for fixture in &self.fixtures {
+            patch.remove(fixture.id);
+            patch.add(fixture.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Adds the fixture a record describes, or a warning about why it
+    /// cannot be. Records without a fixture type are patched as generic
+    /// dimmers.
+    pub(crate) fn push(&mut self, location: &str, record: FixtureRecord, library: &FixtureLibrary) {
+        let Some(id) = record.id else {
+            self.warnings.push(format!("{location}: no fixture ID"));
+            return;
+        };
+        if self.fixtures.iter().any(|fixture| fixture.id == id) {
+            self.warnings
+                .push(format!("{location}: fixture {id} is listed more than once"));
+            return;
+        }
+        let (manufacturer, model) = if record.manufacturer.is_empty() && record.model.is_empty() {
+            (GENERIC_MANUFACTURER, "Dimmer")
+        } else {
+            (record.manufacturer.as_str(), record.model.as_str())
+        };
+
+        let (profile, mode, footprint, cells) = match find_profile(library, manufacturer, model) {
+            Some(profile) => {
+                let key = ProfileKey::new(&profile.manufacturer, &profile.model);
+                let mode = if record.mode.is_empty() {
+                    profile.modes.first()
+                } else {
+                    profile
+                        .modes
+                        .iter()
+                        .find(|mode| mode.name.eq_ignore_ascii_case(&record.mode))
+                };
+                match mode {
+                    Some(mode) => (
+                        key,
+                        mode.name.clone(),
+                        mode.footprint(),
+                        mode.heads.iter().map(|head| head.name.clone()).collect(),
+                    ),
+                    None => {
+                        self.warnings
+                            .push(format!("{location}: {key} has no mode \"{}\"", record.mode));
+                        (key, record.mode, record.footprint.unwrap_or(1), Vec::new())
+                    }
+                }
+            }
+            None => {
+                let key = ProfileKey::new(manufacturer, model);
+                self.warnings
+                    .push(format!("{location}: {key} is not in the library"));
+                (key, record.mode, record.footprint.unwrap_or(1), Vec::new())
+            }
+        };
+
+        let fixture = PatchedFixture {
+            id,
+            name: record.name,
+            profile,
+            mode,
+            footprint,
+            address: record.address,
+            split: None,
+            cells,
+            modifiers: OutputModifiers::default(),
+            rdm_uid: None,
+        };
+        match fixture.validate() {
+            Ok(()) => self.fixtures.push(fixture),
+            Err(err) => self.warnings.push(format!("{location}: {err:#}")),
+        }
+    }
+}
+
+/// Finds a profile by manufacturer and model ignoring case, or by model
+/// alone when there is no manufacturer.
+fn find_profile<'a>(
+    library: &'a FixtureLibrary,
+    manufacturer: &str,
+    model: &str,
+) -> Option<&'a FixtureProfile> {
+    library.profiles().find(|profile| {
+        profile.model.eq_ignore_ascii_case(model)
+            && (manufacturer.is_empty() || profile.manufacturer.eq_ignore_ascii_case(manufacturer))
+    })
+}
+
+/// Writes the patch to `path` in the format its extension names, with
+/// `columns` or the standard ones for CSV.
+pub fn export(patch: &Patch, path: &Path, columns: Option<&CsvColumns>) -> Result<()> {
+    let format = PatchFormat::from_extension(path)
+        .ok_or_else(|| anyhow!("{} is not a .csv, .tsv or .asc file", path.display()))?;
+    let text = match format {
+        PatchFormat::Csv => {
+            let delimiter = if path.extension().is_some_and(|extension| extension == "tsv") {
+                '\t'
+            } else {
+                ','
+            };
+            csv::export(patch, columns.unwrap_or(&CsvColumns::standard()), delimiter)
+        }
+        PatchFormat::UsittAscii => usitt::export(patch),
+    };
+    fs::write(path, text).with_context(|| format!("writing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(id: u32, address: (u16, u16), footprint: u16) -> PatchedFixture {
+        PatchedFixture {
+            id: FixtureId(id),
+            name: String::new(),
+            profile: ProfileKey::new(GENERIC_MANUFACTURER, "Dimmer"),
+            mode: "8 bit".into(),
+            footprint,
+            address: Some(DmxAddress::new(address.0, address.1).unwrap()),
+            split: None,
+            cells: Vec::new(),
+            modifiers: OutputModifiers::default(),
+            rdm_uid: None,
+        }
+    }
+
+    #[test]
+    fn test_preview() {
+        let patch = Patch::from_fixtures(vec![
+            fixture(1, (1, 1), 4),
+            fixture(10, (3, 1), 4),
+            fixture(11, (3, 2), 4),
+        ])
+        .unwrap();
+        let import = PatchImport {
+            fixtures: vec![fixture(1, (1, 3), 4), fixture(2, (1, 5), 2)],
+            warnings: Vec::new(),
+        };
+
+        // Overlaps the import has nothing to do with are left out.
+        let preview = import.preview(&patch);
+        assert_eq!(preview.added, [FixtureId(2)]);
+        assert_eq!(preview.replaced, [FixtureId(1)]);
+        assert_eq!(
+            preview.conflicts,
+            [Conflict {
+                fixtures: (FixtureId(1), FixtureId(2)),
+                from: DmxAddress::new(1, 5).unwrap(),
+                to: DmxAddress::new(1, 6).unwrap(),
+            }]
+        );
+        assert_eq!(preview.error, None);
+
+        let import = PatchImport {
+            fixtures: vec![fixture(0, (1, 3), 4)],
+            warnings: Vec::new(),
+        };
+        let preview = import.preview(&patch);
+        assert_eq!(preview.error.as_deref(), Some("fixture IDs start at 1"));
+        assert!(preview.conflicts.is_empty());
+    }
+}
@@ -0,0 +1,303 @@
+use anyhow::{anyhow, Context, Result};
+use fixtures::ProfileKey;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+use crate::{
+    address::{DmxAddress, UNIVERSE_SIZE},
+    modifiers::OutputModifiers,
+};
+
+/// The number a fixture is called up by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FixtureId(pub u32);
+
+impl fmt::Display for FixtureId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for FixtureId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let id = s
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid fixture ID \"{s}\""))?;
+        if id == 0 {
+            return Err(anyhow!("fixture IDs start at 1"));
+        }
+        Ok(Self(id))
+    }
+}
+
+/// A fixture, or one cell of a multi-cell fixture, written `101` or
+/// `101.3` with cells counted from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct FixtureRef {
+    pub id: FixtureId,
+    #[serde(default)]
+    pub cell: Option<u16>,
+}
+
+impl FixtureRef {
+    pub fn fixture(id: FixtureId) -> Self {
+        Self { id, cell: None }
+    }
+
+    pub fn cell(id: FixtureId, cell: u16) -> Self {
+        Self {
+            id,
+            cell: Some(cell),
+        }
+    }
+}
+
+impl fmt::Display for FixtureRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.cell {
+            Some(cell) => write!(f, "{}.{cell}", self.id),
+            None => write!(f, "{}", self.id),
+        }
+    }
+}
+
+impl FromStr for FixtureRef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        match s.split_once('.') {
+            Some((id, cell)) => {
+                let cell = cell
+                    .parse()
+                    .with_context(|| format!("invalid cell \"{cell}\""))?;
+                if cell == 0 {
+                    return Err(anyhow!("cells start at 1"));
+                }
+                Ok(Self::cell(id.parse()?, cell))
+            }
+            None => Ok(Self::fixture(s.parse()?)),
+        }
+    }
+}
+
+/// Where the channels of a fixture that does not fit in one universe
+/// carry on, written `channel@universe/address`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Split {
+    /// The offset of the first channel sent to the second part.
+    pub offset: u16,
+    pub address: DmxAddress,
+}
+
+impl fmt::Display for Split {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.offset + 1, self.address)
+    }
+}
+
+impl FromStr for Split {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (channel, address) = s
+            .trim()
+            .split_once('@')
+            .ok_or_else(|| anyhow!("expected channel@universe/address, not \"{s}\""))?;
+        let channel: u16 = channel
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid channel \"{channel}\""))?;
+        if channel < 2 {
+            return Err(anyhow!("a split has to start after the first channel"));
+        }
+        Ok(Self {
+            offset: channel - 1,
+            address: address.parse()?,
+        })
+    }
+}
+
+/// A fixture in the patch.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PatchedFixture {
+    pub id: FixtureId,
+    #[serde(default)]
+    pub name: String,
+    /// The profile the fixture was patched with. It may not be in the
+    /// library, for example when it was patched from RDM.
+    pub profile: ProfileKey,
+    pub mode: String,
+    /// The number of addresses the mode takes up, kept so that the patch
+    /// can be checked without the profile.
+    pub footprint: u16,
+    /// Where the fixture's first channel is, or `None` while it is not
+    /// given an address.
+    #[serde(default)]
+    pub address: Option<DmxAddress>,
+    /// Where the rest of the channels go when they are split across two
+    /// universes.
+    #[serde(default)]
+    pub split: Option<Split>,
+    /// The names of the mode's cells, like the pixels of an LED bar, which
+    /// share the fixture's footprint.
+    #[serde(default)]
+    pub cells: Vec<String>,
+    /// Corrections made to the levels sent to the fixture.
+    #[serde(default)]
+    pub modifiers: OutputModifiers,
+    /// The RDM device the fixture was patched from.
+    #[serde(default)]
+    pub rdm_uid: Option<String>,
+}
+
+impl PatchedFixture {
+    /// The name to show, falling back to the fixture type.
+    pub fn label(&self) -> String {
+        if self.name.is_empty() {
+            self.profile.model.clone()
+        } else {
+            self.name.clone()
+        }
+    }
+
+    /// The number of channels patched at `address`, before any split.
+    pub fn first_part_len(&self) -> u16 {
+        self.split.map_or(self.footprint, |split| split.offset)
+    }
+
+    /// The first address and the number of channels of each part of the
+    /// fixture that has an address.
+    pub fn parts(&self) -> impl Iterator<Item = (DmxAddress, u16)> + '_ {
+        let first = self.address.map(|address| (address, self.first_part_len()));
+        let second = self
+            .address
+            .and(self.split)
+            .map(|split| (split.address, self.footprint.saturating_sub(split.offset)));
+        first.into_iter().chain(second)
+    }
+
+    /// The address of the channel at `offset` from the start.
+    pub fn address_of(&self, offset: u16) -> Option<DmxAddress> {
+        // A split only applies while the fixture has an address.
+        let address = self.address?;
+        if offset >= self.footprint {
+            return None;
+        }
+        let (start, offset) = match self.split {
+            Some(split) if offset >= split.offset => (split.address, offset - split.offset),
+            _ => (address, offset),
+        };
+        Some(DmxAddress {
+            universe: start.universe,
+            address: start.address + offset,
+        })
+    }
+
+    /// Whether the fixture takes up `address`.
+    pub fn covers(&self, address: DmxAddress) -> bool {
+        self.parts().any(|(start, len)| {
+            start.universe == address.universe
+                && (start.address..=start.last(len)).contains(&address.address)
+        })
+    }
+
+    /// The fixture and each of its cells.
+    pub fn refs(&self) -> impl Iterator<Item = FixtureRef> + '_ {
+        let id = self.id;
+        std::iter::once(FixtureRef::fixture(id))
+            .chain((1..=self.cells.len() as u16).map(move |cell| FixtureRef::cell(id, cell)))
+    }
+
+    /// The name of a cell, counted from 1.
+    pub fn cell_name(&self, cell: u16) -> Option<&str> {
+        let index = usize::from(cell).checked_sub(1)?;
+        self.cells.get(index).map(String::as_str)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.id.0 == 0 {
+            return Err(anyhow!("fixture IDs start at 1"));
+        }
+        if self.footprint > UNIVERSE_SIZE {
+            return Err(anyhow!(
+                "fixture {} has {} channels, more than a universe",
+                self.id,
+                self.footprint
+            ));
+        }
+        if let Some(split) = self.split {
+            if split.offset == 0 || split.offset >= self.footprint {
+                return Err(anyhow!(
+                    "fixture {} has {} channels and cannot be split at channel {}",
+                    self.id,
+                    self.footprint,
+                    split.offset + 1
+                ));
+            }
+        }
+        for (address, len) in self.parts() {
+            DmxAddress::new(address.universe, address.address)?;
+            address
+                .check_fits(len)
+                .with_context(|| format!("fixture {}", self.id))?;
+        }
+        let parts = self.parts().collect::<Vec<_>>();
+        if let [(first, first_len), (second, second_len)] = parts[..] {
+            if first.universe == second.universe
+                && first.address <= second.last(second_len)
+                && second.address <= first.last(first_len)
+            {
+                return Err(anyhow!("the parts of fixture {} overlap", self.id));
+            }
+        }
+        self.modifiers
+            .validate()
+            .with_context(|| format!("fixture {}", self.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_of() {
+        let mut fixture = PatchedFixture {
+            id: FixtureId(1),
+            name: String::new(),
+            profile: ProfileKey::new("Generic", "RGB"),
+            mode: "Default".into(),
+            footprint: 20,
+            address: Some(DmxAddress::new(1, 500).unwrap()),
+            split: Some(Split {
+                offset: 13,
+                address: DmxAddress::new(2, 1).unwrap(),
+            }),
+            cells: Vec::new(),
+            modifiers: OutputModifiers::default(),
+            rdm_uid: None,
+        };
+        assert_eq!(
+            fixture.address_of(0),
+            Some(DmxAddress::new(1, 500).unwrap())
+        );
+        assert_eq!(
+            fixture.address_of(12),
+            Some(DmxAddress::new(1, 512).unwrap())
+        );
+        assert_eq!(fixture.address_of(13), Some(DmxAddress::new(2, 1).unwrap()));
+        assert_eq!(fixture.address_of(19), Some(DmxAddress::new(2, 7).unwrap()));
+        assert_eq!(fixture.address_of(20), None);
+
+        // Unpatched, the split part is not sent either.
+        fixture.address = None;
+        assert_eq!(fixture.address_of(0), None);
+        assert_eq!(fixture.address_of(13), None);
+        assert!(!fixture.covers(DmxAddress::new(2, 1).unwrap()));
+    }
+}
@@ -0,0 +1,74 @@
+use fixtures::{Attribute, Channel, FixtureProfile, Mode};
+
+pub const GENERIC_MANUFACTURER: &str = "Generic";
+
+/// Profiles that are always in the library, so that dimmers and simple
+/// LED fixtures can be patched without importing anything.
+pub fn generic_profiles() -> Vec<FixtureProfile> {
+    vec![
+        profile(
+            "Dimmer",
+            vec![
+                mode("8 bit", vec![channel("Dimmer", Attribute::Intensity, &[0])]),
+                mode(
+                    "16 bit",
+                    vec![channel("Dimmer", Attribute::Intensity, &[0, 1])],
+                ),
+            ],
+        ),
+        profile(
+            "RGB",
+            vec![mode(
+                "3 channel",
+                vec![
+                    channel("Red", Attribute::Red, &[0]),
+                    channel("Green", Attribute::Green, &[1]),
+                    channel("Blue", Attribute::Blue, &[2]),
+                ],
+            )],
+        ),
+        profile(
+            "RGBW",
+            vec![mode(
+                "4 channel",
+                vec![
+                    channel("Red", Attribute::Red, &[0]),
+                    channel("Green", Attribute::Green, &[1]),
+                    channel("Blue", Attribute::Blue, &[2]),
+                    channel("White", Attribute::White, &[3]),
+                ],
+            )],
+        ),
+    ]
+}
+
+fn profile(model: &str, modes: Vec<Mode>) -> FixtureProfile {
+    FixtureProfile {
+        manufacturer: GENERIC_MANUFACTURER.into(),
+        model: model.into(),
+        short_name: None,
+        modes,
+        wheels: Vec::new(),
+        physical: None,
+    }
+}
+
+fn mode(name: &str, channels: Vec<Channel>) -> Mode {
+    Mode {
+        name: name.into(),
+        channels,
+        heads: Vec::new(),
+    }
+}
+
+fn channel(name: &str, attribute: Attribute, offsets: &[u16]) -> Channel {
+    Channel {
+        name: name.into(),
+        attribute,
+        offsets: offsets.to_vec(),
+        default: 0,
+        highlight: None,
+        physical: None,
+        capabilities: Vec::new(),
+    }
+}
@@ -0,0 +1,646 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::{
+    address::{DmxAddress, UNIVERSE_SIZE},
+    fixture::{FixtureId, FixtureRef, PatchedFixture, Split},
+    modifiers::OutputModifiers,
+};
+
+/// Two fixtures that take up some of the same addresses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Conflict {
+    /// The fixture with the lower start address, and the one that
+    /// overlaps it.
+    pub fixtures: (FixtureId, FixtureId),
+    /// The first and last of the shared addresses.
+    pub from: DmxAddress,
+    pub to: DmxAddress,
+}
+
+/// Every patched fixture, ordered by ID.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Patch {
+    fixtures: Vec<PatchedFixture>,
+}
+
+impl Patch {
+    /// Builds a patch from saved fixtures, checking each one.
+    pub fn from_fixtures(fixtures: Vec<PatchedFixture>) -> Result<Self> {
+        let mut patch = Self::default();
+        for fixture in fixtures {
+            patch.add(fixture)?;
+        }
+        Ok(patch)
+    }
+
+    pub fn fixtures(&self) -> &[PatchedFixture] {
+        &self.fixtures
+    }
+
+    pub fn get(&self, id: FixtureId) -> Option<&PatchedFixture> {
+        self.index(id).ok().map(|index| &self.fixtures[index])
+    }
+
+    pub fn len(&self) -> usize {
+        self.fixtures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fixtures.is_empty()
+    }
+
+    /// The ID after the highest one in use.
+    pub fn next_id(&self) -> FixtureId {
+        FixtureId(self.fixtures.last().map_or(1, |fixture| fixture.id.0 + 1))
+    }
+
+    /// Returns the fixture that takes up `address`.
+    pub fn fixture_at(&self, address: DmxAddress) -> Option<&PatchedFixture> {
+        self.fixtures.iter().find(|fixture| fixture.covers(address))
+    }
+
+    /// Where a fixture with `footprint` addresses would go after the last
+    /// patched one, starting at 1/1 in an empty patch.
+    pub fn next_address(&self, footprint: u16) -> Result<DmxAddress> {
+        let last = self
+            .taken()
+            .map(|(universe, _, last, _)| (universe, last))
+            .max();
+        let Some((universe, last)) = last else {
+            return DmxAddress::new(1, 1);
+        };
+        match DmxAddress::new(universe, last.saturating_add(1)) {
+            Ok(address) if address.check_fits(footprint).is_ok() => Ok(address),
+            _ => next_universe(DmxAddress::new(universe, last)?),
+        }
+    }
+
+    /// The first address at or after `from` where `footprint` addresses
+    /// are free, going on to later universes when needed.
+    pub fn find_free(&self, from: DmxAddress, footprint: u16) -> Result<DmxAddress> {
+        if footprint > UNIVERSE_SIZE {
+            return Err(anyhow!("footprint {footprint} is larger than a universe"));
+        }
+        let mut candidate = from;
+        loop {
+            if candidate.check_fits(footprint).is_err() {
+                candidate = next_universe(candidate)?;
+                continue;
+            }
+            let last = candidate.last(footprint);
+            let taken_until = self
+                .taken()
+                .filter(|(universe, start, end, _)| {
+                    *universe == candidate.universe && *start <= last && *end >= candidate.address
+                })
+                .map(|(_, _, end, _)| end)
+                .max();
+            match taken_until {
+                None => return Ok(candidate),
+                Some(UNIVERSE_SIZE) => candidate = next_universe(candidate)?,
+                Some(end) => candidate = DmxAddress::new(candidate.universe, end + 1)?,
+            }
+        }
+    }
+
+    /// Every pair of fixtures that take up some of the same addresses.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut taken = self.taken().collect::<Vec<_>>();
+        taken.sort();
+        let mut conflicts = Vec::new();
+        for (ix, (universe, _, end, id)) in taken.iter().enumerate() {
+            for (other_universe, other_start, other_end, other_id) in &taken[ix + 1..] {
+                if other_universe != universe || other_start > end {
+                    break;
+                }
+                conflicts.push(Conflict {
+                    fixtures: (*id, *other_id),
+                    from: DmxAddress {
+                        universe: *universe,
+                        address: *other_start,
+                    },
+                    to: DmxAddress {
+                        universe: *universe,
+                        address: (*end).min(*other_end),
+                    },
+                });
+            }
+        }
+        conflicts
+    }
+
+    /// Adds `count` copies of `template` with consecutive IDs from its own.
+    /// Each goes at the first free address from the one before it plus
+    /// `offset`, or plus its footprint when `offset` is 0. Nothing is added
+    /// if any of them cannot be.
+    pub fn insert_fixtures(
+        &mut self,
+        template: &PatchedFixture,
+        count: u32,
+        start: DmxAddress,
+        offset: u16,
+    ) -> Result<Vec<FixtureId>> {
+        let step = if offset == 0 {
+            template.footprint.max(1)
+        } else {
+            offset
+        };
+        let mut updated = self.clone();
+        let mut ids = Vec::new();
+        let mut next = start;
+        for ix in 0..count {
+            let id = template
+                .id
+                .0
+                .checked_add(ix)
+                .map(FixtureId)
+                .ok_or_else(|| anyhow!("ran out of fixture IDs"))?;
+            let address = updated.find_free(next, template.footprint)?;
+            updated.add(PatchedFixture {
+                id,
+                address: Some(address),
+                ..template.clone()
+            })?;
+            ids.push(id);
+            next = match address.address.checked_add(step) {
+                Some(following) if following <= UNIVERSE_SIZE => {
+                    DmxAddress::new(address.universe, following)?
+                }
+                _ => next_universe(address)?,
+            };
+        }
+        *self = updated;
+        Ok(ids)
+    }
+
+    pub fn add(&mut self, fixture: PatchedFixture) -> Result<()> {
+        fixture.validate()?;
+        match self.index(fixture.id) {
+            Ok(_) => Err(anyhow!("fixture {} is already patched", fixture.id)),
+            Err(index) => {
+                self.fixtures.insert(index, fixture);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: FixtureId) -> Option<PatchedFixture> {
+        let index = self.index(id).ok()?;
+        Some(self.fixtures.remove(index))
+    }
+
+    /// Gives a fixture a new ID.
+    pub fn renumber(&mut self, id: FixtureId, new_id: FixtureId) -> Result<()> {
+        if id == new_id {
+            return Ok(());
+        }
+        if self.get(new_id).is_some() {
+            return Err(anyhow!("fixture {new_id} is already patched"));
+        }
+        let mut fixture = self
+            .remove(id)
+            .ok_or_else(|| anyhow!("fixture {id} is not patched"))?;
+        fixture.id = new_id;
+        self.add(fixture)
+    }
+
+    pub fn set_name(&mut self, id: FixtureId, name: impl Into<String>) -> Result<()> {
+        self.fixture_mut(id)?.name = name.into();
+        Ok(())
+    }
+
+    /// Switches a fixture to another mode of its profile, with the names
+    /// of the mode's cells. Any split is removed, since the channels it
+    /// was made for may have moved.
+    pub fn set_mode(
+        &mut self,
+        id: FixtureId,
+        mode: impl Into<String>,
+        footprint: u16,
+        cells: Vec<String>,
+    ) -> Result<()> {
+        self.update_fixture(id, |fixture| {
+            fixture.mode = mode.into();
+            fixture.footprint = footprint;
+            fixture.split = None;
+            fixture.cells = cells;
+        })
+    }
+
+    /// Moves a fixture to `address`, or unpatches it. A split fixture
+    /// keeps the address of its second part.
+    pub fn readdress(&mut self, id: FixtureId, address: Option<DmxAddress>) -> Result<()> {
+        self.update_fixture(id, |fixture| fixture.address = address)
+    }
+
+    /// Splits a fixture's channels across two addresses, or joins them
+    /// back up.
+    pub fn set_split(&mut self, id: FixtureId, split: Option<Split>) -> Result<()> {
+        self.update_fixture(id, |fixture| fixture.split = split)
+    }
+
+    pub fn set_modifiers(&mut self, id: FixtureId, modifiers: OutputModifiers) -> Result<()> {
+        self.update_fixture(id, |fixture| fixture.modifiers = modifiers)
+    }
+
+    /// Looks up a list like `101, 103.2, 105-110, 101.1-101.12`, where a
+    /// range of whole fixtures takes every patched ID in it.
+    pub fn select(&self, text: &str) -> Result<Vec<FixtureRef>> {
+        let mut refs = Vec::new();
+        for item in text
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+        {
+            let (first, last) = match item.split_once('-') {
+                Some((first, last)) => (first.parse::<FixtureRef>()?, last.parse()?),
+                None => {
+                    let fixture_ref = item.parse()?;
+                    (fixture_ref, fixture_ref)
+                }
+            };
+            match (first.cell, last.cell) {
+                (None, None) => refs.extend(
+                    self.fixtures
+                        .iter()
+                        .filter(|fixture| (first.id..=last.id).contains(&fixture.id))
+                        .map(|fixture| FixtureRef::fixture(fixture.id)),
+                ),
+                (Some(first_cell), Some(last_cell)) if first.id == last.id => {
+                    let fixture = self
+                        .get(first.id)
+                        .ok_or_else(|| anyhow!("fixture {} is not patched", first.id))?;
+                    for cell in first_cell..=last_cell {
+                        if fixture.cell_name(cell).is_none() {
+                            return Err(anyhow!("fixture {} has no cell {cell}", first.id));
+                        }
+                        refs.push(FixtureRef::cell(first.id, cell));
+                    }
+                }
+                _ => return Err(anyhow!("cannot select from {first} to {last}")),
+            }
+        }
+        Ok(refs)
+    }
+
+    /// Moves fixtures one after another from `start`, in the order given,
+    /// going on to the next universe when one does not fit.
+    pub fn readdress_from(&mut self, ids: &[FixtureId], start: DmxAddress) -> Result<()> {
+        let mut next = start;
+        let mut addresses = Vec::with_capacity(ids.len());
+        for id in ids {
+            let footprint = self
+                .get(*id)
+                .ok_or_else(|| anyhow!("fixture {id} is not patched"))?
+                .first_part_len();
+            if next.check_fits(footprint).is_err() {
+                next = next_universe(next)?;
+                next.check_fits(footprint)?;
+            }
+            addresses.push(next);
+            let following = next.last(footprint).saturating_add(1);
+            next = if following > UNIVERSE_SIZE {
+                next_universe(next)?
+            } else {
+                DmxAddress::new(next.universe, following)?
+            };
+        }
+        for (id, address) in ids.iter().zip(addresses) {
+            self.readdress(*id, Some(address))?;
+        }
+        Ok(())
+    }
+
+    /// The universe, first and last address and ID of every part of every
+    /// fixture with an address.
+    fn taken(&self) -> impl Iterator<Item = (u16, u16, u16, FixtureId)> + '_ {
+        self.fixtures.iter().flat_map(|fixture| {
+            fixture.parts().map(|(address, len)| {
+                (
+                    address.universe,
+                    address.address,
+                    address.last(len),
+                    fixture.id,
+                )
+            })
+        })
+    }
+
+    /// Changes a fixture if it is still valid afterwards.
+    fn update_fixture(&mut self, id: FixtureId, f: impl FnOnce(&mut PatchedFixture)) -> Result<()> {
+        let fixture = self.fixture_mut(id)?;
+        let mut updated = fixture.clone();
+        f(&mut updated);
+        updated.validate()?;
+        *fixture = updated;
+        Ok(())
+    }
+
+    fn fixture_mut(&mut self, id: FixtureId) -> Result<&mut PatchedFixture> {
+        let index = self
+            .index(id)
+            .map_err(|_| anyhow!("fixture {id} is not patched"))?;
+        Ok(&mut self.fixtures[index])
+    }
+
+    fn index(&self, id: FixtureId) -> Result<usize, usize> {
+        self.fixtures
+            .binary_search_by_key(&id, |fixture| fixture.id)
+    }
+}
+
+fn next_universe(address: DmxAddress) -> Result<DmxAddress> {
+    let universe = address
+        .universe
+        .checked_add(1)
+        .ok_or_else(|| anyhow!("there are no free addresses after {address}"))?;
+    DmxAddress::new(universe, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::ProfileKey;
+
+    fn fixture(id: u32, address: Option<(u16, u16)>, footprint: u16) -> PatchedFixture {
+        PatchedFixture {
+            id: FixtureId(id),
+            name: String::new(),
+            profile: ProfileKey::new("Generic", "Dimmer"),
+            mode: "Default".into(),
+            footprint,
+            address: address.map(|(universe, address)| DmxAddress::new(universe, address).unwrap()),
+            split: None,
+            cells: Vec::new(),
+            modifiers: OutputModifiers::default(),
+            rdm_uid: None,
+        }
+    }
+
+    fn address(universe: u16, address: u16) -> DmxAddress {
+        DmxAddress::new(universe, address).unwrap()
+    }
+
+    #[test]
+    fn test_select() {
+        let mut bar = fixture(101, Some((1, 1)), 36);
+        bar.cells = (1..=12).map(|cell| format!("Cell {cell}")).collect();
+        let mut fixtures = vec![bar];
+        fixtures.extend([104, 105, 107, 110, 111].map(|id| fixture(id, None, 1)));
+        let patch = Patch::from_fixtures(fixtures).unwrap();
+
+        // Ranges of whole fixtures take the patched IDs in them.
+        assert_eq!(
+            patch.select("105-110").unwrap(),
+            [105, 107, 110].map(|id| FixtureRef::fixture(FixtureId(id)))
+        );
+        assert_eq!(
+            patch.select("101.1-101.12").unwrap(),
+            (1..=12)
+                .map(|cell| FixtureRef::cell(FixtureId(101), cell))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            patch.select(" 104, 101.3 ,").unwrap(),
+            [
+                FixtureRef::fixture(FixtureId(104)),
+                FixtureRef::cell(FixtureId(101), 3)
+            ]
+        );
+
+        assert!(patch.select("101.1-101.13").is_err());
+        assert!(patch.select("104.1").is_err());
+        assert!(patch.select("101.1-102.1").is_err());
+        assert!(patch.select("101-101.2").is_err());
+        assert!(patch.select("abc").is_err());
+    }
+
+    #[test]
+    fn test_renumber() {
+        let mut patch = Patch::from_fixtures(vec![
+            fixture(1, Some((1, 1)), 4),
+            fixture(2, Some((1, 5)), 4),
+        ])
+        .unwrap();
+        assert!(patch.renumber(FixtureId(1), FixtureId(2)).is_err());
+        assert!(patch.renumber(FixtureId(3), FixtureId(4)).is_err());
+        assert_eq!(
+            patch
+                .fixtures()
+                .iter()
+                .map(|fixture| fixture.id)
+                .collect::<Vec<_>>(),
+            [FixtureId(1), FixtureId(2)]
+        );
+
+        // The patch stays ordered by ID.
+        patch.renumber(FixtureId(1), FixtureId(10)).unwrap();
+        assert_eq!(
+            patch
+                .fixtures()
+                .iter()
+                .map(|fixture| fixture.id)
+                .collect::<Vec<_>>(),
+            [FixtureId(2), FixtureId(10)]
+        );
+        assert_eq!(
+            patch.get(FixtureId(10)).unwrap().address,
+            Some(address(1, 1))
+        );
+    }
+
+    #[test]
+    fn test_readdress_from() {
+        let mut patch = Patch::from_fixtures(vec![
+            fixture(1, None, 200),
+            fixture(2, None, 200),
+            fixture(3, None, 200),
+            fixture(4, Some((5, 1)), 312),
+        ])
+        .unwrap();
+
+        // The third fixture no longer fits in universe 1, and the fourth
+        // fills universe 2 up to its last address.
+        let ids = [1, 2, 3, 4].map(FixtureId);
+        patch.readdress_from(&ids, address(1, 1)).unwrap();
+        let addresses = ids
+            .iter()
+            .map(|id| patch.get(*id).unwrap().address.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            addresses,
+            [
+                address(1, 1),
+                address(1, 201),
+                address(2, 1),
+                address(2, 201)
+            ]
+        );
+        assert_eq!(patch.next_address(1).unwrap(), address(3, 1));
+
+        // Nothing moves when one of the fixtures is not patched.
+        assert!(patch
+            .readdress_from(&[FixtureId(1), FixtureId(5)], address(3, 1))
+            .is_err());
+        assert_eq!(
+            patch.get(FixtureId(1)).unwrap().address,
+            Some(address(1, 1))
+        );
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let patch = Patch::from_fixtures(vec![
+            fixture(1, Some((1, 1)), 10),
+            fixture(2, Some((1, 11)), 10),
+            fixture(3, Some((1, 15)), 4),
+            fixture(4, Some((2, 1)), 10),
+            fixture(5, None, 10),
+        ])
+        .unwrap();
+        // Fixtures that only meet do not conflict.
+        assert_eq!(
+            patch.conflicts(),
+            [Conflict {
+                fixtures: (FixtureId(2), FixtureId(3)),
+                from: address(1, 15),
+                to: address(1, 18),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_conflicts_of_split_parts() {
+        let mut split = fixture(2, Some((1, 500)), 20);
+        split.split = Some(Split {
+            offset: 13,
+            address: address(2, 5),
+        });
+        let mut patch = Patch::from_fixtures(vec![fixture(1, Some((2, 1)), 10), split]).unwrap();
+        assert_eq!(
+            patch.conflicts(),
+            [Conflict {
+                fixtures: (FixtureId(1), FixtureId(2)),
+                from: address(2, 5),
+                to: address(2, 10),
+            }]
+        );
+
+        patch
+            .readdress(FixtureId(1), Some(address(1, 490)))
+            .unwrap();
+        assert_eq!(patch.conflicts(), []);
+        patch
+            .readdress(FixtureId(1), Some(address(1, 495)))
+            .unwrap();
+        assert_eq!(
+            patch.conflicts(),
+            [Conflict {
+                fixtures: (FixtureId(1), FixtureId(2)),
+                from: address(1, 500),
+                to: address(1, 504),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_find_free() {
+        let patch = Patch::from_fixtures(vec![
+            fixture(1, Some((1, 1)), 10),
+            fixture(2, Some((1, 15)), 10),
+            fixture(3, Some((1, 500)), 13),
+        ])
+        .unwrap();
+        assert_eq!(patch.find_free(address(1, 1), 4).unwrap(), address(1, 11));
+        assert_eq!(patch.find_free(address(1, 1), 5).unwrap(), address(1, 25));
+        assert_eq!(
+            patch.find_free(address(1, 490), 10).unwrap(),
+            address(1, 490)
+        );
+
+        // Fixtures that run into the end of the universe, or that do not
+        // fit before it, go on to the next one.
+        assert_eq!(patch.find_free(address(1, 495), 10).unwrap(), address(2, 1));
+        assert_eq!(patch.find_free(address(1, 510), 5).unwrap(), address(2, 1));
+        assert_eq!(patch.find_free(address(1, 25), 512).unwrap(), address(2, 1));
+        assert_eq!(patch.find_free(address(2, 1), 512).unwrap(), address(2, 1));
+
+        assert!(patch.find_free(address(1, 1), 513).is_err());
+        assert!(patch.find_free(address(u16::MAX, 510), 5).is_err());
+    }
+
+    #[test]
+    fn test_insert_fixtures() {
+        let mut patch = Patch::default();
+        let template = fixture(101, None, 36);
+        let ids = patch
+            .insert_fixtures(&template, 12, address(1, 101), 40)
+            .unwrap();
+        assert_eq!(ids, (101..=112).map(FixtureId).collect::<Vec<_>>());
+
+        // The eleventh would run past 1/512.
+        let mut expected = (0..10)
+            .map(|ix| address(1, 101 + 40 * ix))
+            .collect::<Vec<_>>();
+        expected.extend([address(2, 1), address(2, 41)]);
+        let addresses = patch
+            .fixtures()
+            .iter()
+            .map(|fixture| fixture.address.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(addresses, expected);
+        assert_eq!(patch.conflicts(), []);
+    }
+
+    #[test]
+    fn test_insert_fixtures_at_end_of_universe() {
+        // Without an offset, each fixture follows the one before it, and
+        // the sixteenth ends on the last address.
+        let mut patch = Patch::default();
+        patch
+            .insert_fixtures(&fixture(1, None, 32), 17, address(1, 1), 0)
+            .unwrap();
+        let addresses = patch
+            .fixtures()
+            .iter()
+            .map(|fixture| fixture.address.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(addresses[15], address(1, 481));
+        assert_eq!(addresses[16], address(2, 1));
+
+        // Taken addresses are skipped.
+        let mut patch = Patch::from_fixtures(vec![fixture(200, Some((1, 141)), 10)]).unwrap();
+        patch
+            .insert_fixtures(&fixture(1, None, 36), 3, address(1, 101), 40)
+            .unwrap();
+        let addresses = [1, 2, 3].map(|id| patch.get(FixtureId(id)).unwrap().address.unwrap());
+        assert_eq!(
+            addresses,
+            [address(1, 101), address(1, 151), address(1, 191)]
+        );
+    }
+
+    #[test]
+    fn test_insert_fixtures_rolls_back() {
+        let mut patch = Patch::from_fixtures(vec![fixture(3, Some((3, 1)), 10)]).unwrap();
+        let before = patch.clone();
+
+        // The third ID is taken.
+        assert!(patch
+            .insert_fixtures(&fixture(1, None, 10), 4, address(1, 1), 0)
+            .is_err());
+        assert_eq!(patch, before);
+
+        // The second fixture would go past the last universe.
+        assert!(patch
+            .insert_fixtures(&fixture(10, None, 300), 2, address(u16::MAX, 1), 0)
+            .is_err());
+        assert_eq!(patch, before);
+
+        assert!(patch
+            .insert_fixtures(&fixture(u32::MAX, None, 1), 2, address(1, 1), 0)
+            .is_err());
+        assert_eq!(patch, before);
+    }
+}
@@ -0,0 +1,518 @@
+use anyhow::{anyhow, Context, Result};
+use fixtures::Attribute;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+/// How a level from 0 to 1 is shaped before it is sent.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Curve {
+    #[default]
+    Linear,
+    /// The level squared, so that the bottom of the fader has finer
+    /// control.
+    SquareLaw,
+    /// An S-curve, which eases in at the bottom and out at the top.
+    SShaped,
+    /// Levels spread evenly across the input, joined by straight lines.
+    Table(Vec<f32>),
+}
+
+impl Curve {
+    pub fn apply(&self, level: f32) -> f32 {
+        match self {
+            Curve::Linear => level,
+            Curve::SquareLaw => level * level,
+            Curve::SShaped => level * level * (3. - 2. * level),
+            Curve::Table(points) => match points.len() {
+                0 => level,
+                1 => points[0],
+                len => {
+                    let position = level * (len - 1) as f32;
+                    let ix = (position.floor() as usize).min(len - 2);
+                    let t = position - ix as f32;
+                    points[ix] + (points[ix + 1] - points[ix]) * t
+                }
+            },
+        }
+    }
+}
+
+impl fmt::Display for Curve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Curve::Linear => f.write_str("linear"),
+            Curve::SquareLaw => f.write_str("square law"),
+            Curve::SShaped => f.write_str("s-curve"),
+            Curve::Table(points) => {
+                f.write_str("table")?;
+                for point in points {
+                    write!(f, " {}", percent(*point))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parses `linear`, `square law`, `s-curve` or `table` followed by
+/// percentages, like `table 0 20 60 100`.
+impl FromStr for Curve {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_lowercase();
+        match s.as_str() {
+            "linear" => Ok(Curve::Linear),
+            "square law" | "square" => Ok(Curve::SquareLaw),
+            "s-curve" | "s curve" => Ok(Curve::SShaped),
+            _ => match s.strip_prefix("table") {
+                Some(points) => Ok(Curve::Table(
+                    points
+                        .split_whitespace()
+                        .map(parse_percent)
+                        .collect::<Result<_>>()?,
+                )),
+                None => Err(anyhow!("unknown curve \"{s}\"")),
+            },
+        }
+    }
+}
+
+/// The curve and limits an attribute's levels are sent with.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttributeModifier {
+    pub curve: Curve,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for AttributeModifier {
+    fn default() -> Self {
+        Self {
+            curve: Curve::Linear,
+            min: 0.,
+            max: 1.,
+        }
+    }
+}
+
+impl AttributeModifier {
+    pub fn apply(&self, level: f32) -> f32 {
+        self.curve.apply(level).clamp(self.min, self.max)
+    }
+
+    fn is_limited(&self) -> bool {
+        self.min > 0. || self.max < 1.
+    }
+}
+
+/// Corrections made to a fixture's levels on the way out, for fixtures
+/// that are hung differently from how they are programmed. Pan and tilt
+/// are inverted and offset on the fixture's own channels, after any swap.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputModifiers {
+    pub invert_pan: bool,
+    pub invert_tilt: bool,
+    /// Drives the pan channel from the programmed tilt, and the other way
+    /// round.
+    pub swap_pan_tilt: bool,
+    /// Added to the levels, as a fraction of the range.
+    pub pan_offset: f32,
+    pub tilt_offset: f32,
+    pub attributes: BTreeMap<Attribute, AttributeModifier>,
+}
+
+impl OutputModifiers {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The attribute whose programmed level drives a channel.
+    pub fn source(&self, attribute: Attribute) -> Attribute {
+        match attribute {
+            Attribute::Pan if self.swap_pan_tilt => Attribute::Tilt,
+            Attribute::Tilt if self.swap_pan_tilt => Attribute::Pan,
+            attribute => attribute,
+        }
+    }
+
+    /// The level sent to a channel of `attribute` for `level`.
+    pub fn apply(&self, attribute: Attribute, level: f32) -> f32 {
+        let level = match attribute {
+            Attribute::Pan => invert(level, self.invert_pan) + self.pan_offset,
+            Attribute::Tilt => invert(level, self.invert_tilt) + self.tilt_offset,
+            _ => level,
+        }
+        .clamp(0., 1.);
+        match self.attributes.get(&attribute) {
+            Some(modifier) => modifier.apply(level),
+            None => level,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (attribute, offset) in [
+            (Attribute::Pan, self.pan_offset),
+            (Attribute::Tilt, self.tilt_offset),
+        ] {
+            if !(-1. ..=1.).contains(&offset) {
+                return Err(anyhow!(
+                    "the {attribute} offset is not between -100% and 100%"
+                ));
+            }
+        }
+        for (attribute, modifier) in &self.attributes {
+            if !(0. ..=1.).contains(&modifier.min)
+                || !(0. ..=1.).contains(&modifier.max)
+                || modifier.min > modifier.max
+            {
+                return Err(anyhow!(
+                    "the {attribute} limits are not between 0% and 100%"
+                ));
+            }
+            if let Curve::Table(points) = &modifier.curve {
+                if points.len() < 2 {
+                    return Err(anyhow!("the {attribute} table needs at least two levels"));
+                }
+                if points.iter().any(|point| !(0. ..=1.).contains(point)) {
+                    return Err(anyhow!(
+                        "the {attribute} table has levels outside 0% to 100%"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn invert(level: f32, inverted: bool) -> f32 {
+    if inverted {
+        1. - level
+    } else {
+        level
+    }
+}
+
+/// Lists the modifiers like `Pan inverted, Pan/Tilt swapped, Tilt +5%,
+/// Intensity square law 0-80%`.
+impl fmt::Display for OutputModifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut items = Vec::new();
+        if self.invert_pan {
+            items.push("Pan inverted".to_string());
+        }
+        if self.invert_tilt {
+            items.push("Tilt inverted".to_string());
+        }
+        if self.swap_pan_tilt {
+            items.push("Pan/Tilt swapped".to_string());
+        }
+        for (attribute, offset) in [
+            (Attribute::Pan, self.pan_offset),
+            (Attribute::Tilt, self.tilt_offset),
+        ] {
+            if offset != 0. {
+                let sign = if offset > 0. { "+" } else { "" };
+                items.push(format!("{attribute} {sign}{}%", percent(offset)));
+            }
+        }
+        for (attribute, modifier) in &self.attributes {
+            let mut item = attribute.to_string();
+            if modifier.curve != Curve::Linear || !modifier.is_limited() {
+                item.push_str(&format!(" {}", modifier.curve));
+            }
+            if modifier.is_limited() {
+                item.push_str(&format!(
+                    " {}-{}%",
+                    percent(modifier.min),
+                    percent(modifier.max)
+                ));
+            }
+            items.push(item);
+        }
+        f.write_str(&items.join(", "))
+    }
+}
+
+/// Parses the list the modifiers are shown as. An attribute given more
+/// than once takes its curve and limits from wherever they are given.
+impl FromStr for OutputModifiers {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut modifiers = Self::default();
+        for item in s
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty() && *item != "-")
+        {
+            let lower = item.to_lowercase();
+            if lower == "pan/tilt swapped" {
+                modifiers.swap_pan_tilt = true;
+                continue;
+            }
+            // The longest name the item starts with, since some names
+            // start with others, like Gobo and Gobo Rotation.
+            let (attribute, rest) = Attribute::ALL
+                .into_iter()
+                .filter_map(|attribute| {
+                    let name = attribute.to_string().to_lowercase();
+                    let rest = lower.strip_prefix(&name)?;
+                    rest.starts_with(' ')
+                        .then(|| (name.len(), attribute, rest.trim()))
+                })
+                .max_by_key(|(len, _, _)| *len)
+                .map(|(_, attribute, rest)| (attribute, rest))
+                .ok_or_else(|| anyhow!("\"{item}\" does not start with an attribute"))?;
+
+            match (attribute, rest) {
+                (Attribute::Pan, "inverted") => modifiers.invert_pan = true,
+                (Attribute::Tilt, "inverted") => modifiers.invert_tilt = true,
+                (Attribute::Pan | Attribute::Tilt, offset) if offset.starts_with(['+', '-']) => {
+                    let offset = parse_percent(offset)?;
+                    if attribute == Attribute::Pan {
+                        modifiers.pan_offset = offset;
+                    } else {
+                        modifiers.tilt_offset = offset;
+                    }
+                }
+                (attribute, rest) => {
+                    let (curve, limits) = match rest.rsplit_once(' ') {
+                        Some((curve, limits)) if limits.ends_with('%') => (curve, Some(limits)),
+                        None if rest.ends_with('%') => ("", Some(rest)),
+                        _ => (rest, None),
+                    };
+                    let modifier = modifiers.attributes.entry(attribute).or_default();
+                    if !curve.is_empty() {
+                        modifier.curve = curve.parse()?;
+                    }
+                    if let Some(limits) = limits {
+                        let (min, max) = limits
+                            .trim_end_matches('%')
+                            .split_once('-')
+                            .ok_or_else(|| anyhow!("invalid limits \"{limits}\""))?;
+                        modifier.min = parse_percent(min)?;
+                        modifier.max = parse_percent(max)?;
+                    }
+                }
+            }
+        }
+        modifiers.validate()?;
+        Ok(modifiers)
+    }
+}
+
+/// A level as a percentage, to a tenth of a percent.
+fn percent(level: f32) -> String {
+    ((level * 1000.).round() / 10.).to_string()
+}
+
+fn parse_percent(s: &str) -> Result<f32> {
+    let s = s.trim();
+    let percent = s
+        .trim_start_matches('+')
+        .trim_end_matches('%')
+        .parse::<f32>()
+        .with_context(|| format!("invalid percentage \"{s}\""))?;
+    Ok(percent / 100.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        address::DmxAddress,
+        fixture::{FixtureId, FixtureRef, PatchedFixture},
+        output::FixtureLevels,
+    };
+    use fixtures::{Channel, Mode, ProfileKey};
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-5,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    fn mover() -> Mode {
+        let channel = |name: &str, attribute, offsets: &[u16]| Channel {
+            name: name.into(),
+            attribute,
+            offsets: offsets.to_vec(),
+            default: 0,
+            highlight: None,
+            physical: None,
+            capabilities: Vec::new(),
+        };
+        Mode {
+            name: "Standard".into(),
+            channels: vec![
+                channel("Pan", Attribute::Pan, &[0, 1]),
+                channel("Tilt", Attribute::Tilt, &[2, 3]),
+                channel("Dimmer", Attribute::Intensity, &[4]),
+            ],
+            heads: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_pan_tilt() {
+        let modifiers = OutputModifiers {
+            invert_pan: true,
+            tilt_offset: 0.05,
+            ..OutputModifiers::default()
+        };
+        assert_near(modifiers.apply(Attribute::Pan, 0.2), 0.8);
+        assert_near(modifiers.apply(Attribute::Tilt, 0.5), 0.55);
+        assert_near(modifiers.apply(Attribute::Tilt, 0.98), 1.);
+        assert_near(modifiers.apply(Attribute::Intensity, 0.2), 0.2);
+
+        // Offsets apply after inverting.
+        let modifiers = OutputModifiers {
+            invert_tilt: true,
+            tilt_offset: -0.1,
+            ..OutputModifiers::default()
+        };
+        assert_near(modifiers.apply(Attribute::Tilt, 0.), 0.9);
+        assert_near(modifiers.apply(Attribute::Tilt, 0.95), 0.);
+
+        let swapped = OutputModifiers {
+            swap_pan_tilt: true,
+            ..OutputModifiers::default()
+        };
+        assert_eq!(swapped.source(Attribute::Pan), Attribute::Tilt);
+        assert_eq!(swapped.source(Attribute::Tilt), Attribute::Pan);
+        assert_eq!(swapped.source(Attribute::Intensity), Attribute::Intensity);
+        assert_eq!(modifiers.source(Attribute::Pan), Attribute::Pan);
+    }
+
+    #[test]
+    fn test_curves() {
+        assert_near(Curve::Linear.apply(0.3), 0.3);
+        assert_near(Curve::SquareLaw.apply(0.5), 0.25);
+        assert_near(Curve::SquareLaw.apply(1.), 1.);
+        assert_near(Curve::SShaped.apply(0.), 0.);
+        assert_near(Curve::SShaped.apply(0.25), 0.15625);
+        assert_near(Curve::SShaped.apply(0.5), 0.5);
+        assert_near(Curve::SShaped.apply(1.), 1.);
+
+        let table = Curve::Table(vec![0.1, 0.2, 0.6, 0.9]);
+        assert_near(table.apply(0.), 0.1);
+        assert_near(table.apply(1. / 3.), 0.2);
+        assert_near(table.apply(0.5), 0.4);
+        assert_near(table.apply(5. / 6.), 0.75);
+        assert_near(table.apply(1.), 0.9);
+    }
+
+    #[test]
+    fn test_limits() {
+        let modifier = AttributeModifier {
+            curve: Curve::SquareLaw,
+            min: 0.1,
+            max: 0.8,
+        };
+        assert_near(modifier.apply(0.), 0.1);
+        assert_near(modifier.apply(0.5), 0.25);
+        assert_near(modifier.apply(1.), 0.8);
+
+        let mut modifiers = OutputModifiers::default();
+        modifiers.attributes.insert(Attribute::Intensity, modifier);
+        assert_near(modifiers.apply(Attribute::Intensity, 1.), 0.8);
+        assert_near(modifiers.apply(Attribute::Zoom, 1.), 1.);
+    }
+
+    #[test]
+    fn test_mover() {
+        let modifiers = OutputModifiers {
+            invert_pan: true,
+            swap_pan_tilt: true,
+            tilt_offset: 0.1,
+            ..OutputModifiers::default()
+        };
+        let fixture = PatchedFixture {
+            id: FixtureId(1),
+            name: String::new(),
+            profile: ProfileKey::new("Tungsten", "Mover"),
+            mode: "Standard".into(),
+            footprint: 5,
+            address: Some(DmxAddress::new(1, 1).unwrap()),
+            split: None,
+            cells: Vec::new(),
+            modifiers: modifiers.clone(),
+            rdm_uid: None,
+        };
+        let mut levels = FixtureLevels::default();
+        let id = FixtureRef::fixture(fixture.id);
+        levels.set(id, Attribute::Pan, 0.25);
+        levels.set(id, Attribute::Tilt, 0.75);
+        levels.set(id, Attribute::Intensity, 1.);
+
+        // The pan channel is driven by the programmed tilt and inverted,
+        // and the tilt channel by the programmed pan and offset.
+        let mode = mover();
+        let slots = levels.render_fixture(&fixture, &mode);
+        let pan = mode.channel(Attribute::Pan).unwrap().read(&slots);
+        let tilt = mode.channel(Attribute::Tilt).unwrap().read(&slots);
+        let expected = |level: f32| (level * 65535.).round() as u32;
+        assert_eq!(pan, expected(modifiers.apply(Attribute::Pan, 0.75)));
+        assert_eq!(pan, expected(0.25));
+        assert_eq!(tilt, expected(modifiers.apply(Attribute::Tilt, 0.25)));
+        assert_eq!(tilt, expected(0.35));
+        assert_eq!(slots[4], 255);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let modifiers = "Pan inverted, Pan/Tilt swapped, Tilt +5%, \
+            Intensity square law 0-80%, Intensity table 0 20 60 100"
+            .parse::<OutputModifiers>()
+            .unwrap();
+        assert!(modifiers.invert_pan);
+        assert!(!modifiers.invert_tilt);
+        assert!(modifiers.swap_pan_tilt);
+        assert_near(modifiers.pan_offset, 0.);
+        assert_near(modifiers.tilt_offset, 0.05);
+        // The table given later replaces the curve, and the limits stay.
+        assert_eq!(
+            modifiers.attributes[&Attribute::Intensity],
+            AttributeModifier {
+                curve: Curve::Table(vec![0., 0.2, 0.6, 1.]),
+                min: 0.,
+                max: 0.8,
+            }
+        );
+
+        let text = modifiers.to_string();
+        assert_eq!(
+            text,
+            "Pan inverted, Pan/Tilt swapped, Tilt +5%, Intensity table 0 20 60 100 0-80%"
+        );
+        assert_eq!(text.parse::<OutputModifiers>().unwrap(), modifiers);
+
+        for text in [
+            "",
+            "Tilt inverted, Pan -12.5%, Zoom s-curve",
+            "Intensity 10-90%, Color Wheel linear",
+        ] {
+            let modifiers = text.parse::<OutputModifiers>().unwrap();
+            assert_eq!(modifiers.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        for text in [
+            "Pan +150%",
+            "Intensity 80-20%",
+            "Intensity table 50",
+            "Intensity table 0 120",
+            "Intensity wobbly",
+            "Sideways inverted",
+        ] {
+            assert!(text.parse::<OutputModifiers>().is_err(), "{text}");
+        }
+    }
+}
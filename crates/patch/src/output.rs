@@ -0,0 +1,277 @@
+use fixtures::{Attribute, DmxValue, FixtureLibrary, Mode};
+use std::collections::BTreeMap;
+
+use crate::{
+    address::DmxAddress,
+    fixture::{FixtureId, FixtureRef, PatchedFixture},
+    model::Patch,
+};
+
+/// The levels programmed into fixtures and cells by attribute, from 0
+/// to 1. Only fixtures with levels are sent, so that anything else
+/// writing to the universes keeps the addresses of the rest.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FixtureLevels {
+    levels: BTreeMap<FixtureRef, BTreeMap<Attribute, f32>>,
+}
+
+impl FixtureLevels {
+    pub fn set(&mut self, fixture_ref: FixtureRef, attribute: Attribute, level: f32) {
+        self.levels
+            .entry(fixture_ref)
+            .or_default()
+            .insert(attribute, level.clamp(0., 1.));
+    }
+
+    pub fn get(&self, fixture_ref: FixtureRef, attribute: Attribute) -> Option<f32> {
+        self.levels.get(&fixture_ref)?.get(&attribute).copied()
+    }
+
+    /// Brings a fixture or cell up to full white, so that it can be picked
+    /// out on stage.
+    pub fn highlight(&mut self, fixture_ref: FixtureRef) {
+        for attribute in [
+            Attribute::Intensity,
+            Attribute::Red,
+            Attribute::Green,
+            Attribute::Blue,
+            Attribute::White,
+        ] {
+            self.set(fixture_ref, attribute, 1.);
+        }
+    }
+
+    /// Releases a fixture and its cells.
+    pub fn clear(&mut self, id: FixtureId) {
+        self.levels.retain(|fixture_ref, _| fixture_ref.id != id);
+    }
+
+    pub fn clear_all(&mut self) {
+        self.levels.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    /// Works out the slots of every patched fixture with levels and hands
+    /// each one to `write` with its address. Fixtures whose profile or mode
+    /// is not in `library` are skipped.
+    pub fn render(
+        &self,
+        patch: &Patch,
+        library: &FixtureLibrary,
+        mut write: impl FnMut(DmxAddress, u8),
+    ) {
+        let mut ids = self
+            .levels
+            .keys()
+            .map(|fixture_ref| fixture_ref.id)
+            .collect::<Vec<_>>();
+        ids.dedup();
+        for id in ids {
+            let Some(fixture) = patch.get(id) else {
+                continue;
+            };
+            let Some(mode) = library
+                .get(&fixture.profile)
+                .and_then(|profile| profile.mode(&fixture.mode))
+            else {
+                continue;
+            };
+            for (offset, level) in self.render_fixture(fixture, mode).into_iter().enumerate() {
+                if let Some(address) = fixture.address_of(offset as u16) {
+                    write(address, level);
+                }
+            }
+        }
+    }
+
+    /// The slots of one fixture, from its defaults and its levels and those
+    /// of its cells, corrected by its modifiers. Fixtures without an
+    /// intensity channel have their emitters scaled by a virtual intensity
+    /// instead, which rests at 0 like a dimmer channel would: a color
+    /// without an intensity stays dark.
+    pub fn render_fixture(&self, fixture: &PatchedFixture, mode: &Mode) -> Vec<u8> {
+        let mut slots = mode.defaults();
+        let mut cells = vec![None; mode.channels.len()];
+        for (index, head) in mode.heads.iter().enumerate() {
+            for channel in &head.channels {
+                if let Some(cell) = cells.get_mut(*channel) {
+                    *cell = Some(index as u16 + 1);
+                }
+            }
+        }
+        let modifiers = &fixture.modifiers;
+        let virtual_intensity = mode.has_virtual_intensity();
+
+        for (channel, cell) in mode.channels.iter().zip(cells) {
+            let level = self.level(fixture.id, cell, modifiers.source(channel.attribute));
+            let intensity = if virtual_intensity && channel.attribute.is_additive_color() {
+                let intensity = self
+                    .level(fixture.id, cell, Attribute::Intensity)
+                    .unwrap_or(0.);
+                Some(modifiers.apply(Attribute::Intensity, intensity))
+            } else {
+                None
+            };
+            if level.is_none() && intensity.is_none() {
+                continue;
+            }
+            let max_value = channel.max_value();
+            let level = match level {
+                Some(level) => modifiers.apply(channel.attribute, level),
+                None => channel.default as f32 / max_value as f32,
+            };
+            let level = level * intensity.unwrap_or(1.);
+            channel.write((level * max_value as f32).round() as DmxValue, &mut slots);
+        }
+        slots
+    }
+
+    /// The level of a cell's attribute, falling back to the fixture's.
+    fn level(&self, id: FixtureId, cell: Option<u16>, attribute: Attribute) -> Option<f32> {
+        cell.and_then(|cell| self.get(FixtureRef::cell(id, cell), attribute))
+            .or_else(|| self.get(FixtureRef::fixture(id), attribute))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generic::generic_profiles,
+        modifiers::{AttributeModifier, Curve, OutputModifiers},
+    };
+    use fixtures::{Channel, Head, ProfileKey};
+
+    fn rgb_fixture(cells: usize) -> PatchedFixture {
+        PatchedFixture {
+            id: FixtureId(1),
+            name: String::new(),
+            profile: ProfileKey::new("Generic", "RGB"),
+            mode: "3 channel".into(),
+            footprint: 3 * cells as u16,
+            address: Some(DmxAddress::new(1, 10).unwrap()),
+            split: None,
+            cells: (1..=cells).map(|cell| format!("Cell {cell}")).collect(),
+            modifiers: OutputModifiers::default(),
+            rdm_uid: None,
+        }
+    }
+
+    fn rgb_mode(cells: u16) -> Mode {
+        let mut mode = Mode {
+            name: "RGB".into(),
+            channels: Vec::new(),
+            heads: Vec::new(),
+        };
+        for cell in 0..cells {
+            let attributes = [Attribute::Red, Attribute::Green, Attribute::Blue];
+            for (ix, attribute) in attributes.into_iter().enumerate() {
+                mode.channels.push(Channel {
+                    name: format!("{attribute} {}", cell + 1),
+                    attribute,
+                    offsets: vec![3 * cell + ix as u16],
+                    default: 0,
+                    highlight: None,
+                    physical: None,
+                    capabilities: Vec::new(),
+                });
+            }
+            if cells > 1 {
+                mode.heads.push(Head {
+                    name: format!("Cell {}", cell + 1),
+                    channels: (3 * cell as usize..3 * cell as usize + 3).collect(),
+                });
+            }
+        }
+        mode
+    }
+
+    #[test]
+    fn test_virtual_intensity() {
+        let fixture = rgb_fixture(1);
+        let mode = rgb_mode(1);
+        let id = FixtureRef::fixture(fixture.id);
+        let mut levels = FixtureLevels::default();
+        levels.set(id, Attribute::Red, 1.);
+        levels.set(id, Attribute::Green, 0.5);
+
+        // Without an intensity the color is dark, as it would be with a
+        // dimmer channel at 0.
+        assert_eq!(levels.render_fixture(&fixture, &mode), [0, 0, 0]);
+
+        levels.set(id, Attribute::Intensity, 0.5);
+        assert_eq!(levels.render_fixture(&fixture, &mode), [128, 64, 0]);
+
+        levels.set(id, Attribute::Intensity, 1.);
+        assert_eq!(levels.render_fixture(&fixture, &mode), [255, 128, 0]);
+    }
+
+    #[test]
+    fn test_cell_falls_back_to_fixture() {
+        let fixture = rgb_fixture(2);
+        let mode = rgb_mode(2);
+        let mut levels = FixtureLevels::default();
+        levels.set(FixtureRef::fixture(fixture.id), Attribute::Red, 1.);
+        levels.set(FixtureRef::fixture(fixture.id), Attribute::Intensity, 1.);
+        levels.set(FixtureRef::cell(fixture.id, 2), Attribute::Intensity, 0.2);
+        levels.set(FixtureRef::cell(fixture.id, 2), Attribute::Blue, 1.);
+        assert_eq!(
+            levels.render_fixture(&fixture, &mode),
+            [255, 0, 0, 51, 0, 51]
+        );
+    }
+
+    #[test]
+    fn test_intensity_curve() {
+        let mut fixture = rgb_fixture(1);
+        fixture.modifiers.attributes.insert(
+            Attribute::Intensity,
+            AttributeModifier {
+                curve: Curve::SquareLaw,
+                ..AttributeModifier::default()
+            },
+        );
+        let mode = rgb_mode(1);
+        let id = FixtureRef::fixture(fixture.id);
+        let mut levels = FixtureLevels::default();
+        levels.set(id, Attribute::Red, 1.);
+        levels.set(id, Attribute::Blue, 0.5);
+        levels.set(id, Attribute::Intensity, 0.5);
+        assert_eq!(levels.render_fixture(&fixture, &mode), [64, 0, 32]);
+    }
+
+    #[test]
+    fn test_render_highlight() {
+        let mut library = FixtureLibrary::default();
+        for profile in generic_profiles() {
+            library.insert(profile).unwrap();
+        }
+        let mut unpatched = rgb_fixture(1);
+        unpatched.id = FixtureId(2);
+        unpatched.address = None;
+        let patch = Patch::from_fixtures(vec![rgb_fixture(1), unpatched]).unwrap();
+
+        let mut levels = FixtureLevels::default();
+        levels.highlight(FixtureRef::fixture(FixtureId(1)));
+        levels.highlight(FixtureRef::fixture(FixtureId(2)));
+        let mut written = Vec::new();
+        levels.render(&patch, &library, |address, level| {
+            written.push((address.to_string(), level))
+        });
+        assert_eq!(
+            written,
+            [
+                ("1/10".to_string(), 255),
+                ("1/11".to_string(), 255),
+                ("1/12".to_string(), 255)
+            ]
+        );
+
+        levels.clear(FixtureId(1));
+        levels.clear(FixtureId(2));
+        assert!(levels.is_empty());
+    }
+}
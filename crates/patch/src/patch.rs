@@ -0,0 +1,55 @@
+pub mod address;
+pub mod csv;
+pub mod exchange;
+pub mod fixture;
+pub mod generic;
+pub mod model;
+pub mod modifiers;
+pub mod output;
+mod patch_settings;
+pub mod store;
+pub mod usitt;
+
+pub use address::{DmxAddress, UNIVERSE_SIZE};
+pub use csv::{CsvColumns, CsvField};
+pub use exchange::{ImportPreview, PatchFormat, PatchImport};
+pub use fixture::{FixtureId, FixtureRef, PatchedFixture, Split};
+pub use model::{Conflict, Patch};
+pub use modifiers::{AttributeModifier, Curve, OutputModifiers};
+pub use output::FixtureLevels;
+pub use store::PatchStore;
+
+use gpui::AppContext;
+use patch_settings::PatchSettings;
+use settings::Settings;
+use std::sync::Arc;
+
+pub fn init(cx: &mut AppContext) {
+    PatchSettings::register(cx);
+
+    let settings = PatchSettings::get_global(cx).clone();
+    let store = match PatchStore::load(settings.path.clone()) {
+        Ok(store) => store,
+        Err(err) => {
+            // Starting empty without saving keeps the broken file around
+            // to be fixed by hand.
+            log::error!("failed to load the patch: {err:?}");
+            PatchStore::new()
+        }
+    };
+    let store = Arc::new(store);
+    PatchStore::set_global(store.clone(), cx);
+
+    cx.background_executor()
+        .spawn(async move {
+            let report = store.load_library(&settings.library);
+            for warning in &report.warnings {
+                log::warn!("{warning}");
+            }
+            for (path, err) in &report.failed {
+                log::error!("failed to import {}: {err}", path.display());
+            }
+            log::info!("imported {} fixture profiles", report.imported.len());
+        })
+        .detach();
+}
@@ -0,0 +1,53 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::SettingsSources;
+use std::path::PathBuf;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+pub(crate) struct PatchSettings {
+    pub(crate) path: PathBuf,
+    pub(crate) library: LibrarySettings,
+}
+
+/// Where fixture profiles are imported from at startup.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+pub(crate) struct LibrarySettings {
+    #[serde(default)]
+    pub(crate) ofl_dirs: Vec<PathBuf>,
+    #[serde(default)]
+    pub(crate) gdtf_dirs: Vec<PathBuf>,
+    pub(crate) gdtf_images_dir: PathBuf,
+}
+
+/// Patch-related settings.
+#[derive(Serialize, Deserialize, PartialEq, Default, Clone, JsonSchema)]
+pub(crate) struct PatchSettingsContent {
+    /// The file the patch is loaded from and saved to.
+    path: Option<String>,
+    /// The fixture profiles fixtures can be patched with.
+    library: Option<LibrarySettingsContent>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Default, Clone, JsonSchema)]
+pub(crate) struct LibrarySettingsContent {
+    /// Open Fixture Library `fixtures` directories, with a directory per
+    /// manufacturer.
+    ofl_dirs: Option<Vec<String>>,
+    /// Directories of `.gdtf` files.
+    gdtf_dirs: Option<Vec<String>>,
+    /// Where wheel images from GDTF files are extracted to.
+    gdtf_images_dir: Option<String>,
+}
+
+impl settings::Settings for PatchSettings {
+    const KEY: Option<&'static str> = Some("patch");
+
+    type FileContent = PatchSettingsContent;
+
+    fn load(
+        sources: SettingsSources<Self::FileContent>,
+        _: &mut gpui::AppContext,
+    ) -> anyhow::Result<Self> {
+        sources.json_merge()
+    }
+}
@@ -0,0 +1,189 @@
+use anyhow::{Context, Result};
+use fixtures::{gdtf, ofl, FixtureLibrary, ImportReport};
+use gpui::{AppContext, Global};
+use parking_lot::{Mutex, RwLock};
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    address::DmxAddress, fixture::PatchedFixture, generic::generic_profiles, model::Patch,
+    output::FixtureLevels, patch_settings::LibrarySettings,
+};
+
+struct GlobalPatchStore(Arc<PatchStore>);
+
+impl Global for GlobalPatchStore {}
+
+#[derive(Deserialize)]
+struct SavedPatch {
+    fixtures: Vec<PatchedFixture>,
+}
+
+/// The show's patch, the fixture profiles it is patched with and the
+/// levels programmed into the fixtures, shared by the patch editor and the
+/// output engine. Every change to the patch is saved to its file.
+pub struct PatchStore {
+    patch: RwLock<Patch>,
+    library: RwLock<FixtureLibrary>,
+    levels: RwLock<FixtureLevels>,
+    path: Option<PathBuf>,
+    /// Held while saving, so that saves land in the order of the updates.
+    save_lock: Mutex<()>,
+}
+
+impl PatchStore {
+    pub fn global(cx: &AppContext) -> Arc<Self> {
+        cx.global::<GlobalPatchStore>().0.clone()
+    }
+
+    pub fn try_global(cx: &AppContext) -> Option<Arc<Self>> {
+        cx.try_global::<GlobalPatchStore>()
+            .map(|store| store.0.clone())
+    }
+
+    pub fn set_global(store: Arc<Self>, cx: &mut AppContext) {
+        cx.set_global(GlobalPatchStore(store));
+    }
+
+    /// An empty patch that is not saved anywhere.
+    pub fn new() -> Self {
+        Self {
+            patch: RwLock::default(),
+            library: RwLock::new(generic_library()),
+            levels: RwLock::default(),
+            path: None,
+            save_lock: Mutex::new(()),
+        }
+    }
+
+    /// Loads the patch saved at `path`, starting an empty one if there is
+    /// no file yet.
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let patch = if path.exists() {
+            let json =
+                fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+            let saved: SavedPatch = serde_json::from_str(&json)
+                .with_context(|| format!("parsing {}", path.display()))?;
+            Patch::from_fixtures(saved.fixtures)
+                .with_context(|| format!("loading {}", path.display()))?
+        } else {
+            Patch::default()
+        };
+        Ok(Self {
+            patch: RwLock::new(patch),
+            library: RwLock::new(generic_library()),
+            levels: RwLock::default(),
+            path: Some(path),
+            save_lock: Mutex::new(()),
+        })
+    }
+
+    /// The file the patch is saved to, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns a copy of the patch.
+    pub fn patch(&self) -> Patch {
+        self.patch.read().clone()
+    }
+
+    pub fn read<R>(&self, f: impl FnOnce(&Patch) -> R) -> R {
+        f(&self.patch.read())
+    }
+
+    /// Changes the patch and saves it. Nothing is changed if `f` fails
+    /// part way through. The patch can be read again while it is saved.
+    pub fn update<R>(&self, f: impl FnOnce(&mut Patch) -> Result<R>) -> Result<R> {
+        let mut patch = self.patch.write();
+        let mut updated = patch.clone();
+        let result = f(&mut updated)?;
+        *patch = updated.clone();
+        let _save_lock = self.save_lock.lock();
+        drop(patch);
+        if let Err(err) = self.save(&updated) {
+            log::error!("failed to save the patch: {err:?}");
+        }
+        Ok(result)
+    }
+
+    pub fn read_library<R>(&self, f: impl FnOnce(&FixtureLibrary) -> R) -> R {
+        f(&self.library.read())
+    }
+
+    pub fn levels(&self) -> FixtureLevels {
+        self.levels.read().clone()
+    }
+
+    pub fn update_levels<R>(&self, f: impl FnOnce(&mut FixtureLevels) -> R) -> R {
+        f(&mut self.levels.write())
+    }
+
+    /// Hands the slots of every fixture with levels to `write`.
+    pub fn render(&self, write: impl FnMut(DmxAddress, u8)) {
+        let levels = self.levels.read();
+        if levels.is_empty() {
+            return;
+        }
+        levels.render(&self.patch.read(), &self.library.read(), write);
+    }
+
+    /// Imports the fixture profiles found in the configured directories
+    /// alongside the generic ones.
+    pub(crate) fn load_library(&self, settings: &LibrarySettings) -> ImportReport {
+        let mut library = generic_library();
+        let mut report = ImportReport::default();
+        for dir in &settings.ofl_dirs {
+            match ofl::import_dir(dir, &mut library) {
+                Ok(imported) => merge_report(&mut report, imported),
+                Err(err) => report.failed.push((dir.clone(), format!("{err:#}"))),
+            }
+        }
+        for dir in &settings.gdtf_dirs {
+            match gdtf::import_dir(dir, &settings.gdtf_images_dir, &mut library) {
+                Ok(imported) => merge_report(&mut report, imported),
+                Err(err) => report.failed.push((dir.clone(), format!("{err:#}"))),
+            }
+        }
+        *self.library.write() = library;
+        report
+    }
+
+    fn save(&self, patch: &Patch) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        // Written next to the patch file and moved over it, so that a
+        // failed write leaves the last saved patch in place.
+        let json = serde_json::to_string_pretty(patch)?;
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, json).with_context(|| format!("writing {}", temp_path.display()))?;
+        fs::rename(&temp_path, path).with_context(|| format!("writing {}", path.display()))
+    }
+}
+
+impl Default for PatchStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn generic_library() -> FixtureLibrary {
+    let mut library = FixtureLibrary::default();
+    for profile in generic_profiles() {
+        if let Err(err) = library.insert(profile) {
+            log::error!("invalid generic profile: {err:?}");
+        }
+    }
+    library
+}
+
+fn merge_report(report: &mut ImportReport, other: ImportReport) {
+    report.imported.extend(other.imported);
+    report.warnings.extend(other.warnings);
+    report.failed.extend(other.failed);
+}
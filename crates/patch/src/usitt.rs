@@ -0,0 +1,356 @@
+use anyhow::{anyhow, Context, Result};
+use fixtures::FixtureLibrary;
+use std::collections::BTreeMap;
+
+use crate::{
+    address::DmxAddress,
+    exchange::{FixtureRecord, PatchImport},
+    fixture::FixtureId,
+    model::Patch,
+};
+
+/// The longest line the format allows.
+const MAX_LINE_LEN: usize = 80;
+
+/// Reads the patch from a USITT ASCII show file. `Patch` records patch
+/// channels to dimmers, which become generic dimmers. A `$Patch` record
+/// patches one fixture, with `$$Fixture`, `$$Manufacturer`, `$$Mode`,
+/// `$$Label` and `$$Footprint` records after it describing the fixture.
+/// Everything else, like cues, is skipped.
+pub(crate) fn import(text: &str, library: &FixtureLibrary) -> Result<PatchImport> {
+    let mut records = BTreeMap::<FixtureId, (usize, FixtureRecord)>::new();
+    let mut import = PatchImport::default();
+    // The fixture the `$$` records after a `$Patch` record describe.
+    let mut current = None;
+
+    for (ix, line) in text.lines().enumerate() {
+        let line_number = ix + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('!') {
+            continue;
+        }
+        let (keyword, rest) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(keyword, rest)| (keyword, rest.trim()));
+        let keyword = keyword.to_lowercase();
+        if !keyword.starts_with("$$") {
+            current = None;
+        }
+
+        let result = match keyword.as_str() {
+            "enddata" => break,
+            "patch" => {
+                patch_record(rest, line_number, &mut records, &mut import);
+                Ok(())
+            }
+            "$patch" => {
+                fixture_record(rest, line_number, &mut records).map(|id| current = Some(id))
+            }
+            "$$fixture" | "$$manufacturer" | "$$mode" | "$$label" | "$$footprint" => {
+                match current.and_then(|id| records.get_mut(&id)) {
+                    Some((_, record)) => describe(record, &keyword, rest),
+                    None => Err(anyhow!("{keyword} is not after a $Patch record")),
+                }
+            }
+            _ => Ok(()),
+        };
+        if let Err(err) = result {
+            import.warnings.push(format!("line {line_number}: {err:#}"));
+        }
+    }
+
+    for (line_number, record) in records.into_values() {
+        import.push(&format!("line {line_number}"), record, library);
+    }
+    Ok(import)
+}
+
+/// Reads `Patch <page> <channel><<dimmer>[@<level>] ...`. Pairs that
+/// cannot be read are warned about and the rest of the line is still read.
+fn patch_record(
+    rest: &str,
+    line_number: usize,
+    records: &mut BTreeMap<FixtureId, (usize, FixtureRecord)>,
+    import: &mut PatchImport,
+) {
+    for pair in rest.split_whitespace().skip(1) {
+        let (id, dimmer, address) = match patch_pair(pair) {
+            Ok(patch) => patch,
+            Err(err) => {
+                import.warnings.push(format!("line {line_number}: {err:#}"));
+                continue;
+            }
+        };
+        match records.get(&id) {
+            Some((_, record)) => import.warnings.push(format!(
+                "line {line_number}: channel {id} is already patched to {}, so dimmer {dimmer} is left out",
+                record.address.map_or("-".to_string(), |address| address.to_string())
+            )),
+            None => {
+                records.insert(
+                    id,
+                    (
+                        line_number,
+                        FixtureRecord {
+                            id: Some(id),
+                            address: Some(address),
+                            ..FixtureRecord::default()
+                        },
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Reads a `<channel><<dimmer>[@<level>]` pair of a `Patch` record.
+fn patch_pair(pair: &str) -> Result<(FixtureId, &str, DmxAddress)> {
+    let (channel, dimmer) = pair
+        .split_once('<')
+        .ok_or_else(|| anyhow!("invalid patch \"{pair}\""))?;
+    let id = channel.parse()?;
+    let dimmer = dimmer.split_once('@').map_or(dimmer, |(dimmer, _)| dimmer);
+    Ok((id, dimmer, parse_dimmer(dimmer)?))
+}
+
+/// Reads `$Patch <channel> [<dimmer>]`, which takes the place of any
+/// `Patch` record for the channel.
+fn fixture_record(
+    rest: &str,
+    line_number: usize,
+    records: &mut BTreeMap<FixtureId, (usize, FixtureRecord)>,
+) -> Result<FixtureId> {
+    let mut words = rest.split_whitespace();
+    let id = words
+        .next()
+        .ok_or_else(|| anyhow!("$Patch has no channel"))?
+        .parse()?;
+    let address = words.next().map(parse_dimmer).transpose()?;
+    records.insert(
+        id,
+        (
+            line_number,
+            FixtureRecord {
+                id: Some(id),
+                address,
+                ..FixtureRecord::default()
+            },
+        ),
+    );
+    Ok(id)
+}
+
+fn describe(record: &mut FixtureRecord, keyword: &str, value: &str) -> Result<()> {
+    match keyword {
+        "$$fixture" => record.model = value.to_string(),
+        "$$manufacturer" => record.manufacturer = value.to_string(),
+        "$$mode" => record.mode = value.to_string(),
+        "$$label" => record.name = value.to_string(),
+        "$$footprint" => {
+            record.footprint = Some(
+                value
+                    .parse()
+                    .with_context(|| format!("invalid footprint \"{value}\""))?,
+            )
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Reads a dimmer as a DMX number counted across universes, or as
+/// `universe/address`.
+fn parse_dimmer(dimmer: &str) -> Result<DmxAddress> {
+    if dimmer.contains('/') {
+        dimmer.parse()
+    } else {
+        DmxAddress::from_linear(
+            dimmer
+                .parse()
+                .with_context(|| format!("invalid dimmer \"{dimmer}\""))?,
+        )
+    }
+}
+
+/// Writes the patch as a USITT ASCII show file, with `Patch` records for
+/// consoles that only read dimmers and `$Patch` records for the fixture
+/// types. Fixtures are patched by their first address, so the second part
+/// of a split fixture is left out.
+pub(crate) fn export(patch: &Patch) -> String {
+    let mut lines = vec![
+        "Ident 3:0".to_string(),
+        "Manufacturer Tungsten".to_string(),
+        "Console Tungsten".to_string(),
+    ];
+
+    let mut patch_line = String::new();
+    for fixture in patch.fixtures() {
+        let Some(address) = fixture.address else {
+            continue;
+        };
+        let pair = format!(" {}<{}", fixture.id, address.linear());
+        if !patch_line.is_empty() && patch_line.len() + pair.len() > MAX_LINE_LEN {
+            lines.push(std::mem::take(&mut patch_line));
+        }
+        if patch_line.is_empty() {
+            patch_line.push_str("Patch 1");
+        }
+        patch_line.push_str(&pair);
+    }
+    if !patch_line.is_empty() {
+        lines.push(patch_line);
+    }
+
+    for fixture in patch.fixtures() {
+        lines.push(match fixture.address {
+            Some(address) => format!("$Patch {} {address}", fixture.id),
+            None => format!("$Patch {}", fixture.id),
+        });
+        lines.push(format!("$$Manufacturer {}", fixture.profile.manufacturer));
+        lines.push(format!("$$Fixture {}", fixture.profile.model));
+        lines.push(format!("$$Mode {}", fixture.mode));
+        if !fixture.name.is_empty() {
+            lines.push(format!("$$Label {}", fixture.name));
+        }
+        lines.push(format!("$$Footprint {}", fixture.footprint));
+    }
+    lines.push("Enddata".to_string());
+
+    let mut text = lines.join("\r\n");
+    text.push_str("\r\n");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixture::PatchedFixture, generic::generic_profiles, modifiers::OutputModifiers};
+    use fixtures::ProfileKey;
+
+    fn library() -> FixtureLibrary {
+        let mut library = FixtureLibrary::default();
+        for profile in generic_profiles() {
+            library.insert(profile).unwrap();
+        }
+        library
+    }
+
+    fn fixture(id: u32, model: &str, mode: &str, footprint: u16) -> PatchedFixture {
+        PatchedFixture {
+            id: FixtureId(id),
+            name: String::new(),
+            profile: ProfileKey::new("Generic", model),
+            mode: mode.into(),
+            footprint,
+            address: None,
+            split: None,
+            cells: Vec::new(),
+            modifiers: OutputModifiers::default(),
+            rdm_uid: None,
+        }
+    }
+
+    fn address(universe: u16, address: u16) -> DmxAddress {
+        DmxAddress::new(universe, address).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut fixtures = Vec::new();
+        for id in 1..=30 {
+            let mut dimmer = fixture(id, "Dimmer", "8 bit", 1);
+            dimmer.address = Some(address(1, id as u16));
+            fixtures.push(dimmer);
+        }
+        let mut wash = fixture(101, "RGBW", "4 channel", 4);
+        wash.name = "Cyc wash".into();
+        wash.address = Some(address(2, 509));
+        fixtures.push(wash);
+        fixtures.push(fixture(102, "Dimmer", "16 bit", 2));
+        let patch = Patch::from_fixtures(fixtures).unwrap();
+
+        // Patch records are wrapped to the longest line allowed.
+        let text = export(&patch);
+        assert!(text.lines().all(|line| line.len() <= MAX_LINE_LEN));
+        assert!(text.contains("$Patch 101 2/509\r\n"));
+        assert!(text.contains(" 101<1021"));
+
+        let import = import(&text, &library()).unwrap();
+        assert_eq!(import.warnings, Vec::<String>::new());
+        assert_eq!(import.fixtures, patch.fixtures());
+    }
+
+    #[test]
+    fn test_patch_records() {
+        let text = "Ident 3:0\r\n\
+            ! Dimmers counted across universes\r\n\
+            Patch 1 1<1 2<513 3<1024@FF 4<abc 5<2/7 6 7<1\r\n\
+            PATCH 1 1<5 8<0 9<1025\r\n";
+        let import = import(text, &library()).unwrap();
+        let addresses = import
+            .fixtures
+            .iter()
+            .map(|fixture| (fixture.id.0, fixture.address.unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            addresses,
+            [
+                (1, address(1, 1)),
+                (2, address(2, 1)),
+                (3, address(2, 512)),
+                (5, address(2, 7)),
+                (7, address(1, 1)),
+                (9, address(3, 1)),
+            ]
+        );
+        assert!(import
+            .fixtures
+            .iter()
+            .all(|fixture| fixture.profile == ProfileKey::new("Generic", "Dimmer")));
+        assert_eq!(import.warnings.len(), 4, "{:?}", import.warnings);
+        assert!(import.warnings[0].starts_with("line 3: invalid dimmer \"abc\""));
+        assert_eq!(import.warnings[1], "line 3: invalid patch \"6\"");
+        assert_eq!(
+            import.warnings[2],
+            "line 4: channel 1 is already patched to 1/1, so dimmer 5 is left out"
+        );
+        assert!(import.warnings[3].starts_with("line 4: "));
+    }
+
+    #[test]
+    fn test_fixture_records() {
+        let text = "Ident 3:0\n\
+            Patch 1 101<1 102<2\n\
+            $Patch 101 1/10\n\
+            $$Manufacturer Generic\n\
+            $$Fixture RGB\n\
+            $$Mode 3 channel\n\
+            $$Label Front wash\n\
+            $$Footprint 3\n\
+            $Patch 103\n\
+            $$Fixture rgbw\n\
+            $$Footprint x\n\
+            Cue 1\n\
+            $$Label Lost\n\
+            Enddata\n\
+            Patch 1 200<1\n";
+        let import = import(text, &library()).unwrap();
+
+        let mut wash = fixture(101, "RGB", "3 channel", 3);
+        wash.name = "Front wash".into();
+        wash.address = Some(address(1, 10));
+        let mut dimmer = fixture(102, "Dimmer", "8 bit", 1);
+        dimmer.address = Some(address(1, 2));
+        // Without a manufacturer or mode, the first mode of the model is
+        // taken.
+        let rgbw = fixture(103, "RGBW", "4 channel", 4);
+        assert_eq!(import.fixtures, [wash, dimmer, rgbw]);
+        assert_eq!(
+            import.warnings,
+            [
+                "line 11: invalid footprint \"x\": invalid digit found in string",
+                "line 13: $$label is not after a $Patch record",
+            ]
+        );
+    }
+}
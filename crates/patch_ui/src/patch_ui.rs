@@ -1,17 +1,36 @@
+use anyhow::{anyhow, Context, Result};
+use fixtures::ProfileKey;
 use gpui::{
-    actions, AnyElement, AppContext, EventEmitter, FocusHandle, FocusableView, IntoElement, Render,
-    View, ViewContext, VisualContext, WeakView,
+    actions, div, AnyElement, AppContext, EventEmitter, FocusHandle, FocusableView, IntoElement,
+    KeyDownEvent, Render, StatefulInteractiveElement, Task, View, ViewContext, VisualContext,
+    WeakView,
+};
+use patch::{
+    CsvColumns, DmxAddress, FixtureId, FixtureRef, ImportPreview, OutputModifiers, PatchImport,
+    PatchStore, PatchedFixture,
+};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
 };
 use theme::ActiveTheme;
 use ui::{
-    h_flex, v_flex, Color, InteractiveElement, Label, LabelCommon, ParentElement, Styled,
-    WindowContext,
+    h_flex, rems, v_flex, Button, Clickable, Color, Disableable, FluentBuilder, Indicator,
+    InteractiveElement, Label, LabelCommon, ParentElement, Selectable, Styled, WindowContext,
 };
 use workspace::{
     item::{Item, ItemEvent, TabContentParams},
     Workspace,
 };
 
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many fixtures, conflicts and warnings an import preview lists.
+const PREVIEW_ROWS: usize = 20;
+
 actions!(tungsten, [Patch]);
 
 pub fn init(cx: &mut AppContext) {
@@ -34,42 +53,1181 @@ pub fn init(cx: &mut AppContext) {
     .detach();
 }
 
+/// The columns of the patch table, which it can be sorted by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PatchColumn {
+    Id,
+    Name,
+    Type,
+    Mode,
+    Universe,
+    Address,
+    Split,
+    Modifiers,
+}
+
+impl PatchColumn {
+    const ALL: [PatchColumn; 8] = [
+        PatchColumn::Id,
+        PatchColumn::Name,
+        PatchColumn::Type,
+        PatchColumn::Mode,
+        PatchColumn::Universe,
+        PatchColumn::Address,
+        PatchColumn::Split,
+        PatchColumn::Modifiers,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            PatchColumn::Id => "ID",
+            PatchColumn::Name => "Name",
+            PatchColumn::Type => "Fixture type",
+            PatchColumn::Mode => "Mode",
+            PatchColumn::Universe => "Universe",
+            PatchColumn::Address => "Address",
+            PatchColumn::Split => "Split",
+            PatchColumn::Modifiers => "Modifiers",
+        }
+    }
+
+    fn element_name(self) -> &'static str {
+        match self {
+            PatchColumn::Id => "patch-id",
+            PatchColumn::Name => "patch-name",
+            PatchColumn::Type => "patch-type",
+            PatchColumn::Mode => "patch-mode",
+            PatchColumn::Universe => "patch-universe",
+            PatchColumn::Address => "patch-address",
+            PatchColumn::Split => "patch-split",
+            PatchColumn::Modifiers => "patch-modifiers",
+        }
+    }
+
+    fn width(self) -> f32 {
+        match self {
+            PatchColumn::Id => 5.,
+            PatchColumn::Name => 12.,
+            PatchColumn::Type => 16.,
+            PatchColumn::Mode => 10.,
+            PatchColumn::Universe => 6.,
+            PatchColumn::Address => 6.,
+            PatchColumn::Split => 7.,
+            PatchColumn::Modifiers => 16.,
+        }
+    }
+
+    fn compare(self, a: &PatchedFixture, b: &PatchedFixture) -> Ordering {
+        match self {
+            PatchColumn::Id => a.id.cmp(&b.id),
+            PatchColumn::Name => a.label().cmp(&b.label()),
+            PatchColumn::Type => a.profile.cmp(&b.profile),
+            PatchColumn::Mode => a.mode.cmp(&b.mode),
+            PatchColumn::Universe => a.address.cmp(&b.address),
+            PatchColumn::Address => a
+                .address
+                .map(|address| (address.address, address.universe))
+                .cmp(&b.address.map(|address| (address.address, address.universe))),
+            PatchColumn::Split => a.split.cmp(&b.split),
+            PatchColumn::Modifiers => a.modifiers.to_string().cmp(&b.modifiers.to_string()),
+        }
+        .then(a.id.cmp(&b.id))
+    }
+}
+
+/// What the text being typed will change when it is committed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EditTarget {
+    Cell(FixtureId, PatchColumn),
+    /// The address the selected fixtures are moved to, one after another.
+    Readdress,
+    InsertCount,
+    InsertStart,
+    InsertOffset,
+    /// A list of fixtures and cells to select, like `101.1-101.12`.
+    Select,
+    /// The file to import from or export to.
+    Import,
+    Export,
+    /// The columns CSV files are read and written with.
+    CsvColumns,
+}
+
+struct Edit {
+    target: EditTarget,
+    text: String,
+}
+
+/// A profile in the library and its modes.
+struct ProfileChoice {
+    key: ProfileKey,
+    modes: Vec<ModeChoice>,
+}
+
+/// A patch read from a file, shown with what applying it would do until
+/// it is applied or cancelled.
+struct PendingImport {
+    path: PathBuf,
+    import: PatchImport,
+    preview: ImportPreview,
+}
+
+#[derive(Clone)]
+struct ModeChoice {
+    name: String,
+    footprint: u16,
+    cells: Vec<String>,
+}
+
+/// Lists the patched fixtures in a sortable table, where they can be
+/// added, deleted, renumbered, renamed, re-addressed, highlighted and
+/// given output modifiers, and imported from and exported to other
+/// consoles' files.
 pub struct PatchView {
     workspace: WeakView<Workspace>,
     focus_handle: FocusHandle,
+    store: Arc<PatchStore>,
+    fixtures: Vec<PatchedFixture>,
+    sort: PatchColumn,
+    descending: bool,
+    selected: BTreeSet<FixtureRef>,
+    /// Whether the selected fixtures and cells are brought up to full
+    /// white on stage.
+    highlight: bool,
+    /// The multi-cell fixtures whose cells are listed.
+    expanded: BTreeSet<FixtureId>,
+    editing: Option<Edit>,
+    profiles: Vec<ProfileChoice>,
+    /// The profile and mode new fixtures are added with.
+    new_profile: usize,
+    new_mode: usize,
+    /// How many fixtures are added at once, where the first goes, and how
+    /// far apart they start. The first goes after the last patched fixture
+    /// without a start, and an offset of 0 packs them together.
+    insert_count: u32,
+    insert_start: Option<DmxAddress>,
+    insert_offset: u16,
+    /// The fixtures each fixture shares addresses with.
+    conflicts: BTreeMap<FixtureId, Vec<FixtureId>>,
+    /// The columns CSV files are read and written with, or `None` to read
+    /// them from the header row and write the standard ones.
+    csv_columns: Option<CsvColumns>,
+    import: Option<PendingImport>,
+    notice: Option<String>,
+    error: Option<String>,
+    _refresh_task: Task<()>,
 }
 
 impl PatchView {
     pub fn new(workspace: &Workspace, cx: &mut ViewContext<Workspace>) -> View<Self> {
         cx.new_view(|cx: &mut ViewContext<Self>| {
             let focus_handle = cx.focus_handle();
+            let refresh_task = cx.spawn(|this, mut cx| async move {
+                loop {
+                    cx.background_executor().timer(REFRESH_INTERVAL).await;
+                    if this.update(&mut cx, |this, cx| this.refresh(cx)).is_err() {
+                        break;
+                    }
+                }
+            });
 
-            let this = Self {
+            let mut this = Self {
                 workspace: workspace.weak_handle(),
                 focus_handle,
+                store: PatchStore::try_global(cx).unwrap_or_default(),
+                fixtures: Vec::new(),
+                sort: PatchColumn::Id,
+                descending: false,
+                selected: BTreeSet::new(),
+                highlight: false,
+                expanded: BTreeSet::new(),
+                editing: None,
+                profiles: Vec::new(),
+                new_profile: 0,
+                new_mode: 0,
+                insert_count: 1,
+                insert_start: None,
+                insert_offset: 0,
+                conflicts: BTreeMap::new(),
+                csv_columns: None,
+                import: None,
+                notice: None,
+                error: None,
+                _refresh_task: refresh_task,
             };
-
+            this.refresh(cx);
             this
         })
     }
+
+    fn refresh(&mut self, cx: &mut ViewContext<Self>) {
+        self.fixtures = self.store.read(|patch| patch.fixtures().to_vec());
+        let (sort, descending) = (self.sort, self.descending);
+        self.fixtures.sort_by(|a, b| {
+            let ordering = sort.compare(a, b);
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        self.selected.retain(|selected| {
+            self.fixtures.iter().any(|fixture| {
+                fixture.id == selected.id
+                    && match selected.cell {
+                        Some(cell) => fixture.cell_name(cell).is_some(),
+                        None => true,
+                    }
+            })
+        });
+        self.update_highlight();
+        self.conflicts.clear();
+        for conflict in self.store.read(|patch| patch.conflicts()) {
+            let (first, second) = conflict.fixtures;
+            self.conflicts.entry(first).or_default().push(second);
+            self.conflicts.entry(second).or_default().push(first);
+        }
+        if let Some(pending) = &mut self.import {
+            pending.preview = self.store.read(|patch| pending.import.preview(patch));
+        }
+
+        // The library is imported in the background, so it may grow after
+        // the view is opened.
+        if self.store.read_library(|library| library.len()) != self.profiles.len() {
+            self.profiles = self.store.read_library(|library| {
+                library
+                    .profiles()
+                    .map(|profile| ProfileChoice {
+                        key: ProfileKey::new(&profile.manufacturer, &profile.model),
+                        modes: profile
+                            .modes
+                            .iter()
+                            .map(|mode| ModeChoice {
+                                name: mode.name.clone(),
+                                footprint: mode.footprint(),
+                                cells: mode.heads.iter().map(|head| head.name.clone()).collect(),
+                            })
+                            .collect(),
+                    })
+                    .collect()
+            });
+            self.new_profile = self.new_profile.min(self.profiles.len().saturating_sub(1));
+            self.new_mode = 0;
+        }
+        cx.notify();
+    }
+
+    fn update_patch(
+        &mut self,
+        f: impl FnOnce(&mut patch::Patch) -> Result<()>,
+        cx: &mut ViewContext<Self>,
+    ) {
+        self.error = self.store.update(f).err().map(|err| format!("{err:#}"));
+        self.refresh(cx);
+    }
+
+    fn sort_by(&mut self, column: PatchColumn, cx: &mut ViewContext<Self>) {
+        if self.sort == column {
+            self.descending = !self.descending;
+        } else {
+            self.sort = column;
+            self.descending = false;
+        }
+        self.refresh(cx);
+    }
+
+    fn toggle_selected(&mut self, fixture_ref: FixtureRef, cx: &mut ViewContext<Self>) {
+        if !self.selected.remove(&fixture_ref) {
+            self.selected.insert(fixture_ref);
+        }
+        self.update_highlight();
+        cx.notify();
+    }
+
+    fn toggle_highlight(&mut self, cx: &mut ViewContext<Self>) {
+        self.highlight = !self.highlight;
+        if self.highlight {
+            self.update_highlight();
+        } else {
+            self.store.update_levels(|levels| levels.clear_all());
+        }
+        cx.notify();
+    }
+
+    /// Moves the highlight to the selected fixtures and cells.
+    fn update_highlight(&self) {
+        if !self.highlight {
+            return;
+        }
+        self.store.update_levels(|levels| {
+            levels.clear_all();
+            for fixture_ref in &self.selected {
+                levels.highlight(*fixture_ref);
+            }
+        });
+    }
+
+    fn toggle_expanded(&mut self, id: FixtureId, cx: &mut ViewContext<Self>) {
+        if !self.expanded.remove(&id) {
+            self.expanded.insert(id);
+        }
+        cx.notify();
+    }
+
+    /// The fixtures that are selected or have a selected cell, in the
+    /// order they are shown.
+    fn selected_ids(&self) -> Vec<FixtureId> {
+        self.fixtures
+            .iter()
+            .map(|fixture| fixture.id)
+            .filter(|id| self.selected.iter().any(|selected| selected.id == *id))
+            .collect()
+    }
+
+    fn next_profile(&mut self, cx: &mut ViewContext<Self>) {
+        if !self.profiles.is_empty() {
+            self.new_profile = (self.new_profile + 1) % self.profiles.len();
+            self.new_mode = 0;
+        }
+        cx.notify();
+    }
+
+    fn next_mode(&mut self, cx: &mut ViewContext<Self>) {
+        if let Some(profile) = self.profiles.get(self.new_profile) {
+            self.new_mode = (self.new_mode + 1) % profile.modes.len().max(1);
+        }
+        cx.notify();
+    }
+
+    /// Adds fixtures of the chosen profile and mode at the first free
+    /// addresses from the chosen start.
+    fn add_fixtures(&mut self, cx: &mut ViewContext<Self>) {
+        let Some(profile) = self.profiles.get(self.new_profile) else {
+            return;
+        };
+        let Some(mode) = profile.modes.get(self.new_mode).cloned() else {
+            return;
+        };
+        let key = profile.key.clone();
+        let (count, start, offset) = (self.insert_count, self.insert_start, self.insert_offset);
+        self.update_patch(
+            |patch| {
+                let start = match start {
+                    Some(start) => start,
+                    None => patch.next_address(mode.footprint)?,
+                };
+                let template = PatchedFixture {
+                    id: patch.next_id(),
+                    name: String::new(),
+                    profile: key,
+                    mode: mode.name,
+                    footprint: mode.footprint,
+                    address: None,
+                    split: None,
+                    cells: mode.cells,
+                    modifiers: OutputModifiers::default(),
+                    rdm_uid: None,
+                };
+                patch.insert_fixtures(&template, count, start, offset)?;
+                Ok(())
+            },
+            cx,
+        );
+    }
+
+    fn delete_selected(&mut self, cx: &mut ViewContext<Self>) {
+        let selected = self.selected_ids();
+        self.selected.clear();
+        self.update_patch(
+            |patch| {
+                for id in selected {
+                    patch.remove(id);
+                }
+                Ok(())
+            },
+            cx,
+        );
+    }
+
+    /// Switches a fixture to the next mode of its profile.
+    fn next_fixture_mode(&mut self, fixture: &PatchedFixture, cx: &mut ViewContext<Self>) {
+        let Some(profile) = self
+            .profiles
+            .iter()
+            .find(|profile| profile.key == fixture.profile)
+        else {
+            return;
+        };
+        let index = profile
+            .modes
+            .iter()
+            .position(|mode| mode.name == fixture.mode)
+            .map_or(0, |index| (index + 1) % profile.modes.len());
+        let Some(mode) = profile.modes.get(index).cloned() else {
+            return;
+        };
+        let id = fixture.id;
+        self.update_patch(
+            |patch| patch.set_mode(id, mode.name, mode.footprint, mode.cells),
+            cx,
+        );
+    }
+
+    fn read_import(&mut self, path: PathBuf, cx: &mut ViewContext<Self>) {
+        let import = self
+            .store
+            .read_library(|library| PatchImport::read(&path, self.csv_columns.as_ref(), library));
+        match import {
+            Ok(import) => {
+                let preview = self.store.read(|patch| import.preview(patch));
+                self.import = Some(PendingImport {
+                    path,
+                    import,
+                    preview,
+                });
+                self.notice = None;
+                self.error = None;
+            }
+            Err(err) => self.error = Some(format!("{err:#}")),
+        }
+        cx.notify();
+    }
+
+    fn apply_import(&mut self, cx: &mut ViewContext<Self>) {
+        let Some(pending) = self.import.take() else {
+            return;
+        };
+        self.update_patch(|patch| pending.import.apply(patch), cx);
+        if self.error.is_none() {
+            self.notice = Some(format!(
+                "Imported {} fixtures from {}",
+                pending.import.fixtures.len(),
+                pending.path.display()
+            ));
+        } else {
+            self.import = Some(pending);
+        }
+    }
+
+    fn cancel_import(&mut self, cx: &mut ViewContext<Self>) {
+        self.import = None;
+        cx.notify();
+    }
+
+    fn export(&mut self, path: PathBuf, cx: &mut ViewContext<Self>) {
+        let result = self.store.read(|patch| {
+            patch::exchange::export(patch, &path, self.csv_columns.as_ref()).map(|()| patch.len())
+        });
+        match result {
+            Ok(count) => {
+                self.notice = Some(format!("Exported {count} fixtures to {}", path.display()));
+                self.error = None;
+            }
+            Err(err) => self.error = Some(format!("{err:#}")),
+        }
+        cx.notify();
+    }
+
+    fn start_edit(&mut self, target: EditTarget, text: String, cx: &mut ViewContext<Self>) {
+        self.editing = Some(Edit { target, text });
+        cx.focus(&self.focus_handle);
+        cx.notify();
+    }
+
+    fn key_down(&mut self, event: &KeyDownEvent, cx: &mut ViewContext<Self>) {
+        let Some(edit) = &mut self.editing else {
+            return;
+        };
+        let keystroke = &event.keystroke;
+        match keystroke.key.as_str() {
+            "enter" => self.commit_edit(cx),
+            "escape" => self.editing = None,
+            "backspace" => {
+                edit.text.pop();
+            }
+            _ => {
+                if let Some(text) = &keystroke.ime_key {
+                    if !keystroke.modifiers.control && !keystroke.modifiers.platform {
+                        edit.text.push_str(text);
+                    }
+                }
+            }
+        }
+        cx.stop_propagation();
+        cx.notify();
+    }
+
+    fn commit_edit(&mut self, cx: &mut ViewContext<Self>) {
+        let Some(edit) = self.editing.take() else {
+            return;
+        };
+        let text = edit.text.trim().to_string();
+        match edit.target {
+            EditTarget::Cell(id, PatchColumn::Id) => self.update_patch(
+                |patch| {
+                    let new_id = text.parse()?;
+                    patch.renumber(id, new_id)
+                },
+                cx,
+            ),
+            EditTarget::Cell(id, PatchColumn::Name) => {
+                self.update_patch(|patch| patch.set_name(id, text), cx)
+            }
+            EditTarget::Cell(id, PatchColumn::Universe) => self.update_patch(
+                |patch| {
+                    let universe = text
+                        .parse()
+                        .with_context(|| format!("invalid universe \"{text}\""))?;
+                    let address = fixture_address(patch, id)?.map_or(1, |address| address.address);
+                    patch.readdress(id, Some(DmxAddress::new(universe, address)?))
+                },
+                cx,
+            ),
+            EditTarget::Cell(id, PatchColumn::Address) => self.update_patch(
+                |patch| {
+                    let address = if text.is_empty() {
+                        None
+                    } else if text.contains('/') {
+                        Some(text.parse()?)
+                    } else {
+                        let address = text
+                            .parse()
+                            .with_context(|| format!("invalid address \"{text}\""))?;
+                        let universe =
+                            fixture_address(patch, id)?.map_or(1, |address| address.universe);
+                        Some(DmxAddress::new(universe, address)?)
+                    };
+                    patch.readdress(id, address)
+                },
+                cx,
+            ),
+            EditTarget::Cell(id, PatchColumn::Split) => self.update_patch(
+                |patch| {
+                    let split = if text.is_empty() || text == "-" {
+                        None
+                    } else {
+                        Some(text.parse()?)
+                    };
+                    patch.set_split(id, split)
+                },
+                cx,
+            ),
+            EditTarget::Cell(id, PatchColumn::Modifiers) => self.update_patch(
+                |patch| {
+                    let modifiers = text.parse()?;
+                    patch.set_modifiers(id, modifiers)
+                },
+                cx,
+            ),
+            EditTarget::Cell(_, column) => {
+                self.error = Some(format!("{} cannot be typed in", column.title()));
+                cx.notify();
+            }
+            EditTarget::Readdress => {
+                // In the order they are shown, so that sorting decides the
+                // order they are addressed in.
+                let ids = self.selected_ids();
+                self.update_patch(
+                    |patch| {
+                        let start = text.parse()?;
+                        patch.readdress_from(&ids, start)
+                    },
+                    cx,
+                );
+            }
+            EditTarget::Select => {
+                match self.store.read(|patch| patch.select(&text)) {
+                    Ok(refs) => self.selected = refs.into_iter().collect(),
+                    Err(err) => self.error = Some(format!("{err:#}")),
+                }
+                self.update_highlight();
+                cx.notify();
+            }
+            EditTarget::InsertCount => {
+                match text.parse() {
+                    Ok(count) => self.insert_count = count,
+                    Err(_) => self.error = Some(format!("invalid count \"{text}\"")),
+                }
+                cx.notify();
+            }
+            EditTarget::InsertStart => {
+                if text.is_empty() {
+                    self.insert_start = None;
+                } else {
+                    match text.parse() {
+                        Ok(start) => self.insert_start = Some(start),
+                        Err(err) => self.error = Some(format!("{err:#}")),
+                    }
+                }
+                cx.notify();
+            }
+            EditTarget::InsertOffset => {
+                match text.parse() {
+                    Ok(offset) => self.insert_offset = offset,
+                    Err(_) => self.error = Some(format!("invalid offset \"{text}\"")),
+                }
+                cx.notify();
+            }
+            EditTarget::Import => self.read_import(PathBuf::from(text), cx),
+            EditTarget::Export => self.export(PathBuf::from(text), cx),
+            EditTarget::CsvColumns => {
+                if text.is_empty() {
+                    self.csv_columns = None;
+                } else {
+                    match text.parse() {
+                        Ok(columns) => self.csv_columns = Some(columns),
+                        Err(err) => {
+                            self.error = Some(format!("{err:#}"));
+                            cx.notify();
+                            return;
+                        }
+                    }
+                }
+                // The file being imported is read again with the new
+                // columns.
+                match self.import.as_ref().map(|pending| pending.path.clone()) {
+                    Some(path) => self.read_import(path, cx),
+                    None => cx.notify(),
+                }
+            }
+        }
+    }
+
+    /// A cell that is typed into when clicked.
+    fn render_editable(
+        &self,
+        ix: usize,
+        fixture: &PatchedFixture,
+        column: PatchColumn,
+        text: String,
+        color: Color,
+        cx: &mut ViewContext<Self>,
+    ) -> impl IntoElement {
+        let target = EditTarget::Cell(fixture.id, column);
+        cell(
+            column.width(),
+            self.render_edit_button((column.element_name(), ix), target, text, color, cx),
+        )
+    }
+
+    fn render_edit_button(
+        &self,
+        id: impl Into<gpui::ElementId>,
+        target: EditTarget,
+        text: String,
+        color: Color,
+        cx: &mut ViewContext<Self>,
+    ) -> AnyElement {
+        // Buttons that start an action are typed into from empty, and
+        // cells from their current value.
+        let initial = match target {
+            EditTarget::Readdress
+            | EditTarget::Select
+            | EditTarget::Import
+            | EditTarget::Export => String::new(),
+            _ => text.clone(),
+        };
+        match &self.editing {
+            Some(edit) if edit.target == target => div()
+                .px_1()
+                .border_1()
+                .border_color(cx.theme().colors().border_focused)
+                .child(Label::new(format!("{}|", edit.text)))
+                .into_any_element(),
+            _ => Button::new(id, text)
+                .color(color)
+                .on_click(
+                    cx.listener(move |this, _, cx| this.start_edit(target, initial.clone(), cx)),
+                )
+                .into_any_element(),
+        }
+    }
+
+    fn render_row(
+        &self,
+        ix: usize,
+        fixture: &PatchedFixture,
+        cx: &mut ViewContext<Self>,
+    ) -> impl IntoElement {
+        let id = fixture.id;
+        let selected = self.selected.contains(&FixtureRef::fixture(id));
+        let expanded = self.expanded.contains(&id);
+        let has_profile = self
+            .profiles
+            .iter()
+            .any(|profile| profile.key == fixture.profile);
+        let conflicts = self.conflicts.get(&id);
+        let address_color = if conflicts.is_some() {
+            Color::Warning
+        } else {
+            Color::Default
+        };
+
+        let row = h_flex()
+            .gap_4()
+            .when(selected, |this| this.bg(cx.theme().colors().element_active))
+            .child(self.render_select(("patch-select", ix), FixtureRef::fixture(id), cx))
+            .child(self.render_editable(
+                ix,
+                fixture,
+                PatchColumn::Id,
+                id.to_string(),
+                Color::Default,
+                cx,
+            ))
+            .child(self.render_editable(
+                ix,
+                fixture,
+                PatchColumn::Name,
+                fixture.name.clone(),
+                Color::Default,
+                cx,
+            ))
+            .child(cell(
+                PatchColumn::Type.width(),
+                Label::new(fixture.profile.to_string()).color(if has_profile {
+                    Color::Default
+                } else {
+                    Color::Muted
+                }),
+            ))
+            .child(cell(
+                PatchColumn::Mode.width(),
+                Button::new((PatchColumn::Mode.element_name(), ix), fixture.mode.clone())
+                    .disabled(!has_profile)
+                    .on_click(cx.listener({
+                        let fixture = fixture.clone();
+                        move |this, _, cx| this.next_fixture_mode(&fixture, cx)
+                    })),
+            ))
+            .child(
+                self.render_editable(
+                    ix,
+                    fixture,
+                    PatchColumn::Universe,
+                    fixture
+                        .address
+                        .map_or("-".to_string(), |address| address.universe.to_string()),
+                    address_color,
+                    cx,
+                ),
+            )
+            .child(
+                self.render_editable(
+                    ix,
+                    fixture,
+                    PatchColumn::Address,
+                    fixture
+                        .address
+                        .map_or("-".to_string(), |address| address.address.to_string()),
+                    address_color,
+                    cx,
+                ),
+            )
+            .child(
+                self.render_editable(
+                    ix,
+                    fixture,
+                    PatchColumn::Split,
+                    fixture
+                        .split
+                        .map_or("-".to_string(), |split| split.to_string()),
+                    address_color,
+                    cx,
+                ),
+            )
+            .child(self.render_editable(
+                ix,
+                fixture,
+                PatchColumn::Modifiers,
+                if fixture.modifiers.is_empty() {
+                    "-".to_string()
+                } else {
+                    fixture.modifiers.to_string()
+                },
+                Color::Default,
+                cx,
+            ))
+            .child(cell(
+                5.,
+                Label::new(fixture.footprint.to_string()).color(Color::Muted),
+            ))
+            .when(!fixture.cells.is_empty(), |this| {
+                this.child(
+                    Button::new(
+                        ("patch-cells", ix),
+                        format!(
+                            "{} cells {}",
+                            fixture.cells.len(),
+                            if expanded { "▾" } else { "▸" }
+                        ),
+                    )
+                    .selected(expanded)
+                    .on_click(cx.listener(move |this, _, cx| this.toggle_expanded(id, cx))),
+                )
+            })
+            .when_some(conflicts, |this, conflicts| {
+                let ids = conflicts
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                this.child(Label::new(format!("Overlaps {ids}")).color(Color::Warning))
+            });
+
+        v_flex().gap_2().child(row).when(expanded, |this| {
+            this.children(fixture.refs().skip(1).map(|fixture_ref| {
+                let cell_ix = ix << 16 | usize::from(fixture_ref.cell.unwrap_or_default());
+                let selected = self.selected.contains(&fixture_ref);
+                h_flex()
+                    .gap_4()
+                    .when(selected, |this| this.bg(cx.theme().colors().element_active))
+                    .child(self.render_select(("patch-cell-select", cell_ix), fixture_ref, cx))
+                    .child(cell(
+                        PatchColumn::Id.width(),
+                        Label::new(fixture_ref.to_string()).color(Color::Muted),
+                    ))
+                    .child(cell(
+                        PatchColumn::Name.width(),
+                        Label::new(
+                            fixture_ref
+                                .cell
+                                .and_then(|cell| fixture.cell_name(cell))
+                                .unwrap_or_default()
+                                .to_string(),
+                        )
+                        .color(Color::Muted),
+                    ))
+            }))
+        })
+    }
+
+    /// The dot a fixture or cell is selected and deselected with.
+    fn render_select(
+        &self,
+        id: impl Into<gpui::ElementId>,
+        fixture_ref: FixtureRef,
+        cx: &mut ViewContext<Self>,
+    ) -> impl IntoElement {
+        let selected = self.selected.contains(&fixture_ref);
+        div()
+            .id(id)
+            .w(rems(1.))
+            .child(Indicator::dot().color(if selected {
+                Color::Accent
+            } else {
+                Color::Muted
+            }))
+            .on_click(cx.listener(move |this, _, cx| this.toggle_selected(fixture_ref, cx)))
+    }
+
+    fn render_toolbar(&self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        let profile = self.profiles.get(self.new_profile);
+        let mode = profile.and_then(|profile| profile.modes.get(self.new_mode));
+
+        h_flex()
+            .gap_2()
+            .child(Label::new("Type").color(Color::Muted))
+            .child(
+                Button::new(
+                    "patch-new-profile",
+                    profile.map_or("No profiles".to_string(), |profile| profile.key.to_string()),
+                )
+                .on_click(cx.listener(|this, _, cx| this.next_profile(cx))),
+            )
+            .child(Label::new("Mode").color(Color::Muted))
+            .child(
+                Button::new(
+                    "patch-new-mode",
+                    mode.map_or(String::new(), |mode| {
+                        format!("{} ({})", mode.name, mode.footprint)
+                    }),
+                )
+                .on_click(cx.listener(|this, _, cx| this.next_mode(cx))),
+            )
+            .child(
+                Button::new("patch-add", "Add")
+                    .disabled(mode.is_none())
+                    .on_click(cx.listener(|this, _, cx| this.add_fixtures(cx))),
+            )
+            .child(self.render_edit_button(
+                "patch-insert-count",
+                EditTarget::InsertCount,
+                self.insert_count.to_string(),
+                Color::Default,
+                cx,
+            ))
+            .child(Label::new("at").color(Color::Muted))
+            .child(
+                self.render_edit_button(
+                    "patch-insert-start",
+                    EditTarget::InsertStart,
+                    self.insert_start
+                        .map_or("next".to_string(), |start| start.to_string()),
+                    Color::Default,
+                    cx,
+                ),
+            )
+            .child(Label::new("offset").color(Color::Muted))
+            .child(self.render_edit_button(
+                "patch-insert-offset",
+                EditTarget::InsertOffset,
+                self.insert_offset.to_string(),
+                Color::Default,
+                cx,
+            ))
+            .child(self.render_edit_button(
+                "patch-readdress",
+                EditTarget::Readdress,
+                "Re-address".to_string(),
+                Color::Default,
+                cx,
+            ))
+            .child(self.render_edit_button(
+                "patch-select-list",
+                EditTarget::Select,
+                format!("Select ({})", self.selected.len()),
+                Color::Default,
+                cx,
+            ))
+            .child(
+                Button::new("patch-highlight", "Highlight")
+                    .selected(self.highlight)
+                    .on_click(cx.listener(|this, _, cx| this.toggle_highlight(cx))),
+            )
+            .child(
+                Button::new(
+                    "patch-delete",
+                    format!("Delete {}", self.selected_ids().len()),
+                )
+                .disabled(self.selected.is_empty())
+                .on_click(cx.listener(|this, _, cx| this.delete_selected(cx))),
+            )
+            .when(!self.conflicts.is_empty(), |this| {
+                this.child(
+                    Label::new(format!("{} fixtures overlap", self.conflicts.len()))
+                        .color(Color::Warning),
+                )
+            })
+            .when_some(self.error.clone(), |this, error| {
+                this.child(Label::new(error).color(Color::Error))
+            })
+    }
+
+    /// Where the patch is imported from and exported to, as CSV or USITT
+    /// ASCII depending on the file's extension.
+    fn render_exchange_bar(&self, cx: &mut ViewContext<Self>) -> impl IntoElement {
+        h_flex()
+            .gap_2()
+            .child(self.render_edit_button(
+                "patch-import",
+                EditTarget::Import,
+                "Import…".to_string(),
+                Color::Default,
+                cx,
+            ))
+            .child(self.render_edit_button(
+                "patch-export",
+                EditTarget::Export,
+                "Export…".to_string(),
+                Color::Default,
+                cx,
+            ))
+            .child(Label::new("CSV columns").color(Color::Muted))
+            .child(
+                self.render_edit_button(
+                    "patch-csv-columns",
+                    EditTarget::CsvColumns,
+                    self.csv_columns
+                        .as_ref()
+                        .map_or("From header".to_string(), |columns| columns.to_string()),
+                    Color::Default,
+                    cx,
+                ),
+            )
+            .when_some(self.notice.clone(), |this, notice| {
+                this.child(Label::new(notice).color(Color::Muted))
+            })
+    }
+
+    /// The fixtures in a file being imported, with the overlaps they would
+    /// have and anything in the file that could not be read.
+    fn render_import(
+        &self,
+        pending: &PendingImport,
+        cx: &mut ViewContext<Self>,
+    ) -> impl IntoElement {
+        let import = &pending.import;
+        let preview = &pending.preview;
+        let conflicted = preview
+            .conflicts
+            .iter()
+            .flat_map(|conflict| [conflict.fixtures.0, conflict.fixtures.1])
+            .collect::<BTreeSet<_>>();
+
+        v_flex()
+            .gap_1()
+            .p_2()
+            .border_1()
+            .border_color(cx.theme().colors().border)
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(Label::new(format!(
+                        "Import {} fixtures from {}",
+                        import.fixtures.len(),
+                        pending.path.display()
+                    )))
+                    .child(
+                        Label::new(format!(
+                            "{} new, {} replacing patched fixtures",
+                            preview.added.len(),
+                            preview.replaced.len()
+                        ))
+                        .color(Color::Muted),
+                    )
+                    .child(
+                        Button::new("patch-import-apply", "Apply")
+                            .disabled(import.fixtures.is_empty() || preview.error.is_some())
+                            .on_click(cx.listener(|this, _, cx| this.apply_import(cx))),
+                    )
+                    .child(
+                        Button::new("patch-import-cancel", "Cancel")
+                            .on_click(cx.listener(|this, _, cx| this.cancel_import(cx))),
+                    ),
+            )
+            .when_some(preview.error.clone(), |this, error| {
+                this.child(Label::new(error).color(Color::Error))
+            })
+            .children(preview.conflicts.iter().take(PREVIEW_ROWS).map(|conflict| {
+                Label::new(format!(
+                    "{} and {} overlap at {}–{}",
+                    conflict.fixtures.0, conflict.fixtures.1, conflict.from, conflict.to
+                ))
+                .color(Color::Warning)
+            }))
+            .children(render_more(preview.conflicts.len()))
+            .children(
+                import
+                    .warnings
+                    .iter()
+                    .take(PREVIEW_ROWS)
+                    .map(|warning| Label::new(warning.clone()).color(Color::Muted)),
+            )
+            .children(render_more(import.warnings.len()))
+            .children(import.fixtures.iter().take(PREVIEW_ROWS).map(|fixture| {
+                h_flex()
+                    .gap_4()
+                    .child(cell(
+                        PatchColumn::Id.width(),
+                        Label::new(fixture.id.to_string()),
+                    ))
+                    .child(cell(
+                        PatchColumn::Name.width(),
+                        Label::new(fixture.name.clone()),
+                    ))
+                    .child(cell(
+                        PatchColumn::Type.width(),
+                        Label::new(fixture.profile.to_string()),
+                    ))
+                    .child(cell(
+                        PatchColumn::Mode.width(),
+                        Label::new(fixture.mode.clone()),
+                    ))
+                    .child(cell(
+                        PatchColumn::Universe.width() + PatchColumn::Address.width(),
+                        Label::new(
+                            fixture
+                                .address
+                                .map_or("-".to_string(), |address| address.to_string()),
+                        )
+                        .color(if conflicted.contains(&fixture.id) {
+                            Color::Warning
+                        } else {
+                            Color::Default
+                        }),
+                    ))
+                    .when(preview.replaced.contains(&fixture.id), |this| {
+                        this.child(Label::new("Replaces a patched fixture").color(Color::Muted))
+                    })
+            }))
+            .children(render_more(import.fixtures.len()))
+    }
+}
+
+/// Says how many items a preview list leaves out.
+fn render_more(len: usize) -> Option<Label> {
+    (len > PREVIEW_ROWS)
+        .then(|| Label::new(format!("and {} more", len - PREVIEW_ROWS)).color(Color::Muted))
+}
+
+fn cell(width: f32, child: impl IntoElement) -> impl IntoElement {
+    div().w(rems(width)).child(child)
+}
+
+fn fixture_address(patch: &patch::Patch, id: FixtureId) -> Result<Option<DmxAddress>> {
+    Ok(patch
+        .get(id)
+        .ok_or_else(|| anyhow!("fixture {id} is not patched"))?
+        .address)
 }
 
 impl Render for PatchView {
     fn render(&mut self, cx: &mut ViewContext<Self>) -> impl IntoElement {
-        h_flex()
+        let header = h_flex()
+            .gap_4()
+            .child(cell(1., Label::new("")))
+            .children(PatchColumn::ALL.into_iter().map(|column| {
+                let title = if self.sort != column {
+                    column.title().to_string()
+                } else if self.descending {
+                    format!("{} ▼", column.title())
+                } else {
+                    format!("{} ▲", column.title())
+                };
+                cell(
+                    column.width(),
+                    Button::new(("patch-sort", column as usize), title)
+                        .selected(self.sort == column)
+                        .on_click(cx.listener(move |this, _, cx| this.sort_by(column, cx))),
+                )
+            }))
+            .child(cell(5., Label::new("Footprint").color(Color::Muted)));
+
+        let fixtures = self.fixtures.clone();
+        v_flex()
             .size_full()
+            .p_4()
+            .gap_2()
             .bg(cx.theme().colors().tab_active_background)
             .track_focus(&self.focus_handle)
-            .child(
-                v_flex()
-                    .w_96()
-                    .gap_4()
-                    .mx_auto()
-                    .child(h_flex().justify_center().child(Label::new("Patch List"))),
+            .on_key_down(cx.listener(Self::key_down))
+            .child(self.render_toolbar(cx))
+            .child(self.render_exchange_bar(cx))
+            .when_some(self.import.as_ref(), |this, pending| {
+                this.child(self.render_import(pending, cx))
+            })
+            .child(header)
+            .when(fixtures.is_empty(), |this| {
+                this.child(Label::new("No fixtures are patched").color(Color::Muted))
+            })
+            .children(
+                fixtures
+                    .iter()
+                    .enumerate()
+                    .map(|(ix, fixture)| self.render_row(ix, fixture, cx)),
             )
     }
 }
 
+impl Drop for PatchView {
+    fn drop(&mut self) {
+        if self.highlight {
+            self.store.update_levels(|levels| levels.clear_all());
+        }
+    }
+}
+
 impl EventEmitter<ItemEvent> for PatchView {}
 
 impl FocusableView for PatchView {
@@ -25,6 +25,7 @@ fn main() {
         load_embedded_fonts(cx);
         theme::init(cx);
         workspace::init(app_state.clone(), cx);
+        patch::init(cx);
         dmx_output::init(cx);
         patch_ui::init(cx);
         cue_ui::init(cx);
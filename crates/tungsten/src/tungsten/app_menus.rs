@@ -10,6 +10,14 @@ pub fn app_menus() -> Vec<Menu<'static>> {
                 MenuItem::action("About Tungsten…", About),
                 MenuItem::action("Patches", patch_ui::Patch),
                 MenuItem::action("Cues", cue_ui::Cue),
+                MenuItem::action(
+                    "Channel Monitor",
+                    dmx_output::channel_monitor::ChannelMonitor,
+                ),
+                MenuItem::action("Output Status", dmx_output::output_status::OutputStatus),
+                MenuItem::action("Art-Net Nodes", dmx_output::node_browser::ArtNetNodes),
+                MenuItem::action("sACN Sources", dmx_output::sacn_sources::SacnSources),
+                MenuItem::action("RDM Devices", dmx_output::rdm_devices::RdmDevices),
                 MenuItem::action("Quit", Quit),
             ],
         },